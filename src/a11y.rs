@@ -0,0 +1,30 @@
+//! Screen reader support on top of egui's AccessKit tree: names for the
+//! custom-painted widgets, and live regions so that status changes such as a
+//! written flag or an error are announced.
+
+use eframe::egui;
+use egui::accesskit::Live;
+use egui::{Response, WidgetInfo, WidgetType};
+
+/// Names a custom-painted widget for assistive tech.
+pub fn describe(response: &Response, typ: WidgetType, label: impl ToString) {
+    response.widget_info(|| WidgetInfo::labeled(typ, label.to_string()));
+}
+
+/// A label read out whenever its text changes; `urgent` interrupts the
+/// screen reader and shows it in the error color.
+pub fn status(ui: &mut egui::Ui, text: impl Into<egui::RichText>, urgent: bool) -> Response {
+    let response = if urgent {
+        ui.colored_label(ui.visuals().error_fg_color, text)
+    } else {
+        ui.label(text.into())
+    };
+    let live = if urgent {
+        Live::Assertive
+    } else {
+        Live::Polite
+    };
+    ui.ctx()
+        .accesskit_node_builder(response.id, |node| node.set_live(live));
+    response
+}
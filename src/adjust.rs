@@ -0,0 +1,370 @@
+use image::{DynamicImage, Rgba, RgbaImage, imageops};
+use palette::{Clamp, FromColor, Lab, Lch, Srgb};
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+
+// Rec. 709 luma weights, used to desaturate around each pixel's gray.
+pub const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+// Fraction of pixels auto-levels lets clip at each end of the histogram.
+const LEVELS_CLIP: f32 = 0.005;
+
+// CLAHE splits the image into this many tiles along each axis.
+const CLAHE_TILES: u32 = 4;
+
+// CLAHE histogram bins are capped at this multiple of the average bin.
+const CLAHE_CLIP_LIMIT: f32 = 2.5;
+
+// Linear-light red/blue gain swing at full warm or cool temperature.
+const TEMPERATURE_SWING: f32 = 0.3;
+
+// Auto white balance never scales a channel by more than this (or its inverse).
+const MAX_WHITE_BALANCE_GAIN: f32 = 2.0;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Equalize {
+    #[default]
+    Off,
+    /// One lightness mapping for the whole image.
+    Global,
+    /// Contrast-limited adaptive equalization: per-tile mappings, blended.
+    Clahe,
+}
+
+impl Equalize {
+    pub const ALL: [Equalize; 3] = [Equalize::Off, Equalize::Global, Equalize::Clahe];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Equalize::Off => "Off",
+            Equalize::Global => "Global",
+            Equalize::Clahe => "Adaptive (CLAHE)",
+        }
+    }
+}
+
+/// Tone and color tweaks applied to the flag-sized image before matching.
+/// Brightness, contrast and saturation are -1.0–1.0 with 0.0 neutral.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Adjustments {
+    /// Gray-world white balance: scale channels so the average is neutral.
+    pub auto_white_balance: bool,
+    /// Stretch lightness so the darkest and brightest pixels span 0–255.
+    pub auto_levels: bool,
+    /// Histogram equalization of Lab lightness.
+    pub equalize: Equalize,
+    /// Linear-light gain in stops (each +1 doubles the light).
+    pub exposure: f32,
+    /// Linear-light gamma; above 1.0 lifts shadows and midtones.
+    pub gamma: f32,
+    /// -1.0 (cool, bluer) to 1.0 (warm, redder).
+    pub temperature: f32,
+    /// Hue rotation in degrees around the LCh hue circle.
+    pub hue_shift: f32,
+    pub brightness: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            auto_white_balance: false,
+            auto_levels: false,
+            equalize: Equalize::Off,
+            exposure: 0.0,
+            gamma: 1.0,
+            temperature: 0.0,
+            hue_shift: 0.0,
+            brightness: 0.0,
+            contrast: 0.0,
+            saturation: 0.0,
+        }
+    }
+}
+
+/// Unsharp mask applied to the full-size source before downscaling.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SharpenSettings {
+    pub enabled: bool,
+    /// How much of the high-pass detail is added back, 0.0–3.0.
+    pub amount: f32,
+    /// Gaussian sigma of the blur, in source pixels.
+    pub radius: f32,
+}
+
+impl Default for SharpenSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amount: 1.0,
+            radius: 1.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PosterizeSettings {
+    pub enabled: bool,
+    /// Levels kept per channel, 2–32.
+    pub levels: u8,
+}
+
+impl Default for PosterizeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            levels: 8,
+        }
+    }
+}
+
+/// Applies the automatic corrections; exposure, gamma and temperature in
+/// linear light; brightness (an offset of up to a full channel range),
+/// contrast (a gain of 0–2 around mid-gray) and saturation (a gain of 0–2
+/// away from luma) in sRGB; then the hue shift, in that order.
+pub fn apply(img: &DynamicImage, adjustments: Adjustments) -> DynamicImage {
+    if adjustments == Adjustments::default() {
+        return img.clone();
+    }
+
+    let gain = adjustments.exposure.exp2();
+    let inverse_gamma = 1.0 / adjustments.gamma.max(0.01);
+    let warmth = adjustments.temperature * TEMPERATURE_SWING;
+    let channel_gains = [gain * (1.0 + warmth), gain, gain * (1.0 - warmth)];
+    let tone: [[u8; 256]; 3] = channel_gains.map(|gain| {
+        std::array::from_fn(|c| {
+            linear_to_srgb((srgb_to_linear(c as u8) * gain).powf(inverse_gamma))
+        })
+    });
+
+    let offset = adjustments.brightness * 255.0;
+    let contrast = 1.0 + adjustments.contrast;
+    let saturation = 1.0 + adjustments.saturation;
+
+    let mut rgba = img.to_rgba8();
+    if adjustments.auto_white_balance {
+        white_balance(&mut rgba);
+    }
+    if adjustments.auto_levels {
+        stretch_levels(&mut rgba);
+    }
+    if adjustments.equalize != Equalize::Off {
+        equalize(&mut rgba, adjustments.equalize);
+    }
+
+    for pixel in rgba.pixels_mut() {
+        let Rgba([r, g, b, a]) = *pixel;
+        let rgb: [f32; 3] = std::array::from_fn(|c| {
+            (tone[c][[r, g, b][c] as usize] as f32 + offset - 128.0) * contrast + 128.0
+        });
+        let luma = LUMA[0] * rgb[0] + LUMA[1] * rgb[1] + LUMA[2] * rgb[2];
+        let mut rgb = rgb.map(|c| (luma + (c - luma) * saturation).round().clamp(0.0, 255.0) as u8);
+        if adjustments.hue_shift != 0.0 {
+            rgb = rotate_hue(rgb, adjustments.hue_shift);
+        }
+        *pixel = Rgba([rgb[0], rgb[1], rgb[2], a]);
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Rotates the LCh hue by `degrees`, keeping lightness and chroma.
+fn rotate_hue(rgb: [u8; 3], degrees: f32) -> [u8; 3] {
+    let mut lch = Lch::from_color(Srgb::new(rgb[0], rgb[1], rgb[2]).into_format::<f32>());
+    lch.hue += degrees;
+    // Rotating can leave the sRGB gamut, so clip back into it.
+    let rotated: Srgb<u8> = Srgb::from_color(lch).clamp().into_format();
+    [rotated.red, rotated.green, rotated.blue]
+}
+
+/// Gray-world white balance in linear light: each channel is scaled so its
+/// mean matches the mean of all three, which removes a uniform color cast.
+fn white_balance(rgba: &mut RgbaImage) {
+    let count = (rgba.width() * rgba.height()).max(1) as f32;
+    let mut sums = [0.0f32; 3];
+    for pixel in rgba.pixels() {
+        for c in 0..3 {
+            sums[c] += srgb_to_linear(pixel[c]);
+        }
+    }
+
+    let means = sums.map(|sum| sum / count);
+    let gray = (means[0] + means[1] + means[2]) / 3.0;
+    let gains = means.map(|mean| {
+        if mean > 0.0 {
+            (gray / mean).clamp(1.0 / MAX_WHITE_BALANCE_GAIN, MAX_WHITE_BALANCE_GAIN)
+        } else {
+            1.0
+        }
+    });
+    let tables: [[u8; 256]; 3] =
+        gains.map(|gain| std::array::from_fn(|c| linear_to_srgb(srgb_to_linear(c as u8) * gain)));
+
+    for pixel in rgba.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = tables[c][pixel[c] as usize];
+        }
+    }
+}
+
+/// Stretches all channels by the same ramp so the luma histogram, minus
+/// `LEVELS_CLIP` at each end, spans the full range without shifting hues.
+fn stretch_levels(rgba: &mut RgbaImage) {
+    let mut histogram = [0u32; 256];
+    for pixel in rgba.pixels() {
+        let luma =
+            LUMA[0] * pixel[0] as f32 + LUMA[1] * pixel[1] as f32 + LUMA[2] * pixel[2] as f32;
+        histogram[luma.round() as usize] += 1;
+    }
+
+    let clip = ((rgba.width() * rgba.height()) as f32 * LEVELS_CLIP) as u32;
+    let low = first_past(&histogram, clip, 0..256);
+    let high = first_past(&histogram, clip, (0..256).rev());
+    if high <= low {
+        return;
+    }
+
+    let scale = 255.0 / (high - low) as f32;
+    let ramp: [u8; 256] =
+        std::array::from_fn(|c| ((c as f32 - low as f32) * scale).round().clamp(0.0, 255.0) as u8);
+    for pixel in rgba.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = ramp[pixel[c] as usize];
+        }
+    }
+}
+
+/// Equalizes Lab lightness (in 256 bins) and leaves a and b alone, so colors
+/// keep their hue while the image uses the palette's full lightness range.
+fn equalize(rgba: &mut RgbaImage, mode: Equalize) {
+    let (w, h) = rgba.dimensions();
+    let labs: Vec<Lab> = rgba
+        .pixels()
+        .map(|p| Lab::from_color(Srgb::new(p[0], p[1], p[2]).into_format::<f32>()))
+        .collect();
+    let bins: Vec<usize> = labs
+        .iter()
+        .map(|lab| (lab.l * 2.55).round().clamp(0.0, 255.0) as usize)
+        .collect();
+
+    let lightness: Vec<f32> = match mode {
+        Equalize::Off => return,
+        Equalize::Global => {
+            let mapping = equalization_curve(bins.iter().copied(), f32::INFINITY);
+            bins.iter().map(|&bin| mapping[bin]).collect()
+        }
+        Equalize::Clahe => {
+            let tiles = CLAHE_TILES.min(w).min(h).max(1);
+            let (tile_w, tile_h) = (w.div_ceil(tiles), h.div_ceil(tiles));
+            let bins = &bins;
+            let curves: Vec<[f32; 256]> = (0..tiles * tiles)
+                .map(|tile| {
+                    let (tx, ty) = (tile % tiles, tile / tiles);
+                    let members = (ty * tile_h..((ty + 1) * tile_h).min(h)).flat_map(|y| {
+                        (tx * tile_w..((tx + 1) * tile_w).min(w))
+                            .map(move |x| bins[(y * w + x) as usize])
+                    });
+                    equalization_curve(members, CLAHE_CLIP_LIMIT)
+                })
+                .collect();
+
+            // Blend the four nearest tile curves by distance to tile centers.
+            let axis = |pos: u32, size: u32| {
+                let t = ((pos as f32 + 0.5) / size as f32 - 0.5).clamp(0.0, (tiles - 1) as f32);
+                let low = t.floor() as u32;
+                (low, (low + 1).min(tiles - 1), t - low as f32)
+            };
+            (0..w * h)
+                .map(|i| {
+                    let (x0, x1, fx) = axis(i % w, tile_w);
+                    let (y0, y1, fy) = axis(i / w, tile_h);
+                    let bin = bins[i as usize];
+                    let at = |tx: u32, ty: u32| curves[(ty * tiles + tx) as usize][bin];
+                    let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+                    let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+                    top * (1.0 - fy) + bottom * fy
+                })
+                .collect()
+        }
+    };
+
+    for ((pixel, lab), l) in rgba.pixels_mut().zip(labs).zip(lightness) {
+        let rgb: Srgb<u8> = Srgb::from_color(Lab::new(l, lab.a, lab.b))
+            .clamp()
+            .into_format();
+        pixel[0] = rgb.red;
+        pixel[1] = rgb.green;
+        pixel[2] = rgb.blue;
+    }
+}
+
+/// Cumulative-histogram mapping from lightness bin to Lab L (0–100). Bins
+/// above `clip_limit` times the average are clipped and the excess spread
+/// evenly, which keeps CLAHE from over-amplifying noise.
+fn equalization_curve(bins: impl Iterator<Item = usize>, clip_limit: f32) -> [f32; 256] {
+    let mut histogram = [0.0f32; 256];
+    let mut count = 0.0;
+    for bin in bins {
+        histogram[bin] += 1.0;
+        count += 1.0;
+    }
+    if count == 0.0 {
+        return std::array::from_fn(|bin| bin as f32 / 2.55);
+    }
+
+    let limit = clip_limit * count / 256.0;
+    let excess: f32 = histogram.iter().map(|&n| (n - limit).max(0.0)).sum();
+    for n in &mut histogram {
+        *n = n.min(limit) + excess / 256.0;
+    }
+
+    let mut cumulative = 0.0;
+    histogram.map(|n| {
+        cumulative += n;
+        cumulative / count * 100.0
+    })
+}
+
+/// The first level, walking `levels`, at which more than `clip` pixels have
+/// been passed.
+fn first_past(histogram: &[u32; 256], clip: u32, mut levels: impl Iterator<Item = usize>) -> usize {
+    let mut seen = 0;
+    levels
+        .find(|&level| {
+            seen += histogram[level];
+            seen > clip
+        })
+        .unwrap_or(0)
+}
+
+/// `source + amount * (source - blur(source))` per color channel, so edges
+/// gain the contrast the shrink to 100x66 would otherwise average away.
+pub fn unsharp_mask(source: &RgbaImage, settings: SharpenSettings) -> RgbaImage {
+    let blurred = imageops::blur(source, settings.radius.max(0.1));
+
+    let mut sharpened = source.clone();
+    for (pixel, blur) in sharpened.pixels_mut().zip(blurred.pixels()) {
+        for c in 0..3 {
+            let original = pixel[c] as f32;
+            let detail = original - blur[c] as f32;
+            pixel[c] = (original + settings.amount * detail)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+    }
+    sharpened
+}
+
+/// Snaps every channel to `levels` evenly spaced values, which flattens noise
+/// and JPEG ringing before palette matching.
+pub fn posterize(img: &DynamicImage, levels: u8) -> DynamicImage {
+    let steps = (levels.max(2) - 1) as f32;
+    let snap = |c: u8| ((c as f32 / 255.0 * steps).round() / steps * 255.0).round() as u8;
+
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let Rgba([r, g, b, a]) = *pixel;
+        *pixel = Rgba([snap(r), snap(g), snap(b), a]);
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
@@ -0,0 +1,232 @@
+use image::{Rgba, RgbaImage};
+
+use crate::IMAGE_WIDTH;
+
+// Largest per-channel difference for a pixel to count as a checkerboard cell.
+const CHECKER_TOLERANCE: i16 = 6;
+
+// Share of the border that must follow the pattern before the image is
+// treated as a baked-in checkerboard.
+const CHECKER_MIN_MATCH: f32 = 0.6;
+
+// Alpha at or above which a pixel belongs to the subject an outline traces.
+const OUTLINE_ALPHA: u8 = 128;
+
+// Chamfer distance-transform steps (3-4 approximates Euclidean distance x3).
+const CHAMFER_STRAIGHT: u32 = 3;
+const CHAMFER_DIAGONAL: u32 = 4;
+
+// Checker colors must be at least this light and this close to gray.
+const CHECKER_MIN_LUMA: u8 = 128;
+const CHECKER_MAX_TINT: u8 = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct AlphaSettings {
+    /// Color transparent pixels are composited over.
+    pub background: [u8; 3],
+    /// Treat alpha as on/off at `threshold` instead of blending, for sources
+    /// whose soft edges would otherwise pick up a fringe of background.
+    pub cutout: bool,
+    pub threshold: u8,
+    /// Treat a gray/white checkerboard painted into an opaque image (a
+    /// "transparent" screenshot) as transparency.
+    pub detect_checkerboard: bool,
+}
+
+impl Default for AlphaSettings {
+    fn default() -> Self {
+        Self {
+            background: [255, 255, 255],
+            cutout: false,
+            threshold: 128,
+            detect_checkerboard: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct OutlineSettings {
+    pub enabled: bool,
+    /// Stroke width in flag pixels.
+    pub width: u32,
+    /// Palette index of the stroke.
+    pub color: usize,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 1,
+            color: 0,
+        }
+    }
+}
+
+/// Draws a stroke in the palette color behind the subject of a transparent
+/// source, out to `width` flag pixels from its alpha edge. `None` for an
+/// opaque source.
+pub fn outline(
+    source: &RgbaImage,
+    settings: OutlineSettings,
+    palette: &[[u8; 3]],
+) -> Option<RgbaImage> {
+    if source.pixels().all(|pixel| pixel.0[3] == u8::MAX) {
+        return None;
+    }
+
+    // Flag pixels are this many source pixels wide.
+    let scale = source.width() as f32 / IMAGE_WIDTH as f32;
+    let reach = (settings.width as f32 * scale.max(1.0) * CHAMFER_STRAIGHT as f32).round() as u32;
+    let distance = chamfer_distance(source);
+
+    let [r, g, b] = palette[settings.color.min(palette.len() - 1)];
+    let mut out = source.clone();
+    for (pixel, &d) in out.pixels_mut().zip(&distance) {
+        if d > reach || pixel.0[3] == u8::MAX {
+            continue;
+        }
+        let Rgba([sr, sg, sb, sa]) = *pixel;
+        let alpha = sa as u32;
+        let over = |c: u8, stroke: u8| {
+            ((c as u32 * alpha + stroke as u32 * (255 - alpha) + 127) / 255) as u8
+        };
+        *pixel = Rgba([over(sr, r), over(sg, g), over(sb, b), u8::MAX]);
+    }
+    Some(out)
+}
+
+/// Chamfer distance (in `CHAMFER_STRAIGHT` units per pixel) from each pixel
+/// to the nearest subject pixel, by a forward and a backward sweep.
+fn chamfer_distance(source: &RgbaImage) -> Vec<u32> {
+    let (w, h) = (source.width() as usize, source.height() as usize);
+    let mut distance: Vec<u32> = source
+        .pixels()
+        .map(|pixel| {
+            if pixel.0[3] >= OUTLINE_ALPHA {
+                0
+            } else {
+                u32::MAX / 2
+            }
+        })
+        .collect();
+
+    let forward = [
+        (-1, 0, CHAMFER_STRAIGHT),
+        (-1, -1, CHAMFER_DIAGONAL),
+        (0, -1, CHAMFER_STRAIGHT),
+        (1, -1, CHAMFER_DIAGONAL),
+    ];
+    let backward = forward.map(|(dx, dy, cost)| (-dx, -dy, cost));
+    let mut sweep = |offsets: &[(i64, i64, u32); 4], x: usize, y: usize| {
+        let i = y * w + x;
+        for &(dx, dy, cost) in offsets {
+            let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+            if (0..w as i64).contains(&nx) && (0..h as i64).contains(&ny) {
+                let candidate = distance[ny as usize * w + nx as usize] + cost;
+                distance[i] = distance[i].min(candidate);
+            }
+        }
+    };
+    for y in 0..h {
+        for x in 0..w {
+            sweep(&forward, x, y);
+        }
+    }
+    for y in (0..h).rev() {
+        for x in (0..w).rev() {
+            sweep(&backward, x, y);
+        }
+    }
+    distance
+}
+
+/// Flattens `source` onto the background color, or `None` when it is fully
+/// opaque and has no checkerboard to remove.
+pub fn composite(source: &RgbaImage, settings: AlphaSettings) -> Option<RgbaImage> {
+    let mut image = source.clone();
+    let checker = settings
+        .detect_checkerboard
+        .then(|| Checkerboard::detect(source))
+        .flatten();
+    if let Some(checker) = checker {
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if checker.matches(x, y, pixel.0) {
+                pixel.0[3] = 0;
+            }
+        }
+    } else if source.pixels().all(|pixel| pixel.0[3] == u8::MAX) {
+        return None;
+    }
+
+    for pixel in image.pixels_mut() {
+        let alpha = if settings.cutout {
+            if pixel.0[3] >= settings.threshold {
+                255
+            } else {
+                0
+            }
+        } else {
+            pixel.0[3] as u32
+        };
+        for (channel, background) in pixel.0.iter_mut().zip(settings.background) {
+            *channel =
+                ((*channel as u32 * alpha + background as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+        pixel.0[3] = u8::MAX;
+    }
+    Some(image)
+}
+
+/// A two-color checkerboard anchored at the top-left corner.
+struct Checkerboard {
+    colors: [[u8; 3]; 2],
+    cell: u32,
+}
+
+impl Checkerboard {
+    /// Reads the pattern off the first row, then checks it holds along all
+    /// four edges, where a subject is least likely to cover it.
+    fn detect(image: &RgbaImage) -> Option<Self> {
+        let (w, h) = image.dimensions();
+        let rgb = |x, y| {
+            let [r, g, b, _] = image.get_pixel(x, y).0;
+            [r, g, b]
+        };
+
+        let first = rgb(0, 0);
+        let cell = (1..w).find(|&x| !close(rgb(x, 0), first))?;
+        let checker = Self {
+            colors: [first, rgb(cell, 0)],
+            cell,
+        };
+        if cell < 2 || !checker.colors.iter().all(|&c| is_light_gray(c)) {
+            return None;
+        }
+
+        let border = (0..w)
+            .flat_map(|x| [(x, 0), (x, h - 1)])
+            .chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]));
+        let (total, hits) = border.fold((0, 0), |(total, hits), (x, y)| {
+            let hit = checker.matches(x, y, image.get_pixel(x, y).0);
+            (total + 1, hits + hit as u32)
+        });
+        (hits as f32 >= total as f32 * CHECKER_MIN_MATCH).then_some(checker)
+    }
+
+    fn matches(&self, x: u32, y: u32, [r, g, b, _]: [u8; 4]) -> bool {
+        let parity = ((x / self.cell + y / self.cell) % 2) as usize;
+        close([r, g, b], self.colors[parity])
+    }
+}
+
+fn close(a: [u8; 3], b: [u8; 3]) -> bool {
+    a.iter()
+        .zip(b)
+        .all(|(&a, b)| (a as i16 - b as i16).abs() <= CHECKER_TOLERANCE)
+}
+
+fn is_light_gray([r, g, b]: [u8; 3]) -> bool {
+    let (min, max) = (r.min(g).min(b), r.max(g).max(b));
+    min >= CHECKER_MIN_LUMA && max - min <= CHECKER_MAX_TINT
+}
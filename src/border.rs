@@ -0,0 +1,111 @@
+use crate::overlay::Layer;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH, PALETTE_DARKEST, PALETTE_LIGHTEST};
+
+// Arm length of the corner brackets, in pixels.
+const CORNER_ARM: i32 = 10;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum BorderStyle {
+    #[default]
+    Off,
+    Solid,
+    /// Two rings with a gap of the flag showing between them.
+    Double,
+    /// L brackets in each corner with a dot in the accent color.
+    Corners,
+}
+
+impl BorderStyle {
+    pub const ALL: [BorderStyle; 4] = [
+        BorderStyle::Off,
+        BorderStyle::Solid,
+        BorderStyle::Double,
+        BorderStyle::Corners,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BorderStyle::Off => "Off",
+            BorderStyle::Solid => "Solid",
+            BorderStyle::Double => "Double",
+            BorderStyle::Corners => "Corner ornaments",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Border {
+    pub style: BorderStyle,
+    /// Line thickness in pixels.
+    pub width: u32,
+    /// Palette index of the main line.
+    pub color: usize,
+    /// Palette index of the inner ring (double) or the dots (corners).
+    pub accent: usize,
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Self {
+            style: BorderStyle::Off,
+            width: 1,
+            color: PALETTE_DARKEST,
+            accent: PALETTE_LIGHTEST,
+        }
+    }
+}
+
+pub fn draw(border: Border, layer: &mut Layer) {
+    let width = border.width.max(1) as i32;
+    match border.style {
+        BorderStyle::Off => {}
+        BorderStyle::Solid => ring(layer, 0, width, border.color),
+        BorderStyle::Double => {
+            ring(layer, 0, width, border.color);
+            ring(layer, 2 * width, width, border.accent);
+        }
+        BorderStyle::Corners => {
+            let (w, h) = (IMAGE_WIDTH as i32, IMAGE_HEIGHT as i32);
+            let arm = CORNER_ARM + width;
+            for (x0, y0, sx, sy) in [
+                (0, 0, 1, 1),
+                (w - 1, 0, -1, 1),
+                (0, h - 1, 1, -1),
+                (w - 1, h - 1, -1, -1),
+            ] {
+                for along in 0..arm {
+                    for across in 0..width {
+                        layer.set(x0 + sx * along, y0 + sy * across, border.color);
+                        layer.set(x0 + sx * across, y0 + sy * along, border.color);
+                    }
+                }
+                for dy in 0..2 {
+                    for dx in 0..2 {
+                        layer.set(
+                            x0 + sx * (width + 1 + dx),
+                            y0 + sy * (width + 1 + dy),
+                            border.accent,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A rectangular ring `thickness` pixels wide, `inset` pixels in from the
+/// flag edge.
+fn ring(layer: &mut Layer, inset: i32, thickness: i32, index: usize) {
+    let (w, h) = (IMAGE_WIDTH as i32, IMAGE_HEIGHT as i32);
+    for y in inset..h - inset {
+        for x in inset..w - inset {
+            let depth = (x - inset)
+                .min(y - inset)
+                .min(w - 1 - inset - x)
+                .min(h - 1 - inset - y);
+            if depth < thickness {
+                layer.set(x, y, index);
+            }
+        }
+    }
+}
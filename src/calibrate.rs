@@ -0,0 +1,150 @@
+//! Calibration against the game's rendering: a test flag with every palette
+//! cell as a block, measured back from an in-game screenshot, gives a
+//! per-channel correction for the lighting and tint the flag is shown under.
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::color::{ColorMetric, linear_to_srgb, srgb_to_linear, to_lab};
+use crate::palettes::Palette;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+// Fraction of each block's width and height averaged when measuring; the
+// margin keeps block edges, blurred by the game's filtering, out.
+const MEASURE_INSET: f32 = 0.5;
+// Lowest per-channel gain a fit may produce, so a dark measurement cannot
+// collapse a channel.
+const MIN_GAIN: f32 = 0.05;
+
+/// How palette colors appear in game: `rendered = gain * expected + offset`
+/// per channel, in linear light.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    pub gain: [f32; 3],
+    pub offset: [f32; 3],
+}
+
+impl Curve {
+    /// The color `rgb` is predicted to show as in game.
+    pub fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        std::array::from_fn(|c| {
+            linear_to_srgb(self.gain[c] * srgb_to_linear(rgb[c]) + self.offset[c])
+        })
+    }
+
+    /// Least-squares fit of each channel over the measured cells.
+    pub fn fit(expected: &[[u8; 3]], rendered: &[[u8; 3]]) -> Self {
+        let n = expected.len().min(rendered.len()) as f32;
+        let mut gain = [1.0; 3];
+        let mut offset = [0.0; 3];
+        for c in 0..3 {
+            let (mut sx, mut sy, mut sxx, mut sxy) = (0.0, 0.0, 0.0, 0.0);
+            for (e, r) in expected.iter().zip(rendered) {
+                let (x, y) = (srgb_to_linear(e[c]), srgb_to_linear(r[c]));
+                sx += x;
+                sy += y;
+                sxx += x * x;
+                sxy += x * y;
+            }
+            let variance = n * sxx - sx * sx;
+            if variance > f32::EPSILON {
+                gain[c] = ((n * sxy - sx * sy) / variance).max(MIN_GAIN);
+            }
+            offset[c] = (sy - gain[c] * sx) / n;
+        }
+        Self { gain, offset }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Calibration {
+    /// Match against the colors as the curve predicts them in game.
+    pub enabled: bool,
+    pub curve: Option<Curve>,
+}
+
+impl Calibration {
+    pub fn active(&self) -> Option<Curve> {
+        self.curve.filter(|_| self.enabled)
+    }
+}
+
+/// A fitted curve with its mean ΔE76 to the measurement, next to the ΔE of
+/// the uncorrected palette.
+pub struct Measurement {
+    pub curve: Curve,
+    pub before: f32,
+    pub after: f32,
+}
+
+/// Which block of the test flag pixel (`x`, `y`) is in; blocks follow the
+/// palette grid so cell `idx` sits where it does in the palette image.
+fn block(palette: &Palette, x: u32, y: u32) -> usize {
+    let col = x * palette.cols / IMAGE_WIDTH;
+    let row = y * palette.rows / IMAGE_HEIGHT;
+    (row * palette.cols + col) as usize
+}
+
+/// Row-major indices of the test flag.
+pub fn pattern(palette: &Palette) -> Vec<usize> {
+    (0..IMAGE_HEIGHT)
+        .flat_map(|y| (0..IMAGE_WIDTH).map(move |x| block(palette, x, y)))
+        .map(|idx| idx.min(palette.colors.len() - 1))
+        .collect()
+}
+
+/// Measures every block of the test flag in `shot`, a screenshot cropped to
+/// the flag, and fits the curve taking the palette to what was measured.
+pub fn measure(shot: &RgbaImage, palette: &Palette) -> Result<Measurement, String> {
+    let (w, h) = shot.dimensions();
+    if w < palette.cols * 2 || h < palette.rows * 2 {
+        return Err(format!(
+            "Screenshot crop is {w}x{h}; too small to tell the blocks apart"
+        ));
+    }
+
+    let block_w = w as f32 / palette.cols as f32;
+    let block_h = h as f32 / palette.rows as f32;
+    let rendered: Vec<[u8; 3]> = (0..palette.colors.len() as u32)
+        .map(|idx| {
+            let (col, row) = (idx % palette.cols, idx / palette.cols);
+            let inset_x = block_w * (1.0 - MEASURE_INSET) / 2.0;
+            let inset_y = block_h * (1.0 - MEASURE_INSET) / 2.0;
+            let x0 = (col as f32 * block_w + inset_x) as u32;
+            let y0 = (row as f32 * block_h + inset_y) as u32;
+            let x1 = (((col + 1) as f32 * block_w - inset_x) as u32).clamp(x0 + 1, w);
+            let y1 = (((row + 1) as f32 * block_h - inset_y) as u32).clamp(y0 + 1, h);
+
+            let mut sum = [0.0f32; 3];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pixel = shot.get_pixel(x, y);
+                    for (total, &channel) in sum.iter_mut().zip(&pixel.0[..3]) {
+                        *total += srgb_to_linear(channel);
+                    }
+                }
+            }
+            let count = ((x1 - x0) * (y1 - y0)) as f32;
+            sum.map(|total| linear_to_srgb(total / count))
+        })
+        .collect();
+
+    let curve = Curve::fit(&palette.colors, &rendered);
+    let mean_delta_e = |predict: &dyn Fn([u8; 3]) -> [u8; 3]| {
+        let total: f32 = palette
+            .colors
+            .iter()
+            .zip(&rendered)
+            .map(|(&expected, &measured)| {
+                ColorMetric::Cie76.distance(to_lab(predict(expected)), to_lab(measured))
+            })
+            .sum();
+        total / palette.colors.len() as f32
+    };
+
+    Ok(Measurement {
+        before: mean_delta_e(&|rgb| rgb),
+        after: mean_delta_e(&|rgb| curve.apply(rgb)),
+        curve,
+    })
+}
@@ -0,0 +1,355 @@
+//! Screen capture through GDI, and the overlay a region is picked on.
+
+use eframe::egui;
+use egui::{Color32, Rect, Sense, Stroke, TextureHandle, TextureOptions};
+use image::{RgbaImage, imageops};
+use serde::{Deserialize, Serialize};
+use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM, RECT};
+use windows_sys::Win32::Graphics::Gdi::{
+    BI_RGB, BITMAPINFO, BITMAPINFOHEADER, BitBlt, CAPTUREBLT, CreateCompatibleBitmap,
+    CreateCompatibleDC, DIB_RGB_COLORS, DeleteDC, DeleteObject, GetDC, GetDIBits, GetMonitorInfoW,
+    HBITMAP, HDC, MONITOR_DEFAULTTONEAREST, MONITORINFO, MonitorFromWindow, ReleaseDC, SRCCOPY,
+    SelectObject,
+};
+use windows_sys::Win32::Storage::Xps::PrintWindow;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, GetForegroundWindow, GetSystemMetrics, GetWindowRect, GetWindowTextLengthW,
+    GetWindowTextW, IsIconic, IsWindow, IsWindowVisible, PW_RENDERFULLCONTENT, SM_CXVIRTUALSCREEN,
+    SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+};
+
+// Dims the frozen screen outside the selection.
+const OVERLAY_DIM: Color32 = Color32::from_black_alpha(140);
+
+/// What a one-shot capture grabs.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CaptureTarget {
+    /// The monitor the active window is on.
+    #[default]
+    Screen,
+    ActiveWindow,
+    /// One window picked from the list, whether or not it is in front.
+    Window(HWND),
+}
+
+impl CaptureTarget {
+    /// The targets offered as-is; `Window` is offered with a window list.
+    pub const ALL: [CaptureTarget; 2] = [CaptureTarget::Screen, CaptureTarget::ActiveWindow];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CaptureTarget::Screen => "Current screen",
+            CaptureTarget::ActiveWindow => "Active window",
+            CaptureTarget::Window(_) => "Chosen window",
+        }
+    }
+}
+
+/// Visible, titled top-level windows that are not minimized, with their
+/// titles.
+pub fn windows() -> Vec<(HWND, String)> {
+    unsafe extern "system" fn collect(window: HWND, found: LPARAM) -> BOOL {
+        let found = unsafe { &mut *(found as *mut Vec<(HWND, String)>) };
+        if let Some(title) = window_title(window)
+            && unsafe { IsWindowVisible(window) != 0 && IsIconic(window) == 0 }
+        {
+            found.push((window, title));
+        }
+        1
+    }
+
+    let mut found: Vec<(HWND, String)> = Vec::new();
+    unsafe { EnumWindows(Some(collect), &mut found as *mut _ as LPARAM) };
+    found
+}
+
+/// The window's title, or `None` when it has none.
+pub fn window_title(window: HWND) -> Option<String> {
+    let len = unsafe { GetWindowTextLengthW(window) };
+    if len <= 0 {
+        return None;
+    }
+    let mut title = vec![0u16; len as usize + 1];
+    let copied = unsafe { GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32) };
+    let title = String::from_utf16_lossy(&title[..copied.max(0) as usize]);
+    (!title.trim().is_empty()).then_some(title)
+}
+
+/// What `window` currently draws, even when other windows cover it.
+pub fn window(window: HWND) -> Result<Capture, String> {
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    unsafe {
+        if IsWindow(window) == 0 {
+            return Err("The chosen window was closed".to_owned());
+        }
+        if IsIconic(window) != 0 {
+            return Err("The chosen window is minimized".to_owned());
+        }
+        GetWindowRect(window, &mut rect);
+    }
+    let (w, h) = (rect.right - rect.left, rect.bottom - rect.top);
+    if w <= 0 || h <= 0 {
+        return Err("Nothing to capture".to_owned());
+    }
+
+    let image = unsafe {
+        let screen = GetDC(0);
+        if screen == 0 {
+            return Err("Screen is not accessible".to_owned());
+        }
+        let memory = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, w, h);
+        let previous = SelectObject(memory, bitmap);
+        let printed = PrintWindow(window, memory, PW_RENDERFULLCONTENT) != 0;
+        SelectObject(memory, previous);
+        let image = read_bitmap(memory, bitmap, w, h);
+
+        DeleteObject(bitmap);
+        DeleteDC(memory);
+        ReleaseDC(0, screen);
+        image.filter(|_| printed)
+    };
+    Ok(Capture {
+        image: image.ok_or("Window capture failed")?,
+        origin: [rect.left, rect.top],
+    })
+}
+
+/// Captured pixels and where on the virtual screen they came from.
+pub struct Capture {
+    pub image: RgbaImage,
+    /// Virtual-screen position of the top-left pixel.
+    pub origin: [i32; 2],
+}
+
+/// Every monitor, as one image spanning the virtual screen.
+pub fn screen() -> Result<Capture, String> {
+    let (x, y, w, h) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    };
+    region(x, y, w, h)
+}
+
+/// The active window, or the monitor it is on.
+pub fn foreground(target: CaptureTarget) -> Result<Capture, String> {
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    let found = unsafe {
+        let window = GetForegroundWindow();
+        match target {
+            CaptureTarget::Window(chosen) => return self::window(chosen),
+            CaptureTarget::ActiveWindow => window != 0 && GetWindowRect(window, &mut rect) != 0,
+            CaptureTarget::Screen => {
+                let monitor = MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST);
+                let mut info: MONITORINFO = std::mem::zeroed();
+                info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+                let found = GetMonitorInfoW(monitor, &mut info) != 0;
+                rect = info.rcMonitor;
+                found
+            }
+        }
+    };
+    if !found {
+        return Err("No active window to capture".to_owned());
+    }
+    region(
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+    )
+}
+
+/// The `w`x`h` pixels at virtual-screen position (`x`, `y`).
+pub fn region(x: i32, y: i32, w: i32, h: i32) -> Result<Capture, String> {
+    if w <= 0 || h <= 0 {
+        return Err("Nothing to capture".to_owned());
+    }
+
+    let image = unsafe {
+        let screen = GetDC(0);
+        if screen == 0 {
+            return Err("Screen is not accessible".to_owned());
+        }
+        let memory = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, w, h);
+        let previous = SelectObject(memory, bitmap);
+        let copied = BitBlt(memory, 0, 0, w, h, screen, x, y, SRCCOPY | CAPTUREBLT) != 0;
+        SelectObject(memory, previous);
+        let image = read_bitmap(memory, bitmap, w, h);
+
+        DeleteObject(bitmap);
+        DeleteDC(memory);
+        ReleaseDC(0, screen);
+        image.filter(|_| copied)
+    };
+    Ok(Capture {
+        image: image.ok_or("Screen capture failed")?,
+        origin: [x, y],
+    })
+}
+
+/// The pixels of a `w`x`h` bitmap not selected into any DC, as opaque RGBA.
+unsafe fn read_bitmap(memory: HDC, bitmap: HBITMAP, w: i32, h: i32) -> Option<RgbaImage> {
+    let mut bgra = vec![0u8; w as usize * h as usize * 4];
+    let lines = unsafe {
+        let mut info: BITMAPINFO = std::mem::zeroed();
+        info.bmiHeader = BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: w,
+            // Negative height asks for top-down rows.
+            biHeight: -h,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB as u32,
+            ..std::mem::zeroed()
+        };
+        GetDIBits(
+            memory,
+            bitmap,
+            0,
+            h as u32,
+            bgra.as_mut_ptr().cast(),
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    };
+    if lines != h {
+        return None;
+    }
+
+    for pixel in bgra.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        pixel[3] = 255;
+    }
+    RgbaImage::from_raw(w as u32, h as u32, bgra)
+}
+
+pub enum RegionOutcome {
+    Open,
+    Cancelled,
+    Selected(RgbaImage),
+}
+
+/// A frozen screenshot shown over the whole screen for dragging out a
+/// rectangle.
+pub struct RegionPicker {
+    capture: Capture,
+    texture: TextureHandle,
+    drag_start: Option<egui::Pos2>,
+    /// Last dragged rectangle, in overlay points.
+    selection: Option<Rect>,
+}
+
+impl RegionPicker {
+    pub fn new(ctx: &egui::Context, capture: Capture) -> Self {
+        let image = &capture.image;
+        let texture = ctx.load_texture(
+            "region_capture",
+            egui::ColorImage::from_rgba_unmultiplied(
+                [image.width() as usize, image.height() as usize],
+                image.as_raw(),
+            ),
+            TextureOptions::LINEAR,
+        );
+        Self {
+            capture,
+            texture,
+            drag_start: None,
+            selection: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> RegionOutcome {
+        let ppp = ctx.pixels_per_point();
+        let [x, y] = self.capture.origin;
+        let (w, h) = self.capture.image.dimensions();
+        let builder = egui::ViewportBuilder::default()
+            .with_title("Capture region")
+            .with_position([x as f32 / ppp, y as f32 / ppp])
+            .with_inner_size([w as f32 / ppp, h as f32 / ppp])
+            .with_decorations(false)
+            .with_always_on_top();
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("region_capture"),
+            builder,
+            |ctx, _| {
+                if ctx.input(|i| i.key_pressed(egui::Key::Escape) || i.viewport().close_requested())
+                {
+                    return RegionOutcome::Cancelled;
+                }
+                // Dragging needs a mouse; Enter takes the whole screen.
+                if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    return RegionOutcome::Selected(self.capture.image.clone());
+                }
+
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none())
+                    .show(ctx, |ui| self.picker(ui))
+                    .inner
+            },
+        )
+    }
+
+    fn picker(&mut self, ui: &mut egui::Ui) -> RegionOutcome {
+        let rect = ui.max_rect();
+        let response = ui.interact(rect, ui.id().with("region"), Sense::drag());
+        if response.drag_started() {
+            self.drag_start = response.interact_pointer_pos();
+        }
+        if let Some((start, pos)) = self.drag_start.zip(response.interact_pointer_pos()) {
+            self.selection = Some(Rect::from_two_pos(start, pos).intersect(rect));
+        }
+
+        let full = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        let painter = ui.painter();
+        painter.image(self.texture.id(), rect, full, Color32::WHITE);
+        painter.rect_filled(rect, 0.0, OVERLAY_DIM);
+        painter.text(
+            rect.center_top() + egui::vec2(0.0, 24.0),
+            egui::Align2::CENTER_CENTER,
+            "Drag a rectangle to capture; Enter takes the whole screen, Esc cancels",
+            egui::FontId::proportional(18.0),
+            Color32::WHITE,
+        );
+
+        let Some(selection) = self.selection else {
+            return RegionOutcome::Open;
+        };
+        let to_uv = |pos: egui::Pos2| ((pos - rect.min) / rect.size()).to_pos2();
+        let uv = Rect::from_min_max(to_uv(selection.min), to_uv(selection.max));
+        painter.image(self.texture.id(), selection, uv, Color32::WHITE);
+        painter.rect_stroke(selection, 0.0, Stroke::new(1.0, Color32::WHITE));
+
+        if !response.drag_stopped() {
+            return RegionOutcome::Open;
+        }
+        self.drag_start = None;
+        let (w, h) = self.capture.image.dimensions();
+        let x0 = (uv.min.x * w as f32) as u32;
+        let y0 = (uv.min.y * h as f32) as u32;
+        let x1 = ((uv.max.x * w as f32) as u32).min(w);
+        let y1 = ((uv.max.y * h as f32) as u32).min(h);
+        if x1 <= x0 + 1 || y1 <= y0 + 1 {
+            return RegionOutcome::Open;
+        }
+        RegionOutcome::Selected(
+            imageops::crop_imm(&self.capture.image, x0, y0, x1 - x0, y1 - y0).to_image(),
+        )
+    }
+}
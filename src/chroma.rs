@@ -0,0 +1,58 @@
+use image::{Rgba, RgbaImage};
+use palette::Lab;
+
+use crate::color::{ColorMetric, to_lab};
+use crate::lab;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ChromaKey {
+    pub enabled: bool,
+    pub key: [u8; 3],
+    /// ΔE76 from `key` within which a pixel counts as screen.
+    pub tolerance: f32,
+    /// Palette index the keyed pixels are replaced with.
+    pub background: usize,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: [0x00, 0xB1, 0x40],
+            tolerance: 25.0,
+            background: 0,
+        }
+    }
+}
+
+/// Replaces every source pixel close to the key color with the background
+/// palette color, or makes it transparent when `cut_out` is set, at full
+/// resolution so the downscale softens the cut edge.
+pub fn apply(source: &RgbaImage, key: ChromaKey, palette: &[[u8; 3]], cut_out: bool) -> RgbaImage {
+    let target = to_lab(key.key);
+    let [r, g, b] = palette[key.background.min(palette.len() - 1)];
+    let fill = Rgba([r, g, b, 255]);
+
+    let mut keyed = source.clone();
+    let width = source.width() as usize;
+    let mut rgb = vec![[0u8; 3]; width];
+    let mut labs = vec![Lab::new(0.0, 0.0, 0.0); width];
+
+    for row in keyed.chunks_exact_mut(width * 4) {
+        for (pixel, out) in row.chunks_exact(4).zip(&mut rgb) {
+            *out = [pixel[0], pixel[1], pixel[2]];
+        }
+        lab::convert_row(&rgb, &mut labs);
+
+        for (pixel, lab) in row.chunks_exact_mut(4).zip(&labs) {
+            if ColorMetric::Cie76.distance(*lab, target) <= key.tolerance {
+                if cut_out {
+                    pixel[3] = 0;
+                } else {
+                    pixel.copy_from_slice(&fill.0);
+                }
+            }
+        }
+    }
+    keyed
+}
@@ -0,0 +1,749 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use image::{DynamicImage, GenericImageView};
+use palette::{FromColor, Lab, Srgb};
+use rayon::prelude::*;
+
+use crate::kdtree::KdTree;
+use crate::lab;
+use crate::remap::CompiledRules;
+
+// Resized images with more distinct colors than this are treated as photos.
+const PHOTO_COLOR_THRESHOLD: usize = 256;
+
+// The RGB lookup table has 2^LUT_BITS cells per channel (32³ = 32768 entries).
+const LUT_BITS: u32 = 5;
+const LUT_CELLS: u32 = 1 << LUT_BITS;
+// Tables kept around for metric/weight combinations seen recently.
+const LUT_CACHE_CAPACITY: usize = 4;
+
+// === METRICS ===
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorMetric {
+    /// Euclidean distance in Lab (ΔE*76).
+    Cie76,
+    /// ΔE*94 with graphic-arts weights.
+    Cie94,
+    /// ΔE*00, which fixes CIE76's blue and near-neutral mismatches.
+    Ciede2000,
+}
+
+impl ColorMetric {
+    pub const ALL: [ColorMetric; 3] = [
+        ColorMetric::Cie76,
+        ColorMetric::Cie94,
+        ColorMetric::Ciede2000,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorMetric::Cie76 => "CIE76 (Euclidean Lab)",
+            ColorMetric::Cie94 => "CIE94",
+            ColorMetric::Ciede2000 => "CIEDE2000",
+        }
+    }
+
+    /// Picks the metric for `choice`, where `None` means "auto": CIEDE2000 for
+    /// photographic inputs and the cheaper CIE76 for flat graphics.
+    pub fn resolve(choice: Option<ColorMetric>, img: &DynamicImage) -> ColorMetric {
+        choice.unwrap_or_else(|| {
+            if is_photographic(img) {
+                ColorMetric::Ciede2000
+            } else {
+                ColorMetric::Cie76
+            }
+        })
+    }
+
+    pub fn distance(self, a: Lab, b: Lab) -> f32 {
+        match self {
+            ColorMetric::Cie76 => cie76(a, b),
+            ColorMetric::Cie94 => cie94(a, b),
+            ColorMetric::Ciede2000 => ciede2000(a, b),
+        }
+    }
+}
+
+/// Per-channel multipliers applied to Lab coordinates before matching. Raising
+/// `l` biases matching toward preserving lightness over chroma.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LabWeights {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+impl Default for LabWeights {
+    fn default() -> Self {
+        Self {
+            l: 1.0,
+            a: 1.0,
+            b: 1.0,
+        }
+    }
+}
+
+impl LabWeights {
+    fn apply(self, lab: Lab) -> Lab {
+        Lab::new(lab.l * self.l, lab.a * self.a, lab.b * self.b)
+    }
+}
+
+pub fn to_lab(rgb: [u8; 3]) -> Lab {
+    Lab::from_color(Srgb::new(rgb[0], rgb[1], rgb[2]).into_format())
+}
+
+/// Decodes an sRGB channel value to linear light (0.0–1.0).
+pub fn srgb_to_linear(c: u8) -> f32 {
+    linear_table()[c as usize]
+}
+
+/// Encodes linear light (0.0–1.0) back to an sRGB channel value.
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// sRGB transfer function decoded once for all 256 channel values.
+pub fn linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let c = i as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })
+}
+
+fn is_photographic(img: &DynamicImage) -> bool {
+    let mut seen = HashSet::new();
+    for (_, _, pixel) in img.pixels() {
+        seen.insert([pixel[0], pixel[1], pixel[2]]);
+        if seen.len() > PHOTO_COLOR_THRESHOLD {
+            return true;
+        }
+    }
+    false
+}
+
+fn cie76(a: Lab, b: Lab) -> f32 {
+    let dl = a.l - b.l;
+    let da = a.a - b.a;
+    let db = a.b - b.b;
+
+    (dl * dl + da * da + db * db).sqrt()
+}
+
+fn cie94(a: Lab, b: Lab) -> f32 {
+    const K1: f32 = 0.045;
+    const K2: f32 = 0.015;
+
+    let c1 = a.a.hypot(a.b);
+    let c2 = b.a.hypot(b.b);
+
+    let dl = a.l - b.l;
+    let dc = c1 - c2;
+    let da = a.a - b.a;
+    let db = a.b - b.b;
+    let dh_sq = (da * da + db * db - dc * dc).max(0.0);
+
+    let sc = 1.0 + K1 * c1;
+    let sh = 1.0 + K2 * c1;
+
+    (dl * dl + (dc / sc).powi(2) + dh_sq / (sh * sh)).sqrt()
+}
+
+fn ciede2000(lab1: Lab, lab2: Lab) -> f32 {
+    let pow25_7 = 25f32.powi(7);
+
+    let c1 = lab1.a.hypot(lab1.b);
+    let c2 = lab2.a.hypot(lab2.b);
+    let c_bar7 = ((c1 + c2) / 2.0).powi(7);
+    let g = 0.5 * (1.0 - (c_bar7 / (c_bar7 + pow25_7)).sqrt());
+
+    let a1p = lab1.a * (1.0 + g);
+    let a2p = lab2.a * (1.0 + g);
+    let c1p = a1p.hypot(lab1.b);
+    let c2p = a2p.hypot(lab2.b);
+    let h1p = hue_degrees(lab1.b, a1p);
+    let h2p = hue_degrees(lab2.b, a2p);
+
+    let dlp = lab2.l - lab1.l;
+    let dcp = c2p - c1p;
+    let chroma_product = c1p * c2p;
+
+    let dhp = if chroma_product == 0.0 {
+        0.0
+    } else {
+        let d = h2p - h1p;
+        if d > 180.0 {
+            d - 360.0
+        } else if d < -180.0 {
+            d + 360.0
+        } else {
+            d
+        }
+    };
+    let dh_big = 2.0 * chroma_product.sqrt() * (dhp / 2.0).to_radians().sin();
+
+    let l_bar = (lab1.l + lab2.l) / 2.0;
+    let c_bar_p = (c1p + c2p) / 2.0;
+    let h_bar_p = if chroma_product == 0.0 {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= 180.0 {
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 360.0 {
+        (h1p + h2p + 360.0) / 2.0
+    } else {
+        (h1p + h2p - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_bar_p - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_bar_p).to_radians().cos()
+        + 0.32 * (3.0 * h_bar_p + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_bar_p - 63.0).to_radians().cos();
+
+    let d_theta = 30.0 * (-((h_bar_p - 275.0) / 25.0).powi(2)).exp();
+    let c_bar_p7 = c_bar_p.powi(7);
+    let rc = 2.0 * (c_bar_p7 / (c_bar_p7 + pow25_7)).sqrt();
+    let l_off = (l_bar - 50.0).powi(2);
+    let sl = 1.0 + 0.015 * l_off / (20.0 + l_off).sqrt();
+    let sc = 1.0 + 0.045 * c_bar_p;
+    let sh = 1.0 + 0.015 * c_bar_p * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+    let l_term = dlp / sl;
+    let c_term = dcp / sc;
+    let h_term = dh_big / sh;
+
+    (l_term * l_term + c_term * c_term + h_term * h_term + rt * c_term * h_term).sqrt()
+}
+
+fn hue_degrees(b: f32, a_prime: f32) -> f32 {
+    if b == 0.0 && a_prime == 0.0 {
+        return 0.0;
+    }
+    let h = b.atan2(a_prime).to_degrees();
+    if h < 0.0 { h + 360.0 } else { h }
+}
+
+// === MATCHING ===
+/// Which palette entry wins when two are exactly equidistant from a pixel.
+/// NaN distances count as infinitely far and the rest are compared with
+/// `f32::total_cmp`, so the result never depends on iteration order and a
+/// NaN distance can only win if every one is NaN.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum TieBreak {
+    /// The entry that comes first in the palette grid.
+    #[default]
+    LowerIndex,
+    /// The more saturated entry (Lab chroma), then the lower index.
+    HigherChroma,
+}
+
+impl TieBreak {
+    pub const ALL: [TieBreak; 2] = [TieBreak::LowerIndex, TieBreak::HigherChroma];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TieBreak::LowerIndex => "Prefer lower index",
+            TieBreak::HigherChroma => "Prefer higher chroma",
+        }
+    }
+}
+
+/// How a `Matcher` ranks palette entries and which ones it may return.
+#[derive(Clone, PartialEq)]
+pub struct MatchParams {
+    pub metric: ColorMetric,
+    pub weights: LabWeights,
+    pub tie_break: TieBreak,
+    /// Palette indices the encoder may emit, in ascending order.
+    pub allowed: Vec<usize>,
+}
+
+/// Nearest-palette lookup under a chosen color metric and Lab weighting,
+/// optionally short-circuited through a precomputed `PaletteLut`.
+pub struct Matcher<'a> {
+    palette: &'a [[u8; 3]],
+    params: MatchParams,
+    /// Weighted Lab for each allowed palette entry (parallel to
+    /// `params.allowed`), converted once per matcher.
+    allowed_lab: Vec<Lab>,
+    /// Unweighted Lab chroma of each allowed entry, for `TieBreak::HigherChroma`.
+    allowed_chroma: Vec<f32>,
+    /// Weighted-Lab k-d tree over the allowed entries, only for CIE76 with the
+    /// lower-index tie-break it implements: other metrics are not Euclidean,
+    /// so they keep the linear scan.
+    tree: Option<KdTree>,
+    lut: Option<&'a PaletteLut>,
+    /// User overrides consulted before any nearest-color search.
+    rules: Option<CompiledRules>,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(palette: &'a [[u8; 3]], params: MatchParams) -> Self {
+        assert!(!params.allowed.is_empty(), "no palette entries allowed");
+
+        let allowed_lab: Vec<Lab> = params
+            .allowed
+            .iter()
+            .map(|&idx| params.weights.apply(to_lab(palette[idx])))
+            .collect();
+
+        let allowed_chroma: Vec<f32> = params
+            .allowed
+            .iter()
+            .map(|&idx| {
+                let lab = to_lab(palette[idx]);
+                lab.a.hypot(lab.b)
+            })
+            .collect();
+
+        let use_tree =
+            params.metric == ColorMetric::Cie76 && params.tie_break == TieBreak::LowerIndex;
+        let tree = use_tree.then(|| {
+            let points: Vec<[f32; 3]> = allowed_lab
+                .iter()
+                .map(|lab| [lab.l, lab.a, lab.b])
+                .collect();
+            KdTree::new(&points)
+        });
+
+        Self {
+            palette,
+            params,
+            allowed_lab,
+            allowed_chroma,
+            tree,
+            lut: None,
+            rules: None,
+        }
+    }
+
+    /// Answers lookups from `lut`, which must have been built for the same
+    /// palette and parameters.
+    pub fn with_lut(self, lut: &'a PaletteLut) -> Self {
+        Self {
+            lut: Some(lut),
+            ..self
+        }
+    }
+
+    pub fn with_rules(self, rules: CompiledRules) -> Self {
+        Self {
+            rules: (!rules.is_empty()).then_some(rules),
+            ..self
+        }
+    }
+
+    pub fn color(&self, idx: usize) -> [u8; 3] {
+        self.palette[idx]
+    }
+
+    pub fn nearest(&self, rgb: [u8; 3]) -> usize {
+        if let Some(target) = self
+            .rules
+            .as_ref()
+            .and_then(|rules| rules.apply(to_lab(rgb)))
+        {
+            return target;
+        }
+
+        match self.lut {
+            Some(lut) => lut.lookup(rgb),
+            None => self.nearest_exact(rgb),
+        }
+    }
+
+    /// Like `nearest`, but always searches the palette instead of trusting the
+    /// approximate lookup table.
+    pub fn nearest_precise(&self, rgb: [u8; 3]) -> usize {
+        let lab = to_lab(rgb);
+        self.rules
+            .as_ref()
+            .and_then(|rules| rules.apply(lab))
+            .unwrap_or_else(|| self.nearest_lab(lab))
+    }
+
+    /// Matches a whole scanline into `out`. Without a LUT the row is
+    /// converted to Lab in one vectorized batch.
+    pub fn nearest_row(&self, row: &[[u8; 3]], out: &mut [usize]) {
+        if self.rules.is_none()
+            && let Some(lut) = self.lut
+        {
+            for (rgb, slot) in row.iter().zip(out) {
+                *slot = lut.lookup(*rgb);
+            }
+            return;
+        }
+
+        let mut labs = vec![Lab::new(0.0, 0.0, 0.0); row.len()];
+        lab::convert_row(row, &mut labs);
+        for ((lab, rgb), slot) in labs.into_iter().zip(row).zip(out) {
+            *slot = match (
+                self.rules.as_ref().and_then(|rules| rules.apply(lab)),
+                self.lut,
+            ) {
+                (Some(target), _) => target,
+                (None, Some(lut)) => lut.lookup(*rgb),
+                (None, None) => self.nearest_lab(lab),
+            };
+        }
+    }
+
+    /// Distance from `rgb` to palette entry `idx` under this matcher's metric
+    /// and weights.
+    pub fn distance(&self, rgb: [u8; 3], idx: usize) -> f32 {
+        let weights = self.params.weights;
+        self.params.metric.distance(
+            weights.apply(to_lab(rgb)),
+            weights.apply(to_lab(self.palette[idx])),
+        )
+    }
+
+    /// The best allowed match for `rgb` other than `exclude`, if there is one.
+    pub fn runner_up(&self, rgb: [u8; 3], exclude: usize) -> Option<usize> {
+        let lab = self.params.weights.apply(to_lab(rgb));
+        self.allowed_lab
+            .iter()
+            .enumerate()
+            .filter(|&(slot, _)| self.params.allowed[slot] != exclude)
+            .map(|(slot, candidate)| (slot, rank(self.params.metric.distance(lab, *candidate))))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(slot, _)| self.params.allowed[slot])
+    }
+
+    fn nearest_exact(&self, rgb: [u8; 3]) -> usize {
+        self.nearest_lab(to_lab(rgb))
+    }
+
+    fn nearest_lab(&self, lab: Lab) -> usize {
+        let lab = self.params.weights.apply(lab);
+
+        let slot = match &self.tree {
+            Some(tree) => tree.nearest([lab.l, lab.a, lab.b]),
+            None => self.scan(lab),
+        };
+
+        self.params.allowed[slot]
+    }
+
+    /// Linear search over the allowed entries with a total order on distance
+    /// and the configured tie-break.
+    fn scan(&self, lab: Lab) -> usize {
+        let mut best = 0;
+        let mut best_distance = rank(self.params.metric.distance(lab, self.allowed_lab[0]));
+
+        for (slot, candidate) in self.allowed_lab.iter().enumerate().skip(1) {
+            let distance = rank(self.params.metric.distance(lab, *candidate));
+            let wins = match distance.total_cmp(&best_distance) {
+                Ordering::Less => true,
+                Ordering::Equal => self.wins_tie(slot, best),
+                Ordering::Greater => false,
+            };
+            if wins {
+                best = slot;
+                best_distance = distance;
+            }
+        }
+
+        best
+    }
+
+    /// Whether `challenger` beats the equidistant `incumbent`. Slots are in
+    /// ascending palette order, so the incumbent always has the lower index.
+    fn wins_tie(&self, challenger: usize, incumbent: usize) -> bool {
+        match self.params.tie_break {
+            TieBreak::LowerIndex => false,
+            TieBreak::HigherChroma => {
+                self.allowed_chroma[challenger] > self.allowed_chroma[incumbent]
+            }
+        }
+    }
+}
+
+/// `distance` ordered for matching: `total_cmp` puts negative NaN before
+/// every number, so NaN is pushed to the far end instead.
+fn rank(distance: f32) -> f32 {
+    if distance.is_nan() {
+        f32::INFINITY
+    } else {
+        distance
+    }
+}
+
+/// Row-major palette indices for an image whose every pixel is exactly one of
+/// the `allowed` palette colors, or `None` as soon as one is not. Duplicate
+/// palette colors resolve to the lower index.
+pub fn exact_indices(
+    img: &DynamicImage,
+    palette: &[[u8; 3]],
+    allowed: &[usize],
+) -> Option<Vec<usize>> {
+    let mut lookup = HashMap::with_capacity(allowed.len());
+    for &idx in allowed {
+        lookup.entry(palette[idx]).or_insert(idx);
+    }
+
+    img.pixels()
+        .map(|(_, _, pixel)| lookup.get(&[pixel[0], pixel[1], pixel[2]]).copied())
+        .collect()
+}
+
+// === LOOKUP TABLE ===
+/// Maps any RGB color straight to a palette index. Each channel is bucketed
+/// into `LUT_CELLS` cells and every cell stores the match for its center.
+pub struct PaletteLut {
+    palette: Vec<[u8; 3]>,
+    params: MatchParams,
+    /// Wide enough for any palette grid up to `palettes::GRID_MAX` squared.
+    table: Vec<u16>,
+}
+
+impl PaletteLut {
+    fn build(palette: &[[u8; 3]], params: &MatchParams) -> Self {
+        let matcher = Matcher::new(palette, params.clone());
+        let half = 1 << (8 - LUT_BITS - 1);
+        let center = |cell: u32| ((cell << (8 - LUT_BITS)) + half) as u8;
+
+        // One scanline of blue values per (red, green) pair.
+        let table = (0..LUT_CELLS * LUT_CELLS)
+            .into_par_iter()
+            .flat_map_iter(|rg| {
+                let (r, g) = (center(rg / LUT_CELLS), center(rg % LUT_CELLS));
+                let row: Vec<[u8; 3]> = (0..LUT_CELLS).map(|b| [r, g, center(b)]).collect();
+                let mut indices = vec![0; row.len()];
+                matcher.nearest_row(&row, &mut indices);
+                indices.into_iter().map(|idx| idx as u16)
+            })
+            .collect();
+
+        Self {
+            palette: palette.to_vec(),
+            params: params.clone(),
+            table,
+        }
+    }
+
+    fn matches(&self, palette: &[[u8; 3]], params: &MatchParams) -> bool {
+        self.palette == palette && self.params == *params
+    }
+
+    fn lookup(&self, rgb: [u8; 3]) -> usize {
+        let [r, g, b] = rgb.map(|c| (c >> (8 - LUT_BITS)) as usize);
+        let cells = LUT_CELLS as usize;
+        self.table[(r * cells + g) * cells + b] as usize
+    }
+}
+
+/// Keeps the most recently used lookup tables so switching settings back and
+/// forth does not rebuild them.
+#[derive(Default)]
+pub struct LutCache {
+    luts: Vec<PaletteLut>,
+}
+
+impl LutCache {
+    pub fn get(&mut self, palette: &[[u8; 3]], params: &MatchParams) -> &PaletteLut {
+        let pos = match self
+            .luts
+            .iter()
+            .position(|lut| lut.matches(palette, params))
+        {
+            Some(pos) => pos,
+            None => {
+                if self.luts.len() >= LUT_CACHE_CAPACITY {
+                    self.luts.remove(0);
+                }
+                self.luts.push(PaletteLut::build(palette, params));
+                self.luts.len() - 1
+            }
+        };
+
+        &self.luts[pos]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64*, so failures reproduce from the seed alone.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn rgb(&mut self) -> [u8; 3] {
+            let bits = self.next();
+            [bits as u8, (bits >> 8) as u8, (bits >> 16) as u8]
+        }
+    }
+
+    /// A random palette with some entries repeated, so exact ties occur,
+    /// and a random non-empty subset of it allowed.
+    fn random_case(rng: &mut Rng) -> (Vec<[u8; 3]>, Vec<usize>) {
+        let mut palette: Vec<[u8; 3]> = (0..2 + rng.below(40)).map(|_| rng.rgb()).collect();
+        for _ in 0..rng.below(palette.len()) {
+            let (from, to) = (rng.below(palette.len()), rng.below(palette.len()));
+            palette[to] = palette[from];
+        }
+        let mut allowed: Vec<usize> = (0..palette.len()).filter(|_| rng.below(4) != 0).collect();
+        if allowed.is_empty() {
+            allowed.push(rng.below(palette.len()));
+        }
+        (palette, allowed)
+    }
+
+    fn params(metric: ColorMetric, tie_break: TieBreak, allowed: Vec<usize>) -> MatchParams {
+        MatchParams {
+            metric,
+            weights: LabWeights::default(),
+            tie_break,
+            allowed,
+        }
+    }
+
+    #[test]
+    fn nearest_is_the_closest_allowed_entry() {
+        let mut rng = Rng(0x5EED_0001);
+        for _ in 0..60 {
+            let (palette, allowed) = random_case(&mut rng);
+            for metric in ColorMetric::ALL {
+                for tie_break in TieBreak::ALL {
+                    let matcher =
+                        Matcher::new(&palette, params(metric, tie_break, allowed.clone()));
+                    for _ in 0..50 {
+                        let rgb = rng.rgb();
+                        let got = matcher.nearest_precise(rgb);
+                        assert!(allowed.contains(&got), "{got} is not allowed");
+
+                        let best = allowed
+                            .iter()
+                            .map(|&idx| matcher.distance(rgb, idx))
+                            .fold(f32::INFINITY, f32::min);
+                        assert_eq!(
+                            matcher.distance(rgb, got),
+                            best,
+                            "{rgb:?} with {}",
+                            metric.label()
+                        );
+
+                        // The k-d tree's squared distances can split ties
+                        // that CIE76's square root rounds together.
+                        if metric == ColorMetric::Cie76 {
+                            continue;
+                        }
+                        let mut tied = allowed
+                            .iter()
+                            .copied()
+                            .filter(|&idx| matcher.distance(rgb, idx) == best);
+                        match tie_break {
+                            TieBreak::LowerIndex => assert_eq!(Some(got), tied.min()),
+                            TieBreak::HigherChroma => {
+                                let chroma = |idx: usize| {
+                                    let lab = to_lab(palette[idx]);
+                                    lab.a.hypot(lab.b)
+                                };
+                                assert!(tied.all(|idx| chroma(idx) <= chroma(got)));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rows_and_lookup_tables_agree_with_single_lookups() {
+        let mut rng = Rng(0x5EED_0002);
+        let mut luts = LutCache::default();
+        for _ in 0..8 {
+            let (palette, allowed) = random_case(&mut rng);
+            let params = params(
+                ColorMetric::Ciede2000,
+                TieBreak::LowerIndex,
+                allowed.clone(),
+            );
+            let matcher = Matcher::new(&palette, params.clone());
+            let row: Vec<[u8; 3]> = (0..256).map(|_| rng.rgb()).collect();
+            let mut out = vec![usize::MAX; row.len()];
+            matcher.nearest_row(&row, &mut out);
+            for (&rgb, &idx) in row.iter().zip(&out) {
+                let precise = matcher.distance(rgb, matcher.nearest_precise(rgb));
+                assert!((matcher.distance(rgb, idx) - precise).abs() < 1e-3);
+            }
+
+            let lut = luts.get(&palette, &params);
+            let matcher = Matcher::new(&palette, params.clone()).with_lut(lut);
+            for rgb in row {
+                assert!(allowed.contains(&matcher.nearest(rgb)));
+            }
+        }
+    }
+
+    #[test]
+    fn nan_distance_never_wins() {
+        let mut rng = Rng(0x5EED_0003);
+        let palette: Vec<[u8; 3]> = (0..16).map(|_| rng.rgb()).collect();
+        let allowed: Vec<usize> = (0..palette.len()).collect();
+        for metric in ColorMetric::ALL {
+            let mut matcher = Matcher::new(
+                &palette,
+                params(metric, TieBreak::HigherChroma, allowed.clone()),
+            );
+            // Negative NaN sorts before every number under `total_cmp`.
+            matcher.allowed_lab[0] = Lab::new(-f32::NAN, -f32::NAN, -f32::NAN);
+            for _ in 0..200 {
+                assert_ne!(matcher.nearest_precise(rng.rgb()), 0);
+                assert_ne!(matcher.runner_up(rng.rgb(), 1), Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_tables_address_palettes_over_256_colors() {
+        // Every entry sits on its own table cell's center, where the table
+        // holds exactly that entry's match.
+        let center = |cell: usize| ((cell << (8 - LUT_BITS)) + (1 << (8 - LUT_BITS - 1))) as u8;
+        let cells = LUT_CELLS as usize;
+        let palette: Vec<[u8; 3]> = (0..600)
+            .map(|i| {
+                [
+                    center(i % cells),
+                    center(i / cells % cells),
+                    center(i * 7 % cells),
+                ]
+            })
+            .collect();
+        let allowed: Vec<usize> = (0..palette.len()).collect();
+        let params = params(ColorMetric::Cie76, TieBreak::LowerIndex, allowed);
+        let mut luts = LutCache::default();
+        let lut = luts.get(&palette, &params);
+        let matcher = Matcher::new(&palette, params.clone()).with_lut(lut);
+        for (idx, &rgb) in palette.iter().enumerate() {
+            assert_eq!(matcher.nearest(rgb), idx);
+        }
+    }
+}
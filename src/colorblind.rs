@@ -0,0 +1,85 @@
+//! How the flag looks with a color vision deficiency, after Machado,
+//! Oliveira & Fernandes (2009) at full severity.
+
+use eframe::egui;
+use egui::{Color32, ColorImage};
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+
+/// Color vision the flag preview is simulated for.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Vision {
+    #[default]
+    Normal,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Vision {
+    pub const ALL: [Vision; 4] = [
+        Vision::Normal,
+        Vision::Protanopia,
+        Vision::Deuteranopia,
+        Vision::Tritanopia,
+    ];
+    /// The deficiencies, in the order `PreviewTextures::colorblind` holds them.
+    pub const SIMULATED: [Vision; 3] =
+        [Vision::Protanopia, Vision::Deuteranopia, Vision::Tritanopia];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Vision::Normal => "Normal vision",
+            Vision::Protanopia => "Protanopia (no red)",
+            Vision::Deuteranopia => "Deuteranopia (no green)",
+            Vision::Tritanopia => "Tritanopia (no blue)",
+        }
+    }
+
+    /// Position in `SIMULATED`, or `None` for normal vision.
+    pub fn index(self) -> Option<usize> {
+        Vision::SIMULATED.iter().position(|&vision| vision == self)
+    }
+
+    /// Linear-RGB transform onto what this vision perceives.
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Vision::Normal => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Vision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Vision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Vision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+/// `image` as seen with `vision`; alpha is kept.
+pub fn simulate(image: &ColorImage, vision: Vision) -> ColorImage {
+    let matrix = vision.matrix();
+    let pixels = image
+        .pixels
+        .iter()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+            let linear = [r, g, b].map(srgb_to_linear);
+            let [r, g, b] =
+                matrix.map(|row| linear_to_srgb(row.iter().zip(linear).map(|(m, c)| m * c).sum()));
+            Color32::from_rgba_unmultiplied(r, g, b, a)
+        })
+        .collect();
+    ColorImage {
+        size: image.size,
+        pixels,
+    }
+}
@@ -0,0 +1,74 @@
+use image::{RgbaImage, imageops};
+
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+// Width of the canvas the layers are flattened on; the height follows the
+// flag's aspect ratio. A few times the flag size keeps the later resample
+// smooth without scaling huge sources at full size.
+const CANVAS_WIDTH: u32 = 800;
+
+/// Where one layer sits on the flag.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Placement {
+    /// Center of the layer, in fractions of the flag width and height.
+    pub x: f32,
+    pub y: f32,
+    /// 1.0 fits the whole layer inside the flag.
+    pub scale: f32,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            x: 0.5,
+            y: 0.5,
+            scale: 1.0,
+        }
+    }
+}
+
+/// A pinned background image with the current source drawn over it.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Layers {
+    pub enabled: bool,
+    pub background: Placement,
+    pub foreground: Placement,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            background: Placement::default(),
+            foreground: Placement {
+                scale: 0.6,
+                ..Placement::default()
+            },
+        }
+    }
+}
+
+/// Flattens `foreground` over `background` on a flag-aspect canvas. Areas
+/// neither layer covers stay transparent for the alpha stage to fill.
+pub fn flatten(background: &RgbaImage, foreground: &RgbaImage, layers: Layers) -> RgbaImage {
+    let canvas_h = CANVAS_WIDTH * IMAGE_HEIGHT / IMAGE_WIDTH;
+    let mut canvas = RgbaImage::new(CANVAS_WIDTH, canvas_h);
+    draw(&mut canvas, background, layers.background);
+    draw(&mut canvas, foreground, layers.foreground);
+    canvas
+}
+
+fn draw(canvas: &mut RgbaImage, layer: &RgbaImage, placement: Placement) {
+    let (cw, ch) = (canvas.width() as f32, canvas.height() as f32);
+    let (lw, lh) = (layer.width() as f32, layer.height() as f32);
+    let fit = (cw / lw).min(ch / lh) * placement.scale;
+    let (w, h) = (
+        ((lw * fit).round() as u32).max(1),
+        ((lh * fit).round() as u32).max(1),
+    );
+
+    let scaled = imageops::resize(layer, w, h, imageops::FilterType::CatmullRom);
+    let x = (placement.x * cw - w as f32 / 2.0).round() as i64;
+    let y = (placement.y * ch - h as f32 / 2.0).round() as i64;
+    imageops::overlay(canvas, &scaled, x, y);
+}
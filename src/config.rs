@@ -0,0 +1,285 @@
+//! Preferences kept between sessions in `%APPDATA%\MageFlag\config.json`,
+//! and the Settings window that edits them.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use winreg::RegKey;
+use winreg::enums::HKEY_CURRENT_USER;
+
+use crate::capture::CaptureTarget;
+use crate::dither::DitherMode;
+use crate::fit::FitMode;
+use crate::hotkey::Hotkey;
+use crate::palettes::PaletteChoice;
+use crate::{
+    ANIMATION_INTERVAL, ANIMATION_INTERVAL_MAX, CAPTURE_DELAY, CLIPBOARD_POLL_INTERVAL,
+    WORKER_TICK, onboarding, palettes, source,
+};
+
+const CONFIG_DIR: &str = "MageFlag";
+const CONFIG_FILE: &str = "config.json";
+// Bounds of the clipboard poll interval, in milliseconds.
+const POLL_INTERVAL_MIN: u64 = 200;
+const POLL_INTERVAL_MAX: u64 = 10_000;
+// Longest wait for the window to minimize before a region capture.
+const CAPTURE_DELAY_MAX: u64 = 2_000;
+// Range of the UI scale, on top of Windows' text size.
+pub const UI_SCALE_MIN: f32 = 0.5;
+pub const UI_SCALE_MAX: f32 = 3.0;
+// Change per click of the UI scale buttons.
+const UI_SCALE_STEP: f32 = 0.25;
+// Where Windows keeps the "Make text bigger" setting, in percent.
+const ACCESSIBILITY_REGISTRY_PATH: &str = "Software\\Microsoft\\Accessibility";
+const TEXT_SCALE_VALUE_NAME: &str = "TextScaleFactor";
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    /// Dark or light as Windows is set.
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Dark, Theme::Light];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "Follow Windows",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+
+    /// Whether to draw dark, given the system theme eframe detected.
+    pub fn is_dark(self, system: Option<eframe::Theme>) -> bool {
+        match self {
+            Theme::System => system != Some(eframe::Theme::Light),
+            Theme::Dark => true,
+            Theme::Light => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Take new sources from the clipboard; off leaves the watched folder,
+    /// files and captures.
+    pub watch_clipboard: bool,
+    pub poll_interval_ms: u64,
+    /// Download images whose https URL is copied.
+    pub fetch_urls: bool,
+    /// Encode new sources without writing them until Apply is clicked.
+    pub review: bool,
+    /// Dithering and fit mode each session starts with.
+    pub dither: DitherMode,
+    pub fit: FitMode,
+    pub hotkey: Hotkey,
+    pub watch_folder: Option<PathBuf>,
+    /// Time the window gets to minimize before a region capture.
+    pub capture_delay_ms: u64,
+    /// Frame time animated flags start with.
+    pub animation_interval_ms: u64,
+    pub theme: Theme,
+    /// Zoom of the whole interface, multiplied with Windows' text size.
+    pub ui_scale: f32,
+    /// The palette file in use; `None` for the built-in palette.
+    pub palette: Option<PaletteChoice>,
+    /// The setup walkthrough was finished or skipped.
+    pub onboarding_done: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            watch_clipboard: true,
+            poll_interval_ms: CLIPBOARD_POLL_INTERVAL.as_millis() as u64,
+            fetch_urls: false,
+            review: false,
+            dither: DitherMode::default(),
+            fit: FitMode::default(),
+            hotkey: Hotkey::default(),
+            watch_folder: None,
+            capture_delay_ms: CAPTURE_DELAY.as_millis() as u64,
+            animation_interval_ms: ANIMATION_INTERVAL.as_millis() as u64,
+            theme: Theme::default(),
+            ui_scale: 1.0,
+            palette: None,
+            onboarding_done: false,
+        }
+    }
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        let appdata = std::env::var_os("APPDATA")?;
+        Some(PathBuf::from(appdata).join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// The saved config, or defaults when there is none or it cannot be read.
+    pub fn load() -> Self {
+        let saved = Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str::<Config>(&text).ok());
+        let mut config = saved.unwrap_or_else(|| Config {
+            // Kept in the registry before there was a config file.
+            watch_folder: source::restore_watch_folder(),
+            palette: palettes::registry_choice(),
+            ..Config::default()
+        });
+        config.onboarding_done |= onboarding::done_in_registry();
+        // Window handles do not outlive the session.
+        if matches!(config.hotkey.target, CaptureTarget::Window(_)) {
+            config.hotkey.target = CaptureTarget::default();
+        }
+        config.poll_interval_ms = config
+            .poll_interval_ms
+            .clamp(POLL_INTERVAL_MIN, POLL_INTERVAL_MAX);
+        config.ui_scale = config.ui_scale.clamp(UI_SCALE_MIN, UI_SCALE_MAX);
+        config
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("%APPDATA% is not set")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn capture_delay(&self) -> Duration {
+        Duration::from_millis(self.capture_delay_ms)
+    }
+
+    pub fn animation_interval(&self) -> Duration {
+        Duration::from_millis(self.animation_interval_ms)
+    }
+
+    /// The Settings window's contents. The hotkey has its own section in the
+    /// main window.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Appearance");
+        egui::ComboBox::new("config_theme", "Theme")
+            .selected_text(self.theme.label())
+            .show_ui(ui, |ui| {
+                for theme in Theme::ALL {
+                    ui.selectable_value(&mut self.theme, theme, theme.label());
+                }
+            });
+        // Buttons rather than a slider, which would rescale under the pointer
+        // while dragged.
+        ui.horizontal(|ui| {
+            ui.label("UI scale");
+            if ui
+                .add_enabled(self.ui_scale > UI_SCALE_MIN, egui::Button::new("−"))
+                .on_hover_text("Smaller")
+                .clicked()
+            {
+                self.ui_scale = (self.ui_scale - UI_SCALE_STEP).max(UI_SCALE_MIN);
+            }
+            ui.label(format!("{:.0}%", self.ui_scale * 100.0));
+            if ui
+                .add_enabled(self.ui_scale < UI_SCALE_MAX, egui::Button::new("+"))
+                .on_hover_text("Larger")
+                .clicked()
+            {
+                self.ui_scale = (self.ui_scale + UI_SCALE_STEP).min(UI_SCALE_MAX);
+            }
+            if ui.button("Reset").clicked() {
+                self.ui_scale = 1.0;
+            }
+        })
+        .response
+        .on_hover_text("On top of the Windows text size; Ctrl+plus and Ctrl+minus change it too");
+
+        ui.separator();
+        ui.heading("Sources");
+        ui.checkbox(&mut self.watch_clipboard, "Watch the clipboard");
+        ui.add_enabled(
+            self.watch_clipboard,
+            egui::Slider::new(
+                &mut self.poll_interval_ms,
+                POLL_INTERVAL_MIN..=POLL_INTERVAL_MAX,
+            )
+            .logarithmic(true)
+            .suffix(" ms")
+            .text("Check every"),
+        );
+        ui.add_enabled(
+            self.watch_clipboard,
+            egui::Checkbox::new(&mut self.fetch_urls, "Download copied image links"),
+        )
+        .on_hover_text("Fetch images from copied https links; plain http is never fetched");
+        ui.checkbox(&mut self.review, "Review before apply");
+        ui.horizontal(|ui| {
+            let folder = match &self.watch_folder {
+                Some(dir) => dir.display().to_string(),
+                None => "none".to_owned(),
+            };
+            ui.label(format!("Watched folder: {folder}"));
+            if ui.small_button("Choose…").clicked()
+                && let Some(dir) = rfd::FileDialog::new()
+                    .set_title("Watch folder for new images")
+                    .pick_folder()
+            {
+                self.watch_folder = Some(dir);
+            }
+            if self.watch_folder.is_some() && ui.small_button("Clear").clicked() {
+                self.watch_folder = None;
+            }
+        });
+
+        ui.separator();
+        ui.heading("New sessions");
+        egui::ComboBox::new("config_dither", "Dithering")
+            .selected_text(self.dither.label())
+            .show_ui(ui, |ui| {
+                for mode in DitherMode::ALL {
+                    ui.selectable_value(&mut self.dither, mode, mode.label());
+                }
+            });
+        egui::ComboBox::new("config_fit", "Fit")
+            .selected_text(self.fit.label())
+            .show_ui(ui, |ui| {
+                for mode in FitMode::ALL {
+                    ui.selectable_value(&mut self.fit, mode, mode.label());
+                }
+            });
+        ui.add(
+            egui::Slider::new(
+                &mut self.animation_interval_ms,
+                WORKER_TICK.as_millis() as u64..=ANIMATION_INTERVAL_MAX.as_millis() as u64,
+            )
+            .logarithmic(true)
+            .suffix(" ms")
+            .text("Animation frame time"),
+        );
+
+        ui.separator();
+        ui.heading("Capture");
+        ui.add(
+            egui::Slider::new(&mut self.capture_delay_ms, 0..=CAPTURE_DELAY_MAX)
+                .suffix(" ms")
+                .text("Wait before a region capture"),
+        );
+    }
+}
+
+/// Windows' "Make text bigger" accessibility setting as a factor, 1.0 when
+/// it was never changed.
+pub fn windows_text_scale() -> f32 {
+    RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(ACCESSIBILITY_REGISTRY_PATH)
+        .and_then(|key| key.get_value::<u32, _>(TEXT_SCALE_VALUE_NAME))
+        .map_or(1.0, |percent| percent as f32 / 100.0)
+}
@@ -0,0 +1,73 @@
+use image::{DynamicImage, GenericImageView};
+use palette::Lab;
+
+use crate::color::{ColorMetric, Matcher, to_lab};
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ContrastSettings {
+    pub enabled: bool,
+    /// Source ΔE76 above which two neighbours must not share a palette cell.
+    pub threshold: f32,
+}
+
+impl Default for ContrastSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 12.0,
+        }
+    }
+}
+
+/// Second pass over a finished quantization: wherever two adjacent source
+/// pixels differ by more than `threshold` but landed on the same cell, the one
+/// that loses the least accuracy is moved to its next-best match. A single
+/// greedy scan, so a move can occasionally reintroduce a collision upstream.
+pub fn preserve_local_contrast(
+    img: &DynamicImage,
+    matcher: &Matcher,
+    indices: &mut [usize],
+    threshold: f32,
+) {
+    let w = IMAGE_WIDTH as usize;
+    let h = IMAGE_HEIGHT as usize;
+
+    let rgb: Vec<[u8; 3]> = img
+        .pixels()
+        .map(|(_, _, pixel)| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    let labs: Vec<Lab> = rgb.iter().map(|&pixel| to_lab(pixel)).collect();
+
+    for y in 0..h {
+        for x in 0..w {
+            let here = y * w + x;
+            let right = (x + 1 < w).then(|| here + 1);
+            let below = (y + 1 < h).then(|| here + w);
+
+            for there in [right, below].into_iter().flatten() {
+                if indices[here] != indices[there]
+                    || ColorMetric::Cie76.distance(labs[here], labs[there]) <= threshold
+                {
+                    continue;
+                }
+
+                let shared = indices[here];
+                let cost = |pixel: usize| {
+                    matcher.runner_up(rgb[pixel], shared).map(|alternative| {
+                        let loss = matcher.distance(rgb[pixel], alternative)
+                            - matcher.distance(rgb[pixel], shared);
+                        (loss, alternative)
+                    })
+                };
+
+                match (cost(here), cost(there)) {
+                    (Some((a, alt)), Some((b, _))) if a <= b => indices[here] = alt,
+                    (_, Some((_, alt))) => indices[there] = alt,
+                    (Some((_, alt)), None) => indices[here] = alt,
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use eframe::egui;
+use egui::{Color32, Pos2, Rect, Sense, Stroke, TextureHandle, Vec2, WidgetType};
+use image::{RgbaImage, imageops};
+
+use crate::color::{ColorMetric, to_lab};
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH, a11y};
+
+// Widest the crop editor draws the source thumbnail, in points.
+const EDITOR_MAX_WIDTH: f32 = 360.0;
+
+// How close (in points) the pointer must be to a corner to grab it.
+const HANDLE_GRAB_RADIUS: f32 = 10.0;
+
+// Smallest crop edge, as a fraction of the source.
+const MIN_CROP_FRACTION: f32 = 0.02;
+
+// Longest side of the detail map the subject search runs on.
+const SUBJECT_MAP_MAX: u32 = 96;
+
+// Share of the image's detail the subject crop must keep.
+const SUBJECT_COVERAGE: f32 = 0.8;
+
+// Subject crops are tried from the full flag-aspect rectangle down to this
+// fraction of it, in `SUBJECT_SCALE_STEPS` steps.
+const SUBJECT_MIN_SCALE: f32 = 0.35;
+const SUBJECT_SCALE_STEPS: u32 = 14;
+
+/// Crop rectangle in fractions (0.0–1.0) of the source, so it maps onto both
+/// the thumbnail and the full-size image.
+#[derive(Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl CropRect {
+    /// The largest centered rectangle with the flag's 100:66 aspect ratio.
+    pub fn flag_aspect(source_w: u32, source_h: u32) -> Self {
+        let flag = IMAGE_WIDTH as f32 / IMAGE_HEIGHT as f32;
+        let source = source_w as f32 / source_h.max(1) as f32;
+
+        let (w, h) = if source > flag {
+            (flag / source, 1.0)
+        } else {
+            (1.0, source / flag)
+        };
+        Self {
+            x: (1.0 - w) / 2.0,
+            y: (1.0 - h) / 2.0,
+            w,
+            h,
+        }
+    }
+
+    /// Pixel bounds `(x, y, w, h)` within a `source_w` x `source_h` image,
+    /// never empty.
+    pub fn to_pixels(self, source_w: u32, source_h: u32) -> (u32, u32, u32, u32) {
+        let x = ((self.x * source_w as f32).round() as u32).min(source_w.saturating_sub(1));
+        let y = ((self.y * source_h as f32).round() as u32).min(source_h.saturating_sub(1));
+        let w = ((self.w * source_w as f32).round() as u32).clamp(1, source_w - x);
+        let h = ((self.h * source_h as f32).round() as u32).clamp(1, source_h - y);
+        (x, y, w, h)
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.x = (self.x + dx).clamp(0.0, 1.0 - self.w);
+        self.y = (self.y + dy).clamp(0.0, 1.0 - self.h);
+    }
+
+    fn corner(self, i: usize) -> [f32; 2] {
+        [
+            if i.is_multiple_of(2) {
+                self.x
+            } else {
+                self.x + self.w
+            },
+            if i < 2 { self.y } else { self.y + self.h },
+        ]
+    }
+}
+
+/// The smallest flag-aspect rectangle that still holds most of the image's
+/// detail: local edges plus color that stands out from the average, so a
+/// subject in front of a plain or blurred background is framed tightly.
+pub fn subject(source: &RgbaImage) -> CropRect {
+    let (w, h) = source.dimensions();
+    let scale = (SUBJECT_MAP_MAX as f32 / w.max(h) as f32).min(1.0);
+    let (map_w, map_h) = (
+        ((w as f32 * scale) as u32).max(1),
+        ((h as f32 * scale) as u32).max(1),
+    );
+    let small = imageops::thumbnail(source, map_w, map_h);
+    let labs: Vec<_> = small.pixels().map(|p| to_lab([p[0], p[1], p[2]])).collect();
+    let (map_w, map_h) = (map_w as usize, map_h as usize);
+
+    let count = labs.len() as f32;
+    let mean = labs.iter().fold([0.0; 3], |acc, lab| {
+        [
+            acc[0] + lab.l / count,
+            acc[1] + lab.a / count,
+            acc[2] + lab.b / count,
+        ]
+    });
+    let mean = palette::Lab::new(mean[0], mean[1], mean[2]);
+    let distance = |a, b| ColorMetric::Cie76.distance(a, b);
+
+    let edges: Vec<f32> = (0..labs.len())
+        .map(|i| {
+            let (x, y) = (i % map_w, i / map_w);
+            let right = labs[if x + 1 < map_w { i + 1 } else { i }];
+            let below = labs[if y + 1 < map_h { i + map_w } else { i }];
+            distance(labs[i], right) + distance(labs[i], below)
+        })
+        .collect();
+    let contrast: Vec<f32> = labs.iter().map(|&lab| distance(lab, mean)).collect();
+    let max_edge = edges.iter().copied().fold(f32::EPSILON, f32::max);
+    let max_contrast = contrast.iter().copied().fold(f32::EPSILON, f32::max);
+
+    // Summed-area table so each candidate window costs four lookups.
+    let mut table = vec![0.0f32; (map_w + 1) * (map_h + 1)];
+    for y in 0..map_h {
+        for x in 0..map_w {
+            let i = y * map_w + x;
+            let detail = edges[i] / max_edge + contrast[i] / max_contrast;
+            table[(y + 1) * (map_w + 1) + x + 1] =
+                detail + table[y * (map_w + 1) + x + 1] + table[(y + 1) * (map_w + 1) + x]
+                    - table[y * (map_w + 1) + x];
+        }
+    }
+    let sum = |x: usize, y: usize, cw: usize, ch: usize| {
+        let at = |x: usize, y: usize| table[y * (map_w + 1) + x];
+        at(x + cw, y + ch) - at(x, y + ch) - at(x + cw, y) + at(x, y)
+    };
+    let total = sum(0, 0, map_w, map_h);
+
+    let full = CropRect::flag_aspect(w, h);
+    let mut best = full;
+    for step in (0..SUBJECT_SCALE_STEPS).rev() {
+        let shrink = SUBJECT_MIN_SCALE
+            + (1.0 - SUBJECT_MIN_SCALE) * step as f32 / (SUBJECT_SCALE_STEPS - 1) as f32;
+        let cw = ((full.w * shrink * map_w as f32).round() as usize).clamp(1, map_w);
+        let ch = ((full.h * shrink * map_h as f32).round() as usize).clamp(1, map_h);
+
+        let (score, x, y) = (0..=map_h - ch)
+            .flat_map(|y| (0..=map_w - cw).map(move |x| (x, y)))
+            .map(|(x, y)| (sum(x, y, cw, ch), x, y))
+            .fold((f32::MIN, 0, 0), |top, c| if c.0 > top.0 { c } else { top });
+        if score < total * SUBJECT_COVERAGE {
+            break;
+        }
+        best = CropRect {
+            x: x as f32 / map_w as f32,
+            y: y as f32 / map_h as f32,
+            w: full.w * shrink,
+            h: full.h * shrink,
+        };
+        best.translate(0.0, 0.0);
+    }
+    best
+}
+
+pub fn apply(source: &RgbaImage, rect: CropRect) -> RgbaImage {
+    let (x, y, w, h) = rect.to_pixels(source.width(), source.height());
+    imageops::crop_imm(source, x, y, w, h).to_image()
+}
+
+/// What the current pointer drag on the crop editor is doing.
+#[derive(Clone, Copy)]
+pub enum CropDrag {
+    Move,
+    /// Resizing from corner `0..4` (top-left, top-right, bottom-left,
+    /// bottom-right) with the opposite corner fixed.
+    Corner(usize),
+}
+
+/// Draws the source thumbnail with `rect` overlaid. Drag inside to move it,
+/// drag a corner handle to resize it, or click it and nudge with the arrow
+/// keys (one source pixel, ten with Shift).
+pub fn editor(
+    ui: &mut egui::Ui,
+    texture: &TextureHandle,
+    source_size: [u32; 2],
+    rect: &mut CropRect,
+    drag: &mut Option<CropDrag>,
+    lock_aspect: bool,
+) {
+    let [source_w, source_h] = source_size.map(|v| v.max(1) as f32);
+    let width = ui.available_width().min(EDITOR_MAX_WIDTH);
+    let size = Vec2::new(width, width * source_h / source_w);
+
+    let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+    let frame = response.rect;
+    let to_screen = |[x, y]: [f32; 2]| frame.min + Vec2::new(x, y) * frame.size();
+    let to_fraction = |pos: Pos2| {
+        let v = (pos - frame.min) / frame.size();
+        [v.x.clamp(0.0, 1.0), v.y.clamp(0.0, 1.0)]
+    };
+
+    if response.clicked() || response.drag_started() {
+        response.request_focus();
+    }
+    a11y::describe(
+        &response,
+        WidgetType::Other,
+        "Crop area; arrow keys move it, Shift moves it further",
+    );
+
+    if response.drag_started()
+        && let Some(pos) = response.interact_pointer_pos()
+    {
+        let near = (0..4).find(|&i| to_screen(rect.corner(i)).distance(pos) <= HANDLE_GRAB_RADIUS);
+        let crop = Rect::from_min_max(to_screen(rect.corner(0)), to_screen(rect.corner(3)));
+        *drag = match near {
+            Some(i) => Some(CropDrag::Corner(i)),
+            None if crop.contains(pos) => Some(CropDrag::Move),
+            None => None,
+        };
+    }
+
+    if response.dragged()
+        && let Some(pos) = response.interact_pointer_pos()
+    {
+        match *drag {
+            Some(CropDrag::Move) => {
+                let delta = response.drag_delta() / frame.size();
+                rect.translate(delta.x, delta.y);
+            }
+            Some(CropDrag::Corner(i)) => {
+                resize_from_corner(rect, i, to_fraction(pos), source_w / source_h, lock_aspect);
+            }
+            None => {}
+        }
+    }
+    if response.drag_stopped() {
+        *drag = None;
+    }
+
+    if response.has_focus() {
+        ui.memory_mut(|memory| {
+            memory.set_focus_lock_filter(
+                response.id,
+                egui::EventFilter {
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    ..Default::default()
+                },
+            )
+        });
+
+        let (dx, dy) = ui.input(|input| {
+            let step = if input.modifiers.shift { 10.0 } else { 1.0 };
+            let key = |key: egui::Key| if input.key_pressed(key) { step } else { 0.0 };
+            (
+                key(egui::Key::ArrowRight) - key(egui::Key::ArrowLeft),
+                key(egui::Key::ArrowDown) - key(egui::Key::ArrowUp),
+            )
+        });
+        rect.translate(dx / source_w, dy / source_h);
+    }
+
+    painter.image(
+        texture.id(),
+        frame,
+        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+        Color32::WHITE,
+    );
+
+    // Shade everything outside the crop.
+    let crop = Rect::from_min_max(to_screen(rect.corner(0)), to_screen(rect.corner(3)));
+    let shade = Color32::from_black_alpha(150);
+    for outside in [
+        Rect::from_min_max(frame.min, Pos2::new(frame.max.x, crop.min.y)),
+        Rect::from_min_max(Pos2::new(frame.min.x, crop.max.y), frame.max),
+        Rect::from_min_max(
+            Pos2::new(frame.min.x, crop.min.y),
+            Pos2::new(crop.min.x, crop.max.y),
+        ),
+        Rect::from_min_max(
+            Pos2::new(crop.max.x, crop.min.y),
+            Pos2::new(frame.max.x, crop.max.y),
+        ),
+    ] {
+        painter.rect_filled(outside, 0.0, shade);
+    }
+
+    let outline = if response.has_focus() {
+        ui.visuals().selection.stroke
+    } else {
+        Stroke::new(1.5, Color32::WHITE)
+    };
+    painter.rect_stroke(crop, 0.0, outline);
+    for i in 0..4 {
+        painter.rect_filled(
+            Rect::from_center_size(to_screen(rect.corner(i)), Vec2::splat(8.0)),
+            1.0,
+            Color32::WHITE,
+        );
+    }
+}
+
+/// Moves corner `i` to `pointer` while the opposite corner stays put. With
+/// `lock_aspect` the crop keeps the flag's pixel aspect ratio on a source of
+/// `source_aspect` (width / height).
+fn resize_from_corner(
+    rect: &mut CropRect,
+    i: usize,
+    pointer: [f32; 2],
+    source_aspect: f32,
+    lock_aspect: bool,
+) {
+    let anchor = rect.corner(3 - i);
+    let sign_x = if i.is_multiple_of(2) { -1.0 } else { 1.0 };
+    let sign_y = if i < 2 { -1.0 } else { 1.0 };
+
+    // Room available from the anchor towards the dragged corner.
+    let room_w = if sign_x < 0.0 {
+        anchor[0]
+    } else {
+        1.0 - anchor[0]
+    };
+    let room_h = if sign_y < 0.0 {
+        anchor[1]
+    } else {
+        1.0 - anchor[1]
+    };
+
+    let mut w = ((pointer[0] - anchor[0]) * sign_x).clamp(MIN_CROP_FRACTION, room_w);
+    let mut h = ((pointer[1] - anchor[1]) * sign_y).clamp(MIN_CROP_FRACTION, room_h);
+
+    if lock_aspect {
+        // Height fraction per width fraction that keeps the flag's aspect.
+        let ratio = source_aspect * IMAGE_HEIGHT as f32 / IMAGE_WIDTH as f32;
+        w = w.max(h / ratio).min(room_w).min(room_h / ratio);
+        h = w * ratio;
+    }
+
+    rect.w = w;
+    rect.h = h;
+    rect.x = if sign_x < 0.0 {
+        anchor[0] - w
+    } else {
+        anchor[0]
+    };
+    rect.y = if sign_y < 0.0 {
+        anchor[1] - h
+    } else {
+        anchor[1]
+    };
+}
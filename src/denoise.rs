@@ -0,0 +1,125 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum DenoiseMode {
+    #[default]
+    Off,
+    /// Per-channel median of the window; removes speckle, keeps hard edges.
+    Median,
+    /// Neighbours weighted by distance and color similarity; smooths noise
+    /// without blurring across edges.
+    Bilateral,
+}
+
+impl DenoiseMode {
+    pub const ALL: [DenoiseMode; 3] = [
+        DenoiseMode::Off,
+        DenoiseMode::Median,
+        DenoiseMode::Bilateral,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DenoiseMode::Off => "Off",
+            DenoiseMode::Median => "Median",
+            DenoiseMode::Bilateral => "Bilateral",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct DenoiseSettings {
+    pub mode: DenoiseMode,
+    /// Window radius in flag pixels, 1–3.
+    pub radius: u32,
+    /// Bilateral only: color difference (0–255 RGB units) treated as noise.
+    pub color_sigma: f32,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            mode: DenoiseMode::Off,
+            radius: 1,
+            color_sigma: 24.0,
+        }
+    }
+}
+
+/// Filters the flag-sized image so sensor noise is not amplified into
+/// rainbow speckle by palette mapping and dithering.
+pub fn denoise(img: &DynamicImage, settings: DenoiseSettings) -> DynamicImage {
+    match settings.mode {
+        DenoiseMode::Off => img.clone(),
+        DenoiseMode::Median => filter(img, settings.radius, median),
+        DenoiseMode::Bilateral => filter(img, settings.radius, |center, window| {
+            bilateral(center, window, settings)
+        }),
+    }
+}
+
+/// Runs `kernel(center, window)` for every pixel, where `window` holds the
+/// in-bounds neighbours within `radius` as `(dx, dy, rgb)`.
+fn filter(
+    img: &DynamicImage,
+    radius: u32,
+    kernel: impl Fn([u8; 3], &[(i32, i32, [u8; 3])]) -> [u8; 3],
+) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    let r = radius.max(1) as i32;
+    let mut window = Vec::with_capacity(((2 * r + 1) * (2 * r + 1)) as usize);
+
+    let mut out = RgbaImage::new(w, h);
+    for (x, y, pixel) in img.pixels() {
+        window.clear();
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx < 0 || ny < 0 || nx >= w as i32 || ny >= h as i32 {
+                    continue;
+                }
+                let n = img.get_pixel(nx as u32, ny as u32);
+                window.push((dx, dy, [n[0], n[1], n[2]]));
+            }
+        }
+
+        let [r, g, b] = kernel([pixel[0], pixel[1], pixel[2]], &window);
+        out.put_pixel(x, y, Rgba([r, g, b, pixel[3]]));
+    }
+    DynamicImage::ImageRgba8(out)
+}
+
+fn median(_center: [u8; 3], window: &[(i32, i32, [u8; 3])]) -> [u8; 3] {
+    std::array::from_fn(|c| {
+        let mut values: Vec<u8> = window.iter().map(|&(_, _, rgb)| rgb[c]).collect();
+        let mid = values.len() / 2;
+        *values.select_nth_unstable(mid).1
+    })
+}
+
+fn bilateral(
+    center: [u8; 3],
+    window: &[(i32, i32, [u8; 3])],
+    settings: DenoiseSettings,
+) -> [u8; 3] {
+    let space_sigma = settings.radius.max(1) as f32;
+    let color_sigma = settings.color_sigma.max(1.0);
+
+    let mut sum = [0.0f32; 3];
+    let mut total = 0.0;
+    for &(dx, dy, rgb) in window {
+        let spatial = (dx * dx + dy * dy) as f32 / (2.0 * space_sigma * space_sigma);
+        let color = (0..3)
+            .map(|c| (rgb[c] as f32 - center[c] as f32).powi(2))
+            .sum::<f32>()
+            / (2.0 * color_sigma * color_sigma);
+        let weight = (-spatial - color).exp();
+
+        for c in 0..3 {
+            sum[c] += rgb[c] as f32 * weight;
+        }
+        total += weight;
+    }
+
+    sum.map(|v| (v / total).round().clamp(0.0, 255.0) as u8)
+}
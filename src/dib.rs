@@ -0,0 +1,157 @@
+//! Reading CF_DIBV5 / CF_DIB clipboard bitmaps directly, for the images
+//! arboard rejects: padded rows, bottom-up or top-down order, bit-field
+//! masks, palettes and 32-bit bitmaps whose alpha is left at zero.
+
+use image::RgbaImage;
+
+// Standard clipboard formats.
+const CF_DIB: u32 = 8;
+pub const CF_DIBV5: u32 = 17;
+// biCompression values.
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+// Size of the BITMAPINFOHEADER every later header version extends.
+const INFO_HEADER_SIZE: usize = 40;
+// Offset of the red, green, blue and alpha masks in a V4/V5 header.
+const V4_MASKS: usize = 40;
+
+/// The clipboard bitmap, preferring CF_DIBV5 for its alpha mask.
+pub fn clipboard_image() -> Option<RgbaImage> {
+    let _clipboard = clipboard_win::Clipboard::new_attempts(10).ok()?;
+    [CF_DIBV5, CF_DIB].into_iter().find_map(|format| {
+        if !clipboard_win::raw::is_format_avail(format) {
+            return None;
+        }
+        let mut data = Vec::new();
+        clipboard_win::raw::get_vec(format, &mut data).ok()?;
+        decode(&data)
+    })
+}
+
+/// Decodes a packed DIB: a BITMAPINFO header, optional masks and color
+/// table, then the pixel rows.
+fn decode(data: &[u8]) -> Option<RgbaImage> {
+    let u16_at = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+
+    let header_size = u32_at(0)? as usize;
+    if header_size < INFO_HEADER_SIZE {
+        return None;
+    }
+    let width = u32_at(4)? as i32;
+    let height = u32_at(8)? as i32;
+    let bits = u16_at(14)? as usize;
+    let compression = u32_at(16)?;
+    let colors_used = u32_at(32)? as usize;
+    if width <= 0 || height == 0 {
+        return None;
+    }
+    let (w, h) = (width as usize, height.unsigned_abs() as usize);
+    // Positive heights are stored bottom row first.
+    let bottom_up = height > 0;
+
+    let mut offset = header_size;
+    let masks = match compression {
+        BI_RGB => match bits {
+            32 => [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000],
+            16 => [0x7C00, 0x03E0, 0x001F, 0],
+            _ => [0; 4],
+        },
+        BI_BITFIELDS | BI_ALPHABITFIELDS if header_size > INFO_HEADER_SIZE => [
+            u32_at(V4_MASKS)?,
+            u32_at(V4_MASKS + 4)?,
+            u32_at(V4_MASKS + 8)?,
+            u32_at(V4_MASKS + 12)?,
+        ],
+        BI_BITFIELDS | BI_ALPHABITFIELDS => {
+            // A plain info header is followed by the masks.
+            let count = if compression == BI_ALPHABITFIELDS {
+                4
+            } else {
+                3
+            };
+            let mut masks = [0; 4];
+            for (i, mask) in masks.iter_mut().take(count).enumerate() {
+                *mask = u32_at(offset + i * 4)?;
+            }
+            offset += count * 4;
+            masks
+        }
+        // RLE and embedded JPEG/PNG are left to arboard.
+        _ => return None,
+    };
+
+    let table: Vec<[u8; 3]> = if bits <= 8 {
+        let count = if colors_used == 0 {
+            1 << bits
+        } else {
+            colors_used
+        };
+        let table = (0..count)
+            .map(|i| {
+                let bgr = data.get(offset + i * 4..offset + i * 4 + 3)?;
+                Some([bgr[2], bgr[1], bgr[0]])
+            })
+            .collect::<Option<_>>()?;
+        offset += count * 4;
+        table
+    } else {
+        Vec::new()
+    };
+    if !matches!(bits, 1 | 4 | 8 | 16 | 24 | 32) {
+        return None;
+    }
+
+    // Rows are padded to whole DWORDs. A header claiming sizes that
+    // overflow is corrupt, not a reason to panic.
+    let stride = w.checked_mul(bits)?.div_ceil(32).checked_mul(4)?;
+    let pixels = data.get(offset..offset.checked_add(stride.checked_mul(h)?)?)?;
+    let channel = |value: u32, mask: u32| -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let max = mask >> mask.trailing_zeros();
+        (((value & mask) >> mask.trailing_zeros()) * 255 / max) as u8
+    };
+
+    let mut rgba = Vec::with_capacity(w.checked_mul(h)?.checked_mul(4)?);
+    for y in 0..h {
+        let row = if bottom_up { h - 1 - y } else { y };
+        let row = &pixels[row * stride..(row + 1) * stride];
+        for x in 0..w {
+            let pixel = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits;
+                    let index = (row[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+                    let [r, g, b] = *table.get(index as usize)?;
+                    [r, g, b, 255]
+                }
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                _ => {
+                    let bytes = bits / 8;
+                    let mut value = 0u32;
+                    for (i, &byte) in row[x * bytes..(x + 1) * bytes].iter().enumerate() {
+                        value |= (byte as u32) << (i * 8);
+                    }
+                    let [r, g, b, a] = masks;
+                    [
+                        channel(value, r),
+                        channel(value, g),
+                        channel(value, b),
+                        if a == 0 { 255 } else { channel(value, a) },
+                    ]
+                }
+            };
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+
+    // Many apps write 32-bit bitmaps with the alpha byte left at zero.
+    if rgba.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+    }
+    RgbaImage::from_raw(w as u32, h as u32, rgba)
+}
@@ -0,0 +1,517 @@
+use std::sync::OnceLock;
+
+use image::{DynamicImage, GenericImageView, GrayImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::color::Matcher;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+// How far (in 0–255 RGB units) an ordered-dither threshold may push a pixel.
+const THRESHOLD_SPREAD: f32 = 64.0;
+
+// Radius of the window `gradient_mask` measures local variance over.
+const VARIANCE_RADIUS: i32 = 2;
+
+// Per-channel standard deviation (0–255 units) below which a window counts as
+// flat at zero sensitivity; full sensitivity dithers everything non-constant.
+const MAX_FLAT_STDDEV: f32 = 8.0;
+
+// Largest per-channel difference (0–255 units) between neighbouring pixels
+// of a window that still reads as a smooth ramp; a bigger jump is a hard
+// edge, which dithering would only fray.
+const MAX_RAMP_STEP: i32 = 24;
+// Focus at or above which a pixel is matched exactly rather than via the LUT.
+const PRECISE_FOCUS: f32 = 0.5;
+
+// 64x64 tileable rank texture produced offline with void-and-cluster.
+const EMBEDDED_BLUE_NOISE: &[u8] = include_bytes!("blue_noise.png");
+
+// === MODES ===
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum DitherMode {
+    #[default]
+    None,
+    FloydSteinberg,
+    Atkinson,
+    JarvisJudiceNinke,
+    Stucki,
+    Sierra,
+    Bayer4,
+    Bayer8,
+    BlueNoise,
+}
+
+impl DitherMode {
+    pub const ALL: [DitherMode; 9] = [
+        DitherMode::None,
+        DitherMode::FloydSteinberg,
+        DitherMode::Atkinson,
+        DitherMode::JarvisJudiceNinke,
+        DitherMode::Stucki,
+        DitherMode::Sierra,
+        DitherMode::Bayer4,
+        DitherMode::Bayer8,
+        DitherMode::BlueNoise,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DitherMode::None => "None (nearest color)",
+            DitherMode::FloydSteinberg => "Floyd–Steinberg",
+            DitherMode::Atkinson => "Atkinson",
+            DitherMode::JarvisJudiceNinke => "Jarvis–Judice–Ninke",
+            DitherMode::Stucki => "Stucki",
+            DitherMode::Sierra => "Sierra",
+            DitherMode::Bayer4 => "Bayer 4x4",
+            DitherMode::Bayer8 => "Bayer 8x8",
+            DitherMode::BlueNoise => "Blue noise",
+        }
+    }
+
+    fn kernel(self) -> Option<&'static Kernel> {
+        match self {
+            DitherMode::FloydSteinberg => Some(&FLOYD_STEINBERG),
+            DitherMode::Atkinson => Some(&ATKINSON),
+            DitherMode::JarvisJudiceNinke => Some(&JARVIS_JUDICE_NINKE),
+            DitherMode::Stucki => Some(&STUCKI),
+            DitherMode::Sierra => Some(&SIERRA),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct DitherSettings {
+    pub mode: DitherMode,
+    /// Alternate scan direction on every row for error-diffusion kernels.
+    pub serpentine: bool,
+    /// 0.0–1.0 scale applied to diffused error and threshold offsets.
+    pub strength: f32,
+    /// Dither only where the source has smooth gradients and map flat fills
+    /// straight to their nearest color.
+    pub selective: bool,
+    /// 0.0–1.0; higher treats subtler variation as a gradient.
+    pub sensitivity: f32,
+}
+
+impl Default for DitherSettings {
+    fn default() -> Self {
+        Self {
+            mode: DitherMode::None,
+            serpentine: true,
+            strength: 1.0,
+            selective: false,
+            sensitivity: 0.5,
+        }
+    }
+}
+
+// === KERNELS ===
+/// An error-diffusion kernel: `(dx, dy, weight)` taps relative to the current
+/// pixel, with weights summing to at most `divisor`. `dx` is mirrored when a
+/// serpentine scan runs right-to-left.
+struct Kernel {
+    divisor: f32,
+    taps: &'static [(isize, usize, f32)],
+}
+
+const FLOYD_STEINBERG: Kernel = Kernel {
+    divisor: 16.0,
+    taps: &[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)],
+};
+
+// Only 6/8 of the error is diffused, which keeps highlights and shadows clean.
+const ATKINSON: Kernel = Kernel {
+    divisor: 8.0,
+    taps: &[
+        (1, 0, 1.0),
+        (2, 0, 1.0),
+        (-1, 1, 1.0),
+        (0, 1, 1.0),
+        (1, 1, 1.0),
+        (0, 2, 1.0),
+    ],
+};
+
+const JARVIS_JUDICE_NINKE: Kernel = Kernel {
+    divisor: 48.0,
+    taps: &[
+        (1, 0, 7.0),
+        (2, 0, 5.0),
+        (-2, 1, 3.0),
+        (-1, 1, 5.0),
+        (0, 1, 7.0),
+        (1, 1, 5.0),
+        (2, 1, 3.0),
+        (-2, 2, 1.0),
+        (-1, 2, 3.0),
+        (0, 2, 5.0),
+        (1, 2, 3.0),
+        (2, 2, 1.0),
+    ],
+};
+
+const STUCKI: Kernel = Kernel {
+    divisor: 42.0,
+    taps: &[
+        (1, 0, 8.0),
+        (2, 0, 4.0),
+        (-2, 1, 2.0),
+        (-1, 1, 4.0),
+        (0, 1, 8.0),
+        (1, 1, 4.0),
+        (2, 1, 2.0),
+        (-2, 2, 1.0),
+        (-1, 2, 2.0),
+        (0, 2, 4.0),
+        (1, 2, 2.0),
+        (2, 2, 1.0),
+    ],
+};
+
+const SIERRA: Kernel = Kernel {
+    divisor: 32.0,
+    taps: &[
+        (1, 0, 5.0),
+        (2, 0, 3.0),
+        (-2, 1, 2.0),
+        (-1, 1, 4.0),
+        (0, 1, 5.0),
+        (1, 1, 4.0),
+        (2, 1, 2.0),
+        (-1, 2, 2.0),
+        (0, 2, 3.0),
+        (1, 2, 2.0),
+    ],
+};
+
+// === ENGINE ===
+/// Per-pixel guidance (row-major, flag-sized) shared by every algorithm.
+struct Guide<'a> {
+    /// Pixels that may be dithered; `None` dithers everywhere.
+    gradient: Option<Vec<bool>>,
+    /// 0.0–1.0 per pixel: how strongly to shield it from dithering noise.
+    focus: Option<&'a [f32]>,
+}
+
+impl Guide<'_> {
+    fn dithers(&self, i: usize) -> bool {
+        self.gradient.as_ref().is_none_or(|gradient| gradient[i])
+    }
+
+    fn focus(&self, i: usize) -> f32 {
+        self.focus.map_or(0.0, |focus| focus[i])
+    }
+
+    fn nearest(&self, matcher: &Matcher, i: usize, rgb: [u8; 3]) -> usize {
+        if self.focus(i) >= PRECISE_FOCUS {
+            matcher.nearest_precise(rgb)
+        } else {
+            matcher.nearest(rgb)
+        }
+    }
+}
+
+/// Maps the flag-sized image to palette indices (row-major) using the
+/// selected dithering algorithm. `focus` (see `saliency::focus_map`) steers
+/// dithering noise away from important pixels and matches them exactly.
+pub fn quantize(
+    img: &DynamicImage,
+    matcher: &Matcher,
+    settings: DitherSettings,
+    focus: Option<&[f32]>,
+) -> Vec<usize> {
+    let guide = Guide {
+        gradient: (settings.selective && settings.mode != DitherMode::None)
+            .then(|| gradient_mask(img, settings.sensitivity)),
+        focus,
+    };
+
+    match settings.mode {
+        DitherMode::None => map_nearest(img, matcher, &guide),
+        DitherMode::Bayer4 => dither_bayer(img, matcher, 4, settings.strength, &guide),
+        DitherMode::Bayer8 => dither_bayer(img, matcher, 8, settings.strength, &guide),
+        DitherMode::BlueNoise => dither_blue_noise(img, matcher, settings.strength, &guide),
+        mode => {
+            let kernel = mode
+                .kernel()
+                .expect("error-diffusion mode without a kernel");
+            diffuse_error(
+                img,
+                matcher,
+                kernel,
+                settings.serpentine,
+                settings.strength,
+                &guide,
+            )
+        }
+    }
+}
+
+/// Marks the pixels (row-major) whose neighbourhood varies by more than the
+/// flat threshold `sensitivity` implies, but only in steps small enough to
+/// be a ramp. Flat fills and hard edges stay unmarked so they map to clean
+/// colors.
+fn gradient_mask(img: &DynamicImage, sensitivity: f32) -> Vec<bool> {
+    let threshold = (1.0 - sensitivity.clamp(0.0, 1.0)) * MAX_FLAT_STDDEV;
+    let (w, h) = (IMAGE_WIDTH as i32, IMAGE_HEIGHT as i32);
+
+    let mut mask = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = [0.0f32; 3];
+            let mut sum_sq = [0.0f32; 3];
+            let mut count = 0.0;
+            let mut step = 0;
+
+            for dy in -VARIANCE_RADIUS..=VARIANCE_RADIUS {
+                for dx in -VARIANCE_RADIUS..=VARIANCE_RADIUS {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= w || ny >= h {
+                        continue;
+                    }
+                    let pixel = img.get_pixel(nx as u32, ny as u32);
+                    for c in 0..3 {
+                        let v = pixel[c] as f32;
+                        sum[c] += v;
+                        sum_sq[c] += v * v;
+                    }
+                    count += 1.0;
+
+                    // Steps to the right and down, within the window.
+                    for (sx, sy) in [(nx + 1, ny), (nx, ny + 1)] {
+                        if sx >= w.min(x + VARIANCE_RADIUS + 1)
+                            || sy >= h.min(y + VARIANCE_RADIUS + 1)
+                        {
+                            continue;
+                        }
+                        let next = img.get_pixel(sx as u32, sy as u32);
+                        for c in 0..3 {
+                            step = step.max((pixel[c] as i32 - next[c] as i32).abs());
+                        }
+                    }
+                }
+            }
+
+            let variance = (0..3)
+                .map(|c| (sum_sq[c] / count - (sum[c] / count).powi(2)).max(0.0))
+                .sum::<f32>()
+                / 3.0;
+            let stddev = variance.sqrt();
+            mask.push(stddev > threshold && stddev > 0.0 && step <= MAX_RAMP_STEP);
+        }
+    }
+
+    mask
+}
+
+fn map_nearest(img: &DynamicImage, matcher: &Matcher, guide: &Guide) -> Vec<usize> {
+    map_rows(matcher, guide, |x, y| {
+        let pixel = img.get_pixel(x, y);
+        [pixel[0], pixel[1], pixel[2]]
+    })
+}
+
+/// Matches the colors produced by `pixel(x, y)` into row-major indices, one
+/// scanline per rayon task. Output order is identical to a sequential scan.
+fn map_rows(
+    matcher: &Matcher,
+    guide: &Guide,
+    pixel: impl Fn(u32, u32) -> [u8; 3] + Sync,
+) -> Vec<usize> {
+    let mut indices = vec![0; (IMAGE_WIDTH * IMAGE_HEIGHT) as usize];
+
+    indices
+        .par_chunks_mut(IMAGE_WIDTH as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let rgb: Vec<[u8; 3]> = (0..IMAGE_WIDTH).map(|x| pixel(x, y as u32)).collect();
+            matcher.nearest_row(&rgb, row);
+
+            if guide.focus.is_some() {
+                let start = y * IMAGE_WIDTH as usize;
+                for (x, slot) in row.iter_mut().enumerate() {
+                    if guide.focus(start + x) >= PRECISE_FOCUS {
+                        *slot = matcher.nearest_precise(rgb[x]);
+                    }
+                }
+            }
+        });
+
+    indices
+}
+
+/// Error diffusion: each pixel's quantization error is pushed onto its
+/// unvisited neighbours according to `kernel` before they are mapped. Focused
+/// neighbours take a smaller share, the rest going to the others.
+fn diffuse_error(
+    img: &DynamicImage,
+    matcher: &Matcher,
+    kernel: &Kernel,
+    serpentine: bool,
+    strength: f32,
+    guide: &Guide,
+) -> Vec<usize> {
+    let w = IMAGE_WIDTH as usize;
+    let h = IMAGE_HEIGHT as usize;
+
+    let mut buffer: Vec<[f32; 3]> = Vec::with_capacity(w * h);
+    for y in 0..IMAGE_HEIGHT {
+        for x in 0..IMAGE_WIDTH {
+            let pixel = img.get_pixel(x, y);
+            buffer.push([pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]);
+        }
+    }
+
+    let mut indices = vec![0; w * h];
+
+    for y in 0..h {
+        let reversed = serpentine && y % 2 == 1;
+        let direction: isize = if reversed { -1 } else { 1 };
+
+        for step in 0..w {
+            let x = if reversed { w - 1 - step } else { step };
+
+            let i = y * w + x;
+
+            // Flat pixels ignore incoming error and push none of their own.
+            if !guide.dithers(i) {
+                let pixel = img.get_pixel(x as u32, y as u32);
+                indices[i] = guide.nearest(matcher, i, [pixel[0], pixel[1], pixel[2]]);
+                continue;
+            }
+
+            let old = buffer[i];
+            let rgb = old.map(|c| c.round().clamp(0.0, 255.0) as u8);
+            let idx = guide.nearest(matcher, i, rgb);
+            indices[i] = idx;
+
+            let chosen = matcher.color(idx);
+            let error = [
+                (old[0] - chosen[0] as f32) * strength,
+                (old[1] - chosen[1] as f32) * strength,
+                (old[2] - chosen[2] as f32) * strength,
+            ];
+
+            let targets: Vec<(usize, f32)> = kernel
+                .taps
+                .iter()
+                .filter_map(|&(dx, dy, weight)| {
+                    let nx = x as isize + dx * direction;
+                    let ny = y + dy;
+                    (nx >= 0 && nx < w as isize && ny < h).then(|| (ny * w + nx as usize, weight))
+                })
+                .collect();
+
+            // Shrink each tap by its target's focus, then rescale so the same
+            // total error is still diffused.
+            let total: f32 = targets.iter().map(|&(_, weight)| weight).sum();
+            let shielded: f32 = targets
+                .iter()
+                .map(|&(n, weight)| weight * (1.0 - guide.focus(n)))
+                .sum();
+            if shielded <= 0.0 {
+                continue;
+            }
+
+            for (n, weight) in targets {
+                let factor = weight * (1.0 - guide.focus(n)) / shielded * total / kernel.divisor;
+                for c in 0..3 {
+                    buffer[n][c] += error[c] * factor;
+                }
+            }
+        }
+    }
+
+    indices
+}
+
+/// Ordered dithering with an `n`x`n` Bayer matrix (`n` a power of two), which
+/// gives a stable repeating texture instead of diffusion noise.
+fn dither_bayer(
+    img: &DynamicImage,
+    matcher: &Matcher,
+    n: u32,
+    strength: f32,
+    guide: &Guide,
+) -> Vec<usize> {
+    let matrix = bayer_matrix(n);
+    let levels = (n * n) as f32;
+
+    dither_threshold(img, matcher, strength, guide, |x, y| {
+        (matrix[((y % n) * n + x % n) as usize] as f32 + 0.5) / levels - 0.5
+    })
+}
+
+/// Threshold dithering against a tiled blue-noise texture. Unlike Bayer it has
+/// no visible cross-hatch, which matters at 100x66.
+fn dither_blue_noise(
+    img: &DynamicImage,
+    matcher: &Matcher,
+    strength: f32,
+    guide: &Guide,
+) -> Vec<usize> {
+    let noise = blue_noise();
+    let (w, h) = noise.dimensions();
+
+    dither_threshold(img, matcher, strength, guide, |x, y| {
+        (noise.get_pixel(x % w, y % h)[0] as f32 + 0.5) / 256.0 - 0.5
+    })
+}
+
+/// Offsets each pixel by `threshold(x, y)` (in -0.5..0.5) scaled to
+/// `THRESHOLD_SPREAD * strength` before the nearest-color lookup. Flat pixels
+/// get no offset and focused ones a proportionally smaller one.
+fn dither_threshold(
+    img: &DynamicImage,
+    matcher: &Matcher,
+    strength: f32,
+    guide: &Guide,
+    threshold: impl Fn(u32, u32) -> f32 + Sync,
+) -> Vec<usize> {
+    map_rows(matcher, guide, |x, y| {
+        let i = (y * IMAGE_WIDTH + x) as usize;
+        let offset = if guide.dithers(i) {
+            threshold(x, y) * THRESHOLD_SPREAD * strength * (1.0 - guide.focus(i))
+        } else {
+            0.0
+        };
+
+        let pixel = img.get_pixel(x, y);
+        [pixel[0], pixel[1], pixel[2]].map(|c| (c as f32 + offset).round().clamp(0.0, 255.0) as u8)
+    })
+}
+
+fn blue_noise() -> &'static GrayImage {
+    static NOISE: OnceLock<GrayImage> = OnceLock::new();
+    NOISE.get_or_init(|| {
+        image::load_from_memory(EMBEDDED_BLUE_NOISE)
+            .expect("Invalid embedded blue-noise texture")
+            .to_luma8()
+    })
+}
+
+/// Builds the `n`x`n` Bayer index matrix (row-major) by recursive doubling.
+fn bayer_matrix(n: u32) -> Vec<u32> {
+    let mut matrix = vec![0u32];
+    let mut size = 1;
+
+    while size < n {
+        let next = size * 2;
+        let mut grown = vec![0u32; (next * next) as usize];
+        for y in 0..size {
+            for x in 0..size {
+                let v = 4 * matrix[(y * size + x) as usize];
+                grown[(y * next + x) as usize] = v;
+                grown[(y * next + x + size) as usize] = v + 2;
+                grown[((y + size) * next + x) as usize] = v + 3;
+                grown[((y + size) * next + x + size) as usize] = v + 1;
+            }
+        }
+        matrix = grown;
+        size = next;
+    }
+
+    matrix
+}
@@ -0,0 +1,173 @@
+//! Flag pixels painted by hand, drawn over the encoded flag as a layer so
+//! they survive re-encoding while the source stays the same.
+
+use std::collections::BTreeMap;
+
+use crate::overlay::Layer;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+#[derive(Clone, Default, PartialEq)]
+pub struct PixelEdits {
+    /// Palette index per edited (x, y).
+    cells: BTreeMap<(u32, u32), usize>,
+}
+
+impl PixelEdits {
+    /// Paints one pixel, ignoring coordinates off the flag.
+    pub fn set(&mut self, x: i32, y: i32, index: usize) {
+        if (0..IMAGE_WIDTH as i32).contains(&x) && (0..IMAGE_HEIGHT as i32).contains(&y) {
+            self.cells.insert((x as u32, y as u32), index);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn draw(&self, layer: &mut Layer) {
+        for (&(x, y), &index) in &self.cells {
+            layer.set(x as i32, y as i32, index);
+        }
+    }
+}
+
+/// A tool of the flag editor.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Tool {
+    #[default]
+    Pencil,
+    /// Recolors the area of one color around the clicked pixel, or every
+    /// pixel of that color.
+    Fill,
+    /// Shapes are dragged out from corner to corner and stamped on release.
+    Line,
+    Rectangle,
+    Ellipse,
+    /// Picks the palette color of the clicked pixel to paint with.
+    Eyedropper,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 6] = [
+        Tool::Pencil,
+        Tool::Fill,
+        Tool::Line,
+        Tool::Rectangle,
+        Tool::Ellipse,
+        Tool::Eyedropper,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Tool::Pencil => "✏ Pencil",
+            Tool::Fill => "Bucket fill",
+            Tool::Line => "Line",
+            Tool::Rectangle => "Rectangle",
+            Tool::Ellipse => "Ellipse",
+            Tool::Eyedropper => "Eyedropper",
+        }
+    }
+}
+
+/// The pixels of a shape dragged from `from` to `to`, both included; the
+/// rectangle and ellipse fill the box between them when `filled`, or trace
+/// its outline.
+pub fn shape(tool: Tool, from: (i32, i32), to: (i32, i32), filled: bool) -> Vec<(i32, i32)> {
+    let (x0, x1) = (from.0.min(to.0), from.0.max(to.0));
+    let (y0, y1) = (from.1.min(to.1), from.1.max(to.1));
+    let inside: Box<dyn Fn(i32, i32) -> bool> = match tool {
+        Tool::Line => return line(from, to),
+        Tool::Rectangle => Box::new(move |x, y| (x0..=x1).contains(&x) && (y0..=y1).contains(&y)),
+        Tool::Ellipse => {
+            // Measured between pixel centers so a 1-pixel box is one pixel.
+            let (cx, cy) = ((x0 + x1 + 1) as f32 / 2.0, (y0 + y1 + 1) as f32 / 2.0);
+            let (rx, ry) = ((x1 - x0 + 1) as f32 / 2.0, (y1 - y0 + 1) as f32 / 2.0);
+            Box::new(move |x, y| {
+                let dx = (x as f32 + 0.5 - cx) / rx;
+                let dy = (y as f32 + 0.5 - cy) / ry;
+                dx * dx + dy * dy <= 1.0
+            })
+        }
+        Tool::Pencil | Tool::Fill | Tool::Eyedropper => return Vec::new(),
+    };
+
+    (y0..=y1)
+        .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+        .filter(|&(x, y)| inside(x, y))
+        .filter(|&(x, y)| {
+            filled
+                || [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .into_iter()
+                    .any(|(nx, ny)| !inside(nx, ny))
+        })
+        .collect()
+}
+
+/// The pixels sharing the palette index of (`x`, `y`) in the row-major
+/// flag `indices`: only those connected to it edge to edge when
+/// `contiguous`, otherwise all of them.
+pub fn fill(indices: &[usize], x: i32, y: i32, contiguous: bool) -> Vec<(i32, i32)> {
+    let (w, h) = (IMAGE_WIDTH as i32, IMAGE_HEIGHT as i32);
+    if !(0..w).contains(&x) || !(0..h).contains(&y) || indices.len() != (w * h) as usize {
+        return Vec::new();
+    }
+    let at = |x: i32, y: i32| indices[(y * w + x) as usize];
+    let target = at(x, y);
+
+    if !contiguous {
+        return (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .filter(|&(x, y)| at(x, y) == target)
+            .collect();
+    }
+
+    let mut seen = vec![false; indices.len()];
+    let mut stack = vec![(x, y)];
+    let mut pixels = Vec::new();
+    seen[(y * w + x) as usize] = true;
+    while let Some((x, y)) = stack.pop() {
+        pixels.push((x, y));
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if (0..w).contains(&nx)
+                && (0..h).contains(&ny)
+                && !seen[(ny * w + nx) as usize]
+                && at(nx, ny) == target
+            {
+                seen[(ny * w + nx) as usize] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+    pixels
+}
+
+/// The pixels on the straight line from `from` to `to`, so a fast drag
+/// leaves no gaps.
+pub fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    let mut pixels = vec![from];
+    while (x, y) != to {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+        pixels.push((x, y));
+    }
+    pixels
+}
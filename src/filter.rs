@@ -0,0 +1,140 @@
+use image::{DynamicImage, Rgba};
+use palette::{FromColor, Lab, Mix, Srgb};
+
+use crate::adjust::LUMA;
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::{PALETTE_DARKEST, PALETTE_LIGHTEST};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum FilterMode {
+    #[default]
+    Off,
+    Grayscale,
+    Sepia,
+    /// Luminance mapped onto a ramp between two palette colors.
+    Duotone,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 4] = [
+        FilterMode::Off,
+        FilterMode::Grayscale,
+        FilterMode::Sepia,
+        FilterMode::Duotone,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterMode::Off => "Off",
+            FilterMode::Grayscale => "Grayscale",
+            FilterMode::Sepia => "Sepia",
+            FilterMode::Duotone => "Duotone",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct FilterSettings {
+    pub mode: FilterMode,
+    /// Palette indices the shadows and highlights of a duotone map to.
+    pub dark: usize,
+    pub light: usize,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::default(),
+            dark: PALETTE_DARKEST,
+            light: PALETTE_LIGHTEST,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    /// 0.0–1.0; how dark the corners get.
+    pub strength: f32,
+    /// 0.0–1.0; how far in from the corners the falloff starts.
+    pub feather: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.5,
+            feather: 0.6,
+        }
+    }
+}
+
+pub fn apply(img: &DynamicImage, settings: FilterSettings, palette: &[[u8; 3]]) -> DynamicImage {
+    let ramp = match settings.mode {
+        FilterMode::Off => return img.clone(),
+        FilterMode::Duotone => Some(duotone_ramp(
+            palette[settings.dark.min(palette.len() - 1)],
+            palette[settings.light.min(palette.len() - 1)],
+        )),
+        _ => None,
+    };
+
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0.map(|c| c as f32);
+        let luma = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+        let [r, g, b] = match settings.mode {
+            FilterMode::Sepia => [
+                0.393 * r + 0.769 * g + 0.189 * b,
+                0.349 * r + 0.686 * g + 0.168 * b,
+                0.272 * r + 0.534 * g + 0.131 * b,
+            ],
+            FilterMode::Duotone => {
+                let ramp = ramp.as_ref().unwrap();
+                ramp[luma.round().clamp(0.0, 255.0) as usize].map(|c| c as f32)
+            }
+            _ => [luma; 3],
+        };
+        *pixel = Rgba([
+            r.round().clamp(0.0, 255.0) as u8,
+            g.round().clamp(0.0, 255.0) as u8,
+            b.round().clamp(0.0, 255.0) as u8,
+            a as u8,
+        ]);
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Darkens toward the corners in linear light, with a smoothstep falloff
+/// over an ellipse matched to the flag's aspect.
+pub fn vignette(img: &DynamicImage, settings: VignetteSettings) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    let (w, h) = (rgba.width() as f32, rgba.height() as f32);
+    let outer = std::f32::consts::SQRT_2;
+    let inner = outer * (1.0 - settings.feather.clamp(0.05, 1.0));
+
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let dx = (x as f32 + 0.5) / w * 2.0 - 1.0;
+        let dy = (y as f32 + 0.5) / h * 2.0 - 1.0;
+        let t = (((dx * dx + dy * dy).sqrt() - inner) / (outer - inner)).clamp(0.0, 1.0);
+        let gain = 1.0 - settings.strength * t * t * (3.0 - 2.0 * t);
+        for c in 0..3 {
+            pixel.0[c] = linear_to_srgb(srgb_to_linear(pixel.0[c]) * gain);
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// 256 steps from `dark` to `light`, interpolated in Lab so the midtones
+/// don't muddy the way a straight RGB blend of two hues does.
+fn duotone_ramp(dark: [u8; 3], light: [u8; 3]) -> [[u8; 3]; 256] {
+    let lab = |[r, g, b]: [u8; 3]| Lab::from_color(Srgb::new(r, g, b).into_format::<f32>());
+    let (dark, light) = (lab(dark), lab(light));
+
+    std::array::from_fn(|i| {
+        let mixed = Srgb::from_color(dark.mix(light, i as f32 / 255.0));
+        let rgb: Srgb<u8> = mixed.into_format();
+        [rgb.red, rgb.green, rgb.blue]
+    })
+}
@@ -0,0 +1,64 @@
+use image::{Rgba, RgbaImage, imageops};
+use serde::{Deserialize, Serialize};
+
+use crate::crop::{self, CropRect};
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+/// How a source whose aspect ratio is not 100:66 is fitted to the flag.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum FitMode {
+    /// Scale each axis independently (the original behaviour).
+    #[default]
+    Stretch,
+    /// Fit the whole image and fill the leftover bars with a palette color.
+    Letterbox,
+    /// Fill the flag and trim the overflow, keeping the center.
+    CropToFill,
+}
+
+impl FitMode {
+    pub const ALL: [FitMode; 3] = [FitMode::Stretch, FitMode::Letterbox, FitMode::CropToFill];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FitMode::Stretch => "Stretch",
+            FitMode::Letterbox => "Letterbox",
+            FitMode::CropToFill => "Crop to fill",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct FitSettings {
+    pub mode: FitMode,
+    /// Palette index that fills the letterbox bars.
+    pub background: usize,
+}
+
+/// Reshapes `source` to the flag's aspect ratio according to `settings`, at
+/// source resolution so the resampler still sees every pixel.
+pub fn apply(source: &RgbaImage, settings: FitSettings, palette: &[[u8; 3]]) -> RgbaImage {
+    let (w, h) = source.dimensions();
+    match settings.mode {
+        FitMode::Stretch => source.clone(),
+        FitMode::CropToFill => crop::apply(source, CropRect::flag_aspect(w, h)),
+        FitMode::Letterbox => {
+            let flag = IMAGE_WIDTH as f32 / IMAGE_HEIGHT as f32;
+            let (canvas_w, canvas_h) = if w as f32 / h as f32 > flag {
+                (w, ((w as f32 / flag).round() as u32).max(h))
+            } else {
+                (((h as f32 * flag).round() as u32).max(w), h)
+            };
+
+            let [r, g, b] = palette[settings.background.min(palette.len() - 1)];
+            let mut canvas = RgbaImage::from_pixel(canvas_w, canvas_h, Rgba([r, g, b, 255]));
+            imageops::overlay(
+                &mut canvas,
+                source,
+                ((canvas_w - w) / 2) as i64,
+                ((canvas_h - h) / 2) as i64,
+            );
+            canvas
+        }
+    }
+}
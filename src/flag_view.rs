@@ -0,0 +1,826 @@
+//! The encoded flag shown under the source controls, optionally next to or
+//! under a slider with the resized source it was quantized from. Ctrl+wheel
+//! zooms and dragging pans; close up, a grid outlines every flag pixel. In
+//! edit mode dragging paints pixels instead and the right button pans.
+//! Game filtering previews the bilinear smoothing the game applies, and
+//! the cloth option ripples the flag like the banner it is drawn on. The
+//! flag can also be shown as seen with a color vision deficiency. Once
+//! clicked or tabbed to, the arrow keys pan and +/- zoom.
+
+use eframe::egui;
+use std::f32::consts::TAU;
+
+use egui::epaint::{Mesh, Vertex};
+use egui::{Color32, PointerButton, Rect, Response, Sense, Stroke, WidgetInfo, WidgetType};
+
+use crate::a11y;
+use crate::colorblind::Vision;
+use crate::edit::{self, PixelEdits, Tool};
+use crate::palettes::Palette;
+use crate::{FLAG_PREVIEW_MAX_SCALE, IMAGE_HEIGHT, IMAGE_WIDTH, PREVIEW_SCALE, PreviewTextures};
+
+// Deepest zoom, as a multiple of the fitted view.
+const MAX_ZOOM: f32 = 16.0;
+// Zoom change per click of the +/- buttons.
+const ZOOM_STEP: f32 = 1.5;
+// Distance an arrow key pans, as a fraction of the visible part, and moves
+// the comparison slider.
+const PAN_STEP: f32 = 0.1;
+const SPLIT_STEP: f32 = 0.05;
+// Screen size a flag pixel needs before the grid is drawn, in points.
+const GRID_MIN_CELL: f32 = 6.0;
+const GRID_COLOR: Color32 = Color32::from_black_alpha(110);
+// Composition guides, as unmultiplied RGBA: evenly spaced lines, the center
+// lines and thirds.
+const GUIDE_COLOR: [u8; 4] = [0, 209, 255, 110];
+const GUIDE_CENTER_COLOR: [u8; 4] = [0, 200, 255, 200];
+const GUIDE_THIRDS_COLOR: [u8; 4] = [230, 180, 0, 200];
+// Cloth simulation: mesh resolution, ripples across the flag, their height
+// as a fraction of the flag's and how much the folds darken.
+const CLOTH_COLUMNS: u32 = 48;
+const CLOTH_ROWS: u32 = 8;
+const CLOTH_WAVES: f32 = 2.5;
+const CLOTH_AMPLITUDE: f32 = 0.04;
+const CLOTH_SHADE: f32 = 0.35;
+// Width of each ring of the mat around the flag: a light one against its
+// edge inside a dark one, so the edge shows whatever colors it has.
+const MAT_WIDTH: f32 = 1.5;
+const MAT_LIGHT: Color32 = Color32::from_gray(230);
+const MAT_DARK: Color32 = Color32::from_gray(20);
+// Side of a palette swatch in the editor, in points.
+const SWATCH_SIZE: f32 = 18.0;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Compare {
+    #[default]
+    Off,
+    SideBySide,
+    /// The source drawn over the flag left of a draggable divider.
+    Slider,
+    /// Pixels that differ from the flag in the registry highlighted.
+    Changes,
+}
+
+impl Compare {
+    pub const ALL: [Compare; 4] = [
+        Compare::Off,
+        Compare::SideBySide,
+        Compare::Slider,
+        Compare::Changes,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Compare::Off => "Flag",
+            Compare::SideBySide => "Side by side",
+            Compare::Slider => "Slider",
+            Compare::Changes => "Changes",
+        }
+    }
+}
+
+/// Alignment lines drawn over every view of the flag.
+#[derive(Clone, Copy)]
+pub struct Guides {
+    pub enabled: bool,
+    /// Flag pixels between evenly spaced lines; 0 draws none.
+    pub spacing: u32,
+    pub center: bool,
+    pub thirds: bool,
+}
+
+impl Default for Guides {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            spacing: 10,
+            center: true,
+            thirds: false,
+        }
+    }
+}
+
+impl Guides {
+    /// Flag-pixel positions of the lines across one axis of `len` pixels,
+    /// with their colors.
+    fn lines(&self, len: u32) -> Vec<(f32, Color32)> {
+        let color = |[r, g, b, a]: [u8; 4]| Color32::from_rgba_unmultiplied(r, g, b, a);
+        let mut lines = Vec::new();
+        if self.spacing > 0 {
+            lines.extend(
+                (self.spacing..len)
+                    .step_by(self.spacing as usize)
+                    .map(|at| (at as f32, color(GUIDE_COLOR))),
+            );
+        }
+        if self.thirds {
+            lines.push((len as f32 / 3.0, color(GUIDE_THIRDS_COLOR)));
+            lines.push((len as f32 * 2.0 / 3.0, color(GUIDE_THIRDS_COLOR)));
+        }
+        if self.center {
+            lines.push((len as f32 / 2.0, color(GUIDE_CENTER_COLOR)));
+        }
+        lines
+    }
+}
+
+pub struct FlagView {
+    pub compare: Compare,
+    /// Divider position of the slider, as a fraction of the width.
+    split: f32,
+    /// Magnification over the fitted view; 1 shows the whole flag.
+    zoom: f32,
+    /// Center of the view in texture coordinates.
+    center: egui::Pos2,
+    pub grid: bool,
+    pub guides: Guides,
+    /// Sample the flag bilinearly as the game does.
+    pub filtering: bool,
+    /// Ripple and shade the filtered flag like cloth.
+    pub cloth: bool,
+    pub vision: Vision,
+    /// Dragging over the flag paints `color` instead of panning.
+    pub editing: bool,
+    tool: Tool,
+    /// Fill only the connected area rather than every pixel of its color.
+    contiguous: bool,
+    /// Palette index painted with.
+    color: usize,
+    /// Pixel painted last in the current stroke.
+    last_painted: Option<(i32, i32)>,
+    /// Fill rectangles and ellipses rather than outline them.
+    filled: bool,
+    /// Pixel a shape drag started on.
+    shape_start: Option<(i32, i32)>,
+    /// Pixel the tools work on from the keyboard.
+    cursor: (i32, i32),
+}
+
+impl Default for FlagView {
+    fn default() -> Self {
+        Self {
+            compare: Compare::default(),
+            split: 0.5,
+            zoom: 1.0,
+            center: egui::pos2(0.5, 0.5),
+            grid: true,
+            guides: Guides::default(),
+            filtering: false,
+            cloth: false,
+            vision: Vision::default(),
+            editing: false,
+            tool: Tool::default(),
+            contiguous: true,
+            color: 0,
+            last_painted: None,
+            filled: false,
+            shape_start: None,
+            cursor: (0, 0),
+        }
+    }
+}
+
+impl FlagView {
+    /// Draws the view; in edit mode strokes go into `edits`, in colors of
+    /// `palette`, with tools that read the flag's current `indices`.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        textures: &PreviewTextures,
+        in_game: bool,
+        palette: &Palette,
+        indices: &[usize],
+        edits: &mut PixelEdits,
+    ) {
+        ui.horizontal(|ui| {
+            for compare in Compare::ALL {
+                ui.selectable_value(&mut self.compare, compare, compare.label());
+            }
+            if ui.toggle_value(&mut self.editing, "✏ Edit").changed() && self.editing {
+                self.compare = Compare::Off;
+            }
+            ui.separator();
+            if ui.small_button("−").clicked() {
+                self.set_zoom(self.zoom / ZOOM_STEP, self.center);
+            }
+            ui.label(format!("{:.0}%", self.zoom * 100.0));
+            if ui.small_button("+").clicked() {
+                self.set_zoom(self.zoom * ZOOM_STEP, self.center);
+            }
+            if ui.small_button("Fit").clicked() {
+                self.set_zoom(1.0, self.center);
+            }
+            ui.checkbox(&mut self.grid, "Grid");
+            ui.checkbox(&mut self.guides.enabled, "Guides");
+            ui.checkbox(&mut self.filtering, "Game filtering")
+                .on_hover_text("Blur the flag as the game's bilinear filtering does on the cloth");
+            if self.filtering {
+                ui.checkbox(&mut self.cloth, "Cloth");
+            }
+            egui::ComboBox::new("flag_vision", "Vision")
+                .selected_text(self.vision.label())
+                .show_ui(ui, |ui| {
+                    for vision in Vision::ALL {
+                        ui.selectable_value(&mut self.vision, vision, vision.label());
+                    }
+                })
+                .response
+                .on_hover_text("Check the flag stays readable for colorblind players");
+        });
+        if self.guides.enabled {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.guides.spacing)
+                        .clamp_range(0..=IMAGE_HEIGHT / 2)
+                        .prefix("Every ")
+                        .suffix(" px"),
+                )
+                .on_hover_text("0 hides the evenly spaced lines");
+                ui.checkbox(&mut self.guides.center, "Center");
+                ui.checkbox(&mut self.guides.thirds, "Thirds");
+            });
+        }
+        if self.compare != Compare::Off {
+            self.editing = false;
+        }
+        if self.editing {
+            self.color = self.color.min(palette.colors.len() - 1);
+            ui.horizontal(|ui| {
+                for tool in Tool::ALL {
+                    ui.selectable_value(&mut self.tool, tool, tool.label());
+                }
+                if self.tool == Tool::Fill {
+                    ui.checkbox(&mut self.contiguous, "Contiguous")
+                        .on_hover_text("Off recolors every pixel of the clicked color");
+                }
+                if matches!(self.tool, Tool::Rectangle | Tool::Ellipse) {
+                    ui.checkbox(&mut self.filled, "Filled");
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing = egui::vec2(2.0, 2.0);
+                for (idx, &[r, g, b]) in palette.colors.iter().enumerate() {
+                    let (rect, response) = ui
+                        .allocate_exact_size(egui::vec2(SWATCH_SIZE, SWATCH_SIZE), Sense::click());
+                    let selected = idx == self.color;
+                    response.widget_info(|| {
+                        WidgetInfo::selected(
+                            WidgetType::SelectableLabel,
+                            selected,
+                            format!("Color {idx}"),
+                        )
+                    });
+                    let outline = if selected {
+                        Stroke::new(2.0, ui.visuals().selection.stroke.color)
+                    } else if response.hovered() || response.has_focus() {
+                        ui.visuals().widgets.hovered.fg_stroke
+                    } else {
+                        Stroke::new(1.0, GRID_COLOR)
+                    };
+                    ui.painter()
+                        .rect(rect, 2.0, Color32::from_rgb(r, g, b), outline);
+                    if response.on_hover_text(format!("Color {idx}")).clicked() {
+                        self.color = idx;
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        !edits.is_empty(),
+                        egui::Button::new(format!("Clear edits ({})", edits.len())),
+                    )
+                    .clicked()
+                {
+                    edits.clear();
+                }
+            });
+        }
+
+        let flag = match self.vision.index() {
+            Some(i) => &textures.colorblind[i],
+            None => textures.flag(in_game, self.filtering),
+        };
+        let columns = if self.compare == Compare::SideBySide {
+            2.0
+        } else {
+            1.0
+        };
+        let available =
+            (ui.available_width() - ui.spacing().item_spacing.x * (columns - 1.0)) / columns;
+        // Whole multiples keep every flag pixel the same size.
+        let scale = (available / IMAGE_WIDTH as f32)
+            .floor()
+            .clamp(PREVIEW_SCALE, FLAG_PREVIEW_MAX_SCALE);
+        let size = egui::vec2(IMAGE_WIDTH as f32, IMAGE_HEIGHT as f32) * scale;
+
+        let mut hovered = None;
+        match self.compare {
+            Compare::Off => {
+                let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+                hovered = self.hovered(&response, rect);
+                if self.editing {
+                    a11y::describe(
+                        &response,
+                        WidgetType::Other,
+                        format!("Flag editor, {}", self.tool.label()),
+                    );
+                } else {
+                    a11y::describe(&response, WidgetType::Other, "Flag preview");
+                }
+                let mut stamping = Vec::new();
+                if self.editing {
+                    match self.tool {
+                        Tool::Pencil => self.pencil(&response, rect, edits),
+                        Tool::Fill => {
+                            if response.clicked()
+                                && let Some(pos) = response.interact_pointer_pos()
+                            {
+                                let (x, y) = self.pixel_at(rect, pos);
+                                for (x, y) in edit::fill(indices, x, y, self.contiguous) {
+                                    edits.set(x, y, self.color);
+                                }
+                            }
+                        }
+                        Tool::Line | Tool::Rectangle | Tool::Ellipse => {
+                            stamping = self.shape(&response, rect, edits);
+                        }
+                        Tool::Eyedropper => {
+                            if response.clicked()
+                                && let Some(pos) = response.interact_pointer_pos()
+                                && let Some(&idx) = self.index_at(rect, pos, indices)
+                            {
+                                self.color = idx.min(palette.colors.len() - 1);
+                            }
+                        }
+                    }
+                    if response.has_focus() {
+                        stamping.extend(self.keyboard(ui, indices, palette, edits));
+                    }
+                }
+                self.navigate(ui, &response, rect, !self.editing);
+                // Tools need pixels where they are drawn, so no ripples while
+                // editing.
+                if self.filtering && self.cloth && !self.editing {
+                    self.paint_cloth(ui, rect, flag.id());
+                } else {
+                    self.paint(ui, rect, rect, flag.id());
+                }
+                let [r, g, b] = palette.colors[self.color];
+                let painter = ui.painter_at(rect);
+                for (x, y) in stamping {
+                    painter.rect_filled(
+                        self.pixel_rect(rect, x, y),
+                        0.0,
+                        Color32::from_rgb(r, g, b),
+                    );
+                }
+                focus_ring(ui, &response);
+                if self.editing && response.has_focus() {
+                    let (x, y) = self.cursor;
+                    painter.rect_stroke(
+                        self.pixel_rect(rect, x, y),
+                        0.0,
+                        ui.visuals().selection.stroke,
+                    );
+                    hovered = hovered.or(Some((x as u32, y as u32)));
+                }
+                if self.editing {
+                    response
+                        .on_hover_cursor(egui::CursorIcon::Crosshair)
+                        .on_hover_text(
+                            "Arrow keys move the cursor and Space uses the tool; \
+                             hold Space to draw with the pencil",
+                        );
+                }
+            }
+            Compare::SideBySide => {
+                ui.horizontal(|ui| {
+                    for (texture, hover) in [(&textures.resized, "Resized source"), (flag, "Flag")]
+                    {
+                        let (rect, response) = ui.allocate_exact_size(size, Sense::drag());
+                        hovered = hovered.or(self.hovered(&response, rect));
+                        a11y::describe(&response, WidgetType::Other, hover);
+                        self.navigate(ui, &response, rect, true);
+                        self.paint(ui, rect, rect, texture.id());
+                        focus_ring(ui, &response);
+                        response.on_hover_text(hover);
+                    }
+                });
+            }
+            Compare::Slider => {
+                let (rect, response) = ui.allocate_exact_size(size, Sense::drag());
+                hovered = self.hovered(&response, rect);
+                if response.dragged_by(PointerButton::Primary)
+                    && let Some(pos) = response.interact_pointer_pos()
+                {
+                    self.split = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+                }
+                // Left and right move the divider instead of panning.
+                if response.has_focus() {
+                    let step = ui.input_mut(|i| {
+                        let mut key = |key| i.consume_key(egui::Modifiers::NONE, key) as i32;
+                        key(egui::Key::ArrowRight) - key(egui::Key::ArrowLeft)
+                    });
+                    self.split = (self.split + step as f32 * SPLIT_STEP).clamp(0.0, 1.0);
+                }
+                response.widget_info(|| {
+                    WidgetInfo::slider(self.split as f64, "Source and flag divider")
+                });
+                self.navigate(ui, &response, rect, false);
+                let divider = rect.left() + rect.width() * self.split;
+                self.paint(ui, rect, rect, flag.id());
+                let left = Rect::from_min_max(rect.min, egui::pos2(divider, rect.bottom()));
+                self.paint(ui, rect, left, textures.resized.id());
+                ui.painter_at(rect).vline(
+                    divider,
+                    rect.y_range(),
+                    Stroke::new(2.0, Color32::WHITE),
+                );
+                focus_ring(ui, &response);
+                response
+                    .on_hover_cursor(egui::CursorIcon::ResizeHorizontal)
+                    .on_hover_text("Source on the left, flag on the right; right-drag to pan");
+            }
+            Compare::Changes => {
+                let (rect, response) = ui.allocate_exact_size(size, Sense::drag());
+                hovered = self.hovered(&response, rect);
+                a11y::describe(
+                    &response,
+                    WidgetType::Other,
+                    "Changes from the flag in the game",
+                );
+                self.navigate(ui, &response, rect, true);
+                focus_ring(ui, &response);
+                match &textures.diff {
+                    Some(diff) => {
+                        self.paint(ui, rect, rect, diff.id());
+                        response.on_hover_text("Grayed pixels match the flag in the game");
+                    }
+                    None => {
+                        self.paint(ui, rect, rect, flag.id());
+                        response.on_hover_text("The game has no flag saved to compare with");
+                    }
+                }
+            }
+        }
+        inspector(ui, hovered, palette, indices);
+    }
+
+    /// The flag pixel under the pointer, if it is over the flag.
+    fn hovered(&self, response: &Response, rect: Rect) -> Option<(u32, u32)> {
+        let (x, y) = self.pixel_at(rect, response.hover_pos()?);
+        ((0..IMAGE_WIDTH as i32).contains(&x) && (0..IMAGE_HEIGHT as i32).contains(&y))
+            .then_some((x as u32, y as u32))
+    }
+
+    /// The flag pixel under screen position `pos` of a view drawn in `rect`.
+    fn pixel_at(&self, rect: Rect, pos: egui::Pos2) -> (i32, i32) {
+        let view = self.view();
+        let uv = view.min + (pos - rect.min) / rect.size() * view.size();
+        (
+            (uv.x * IMAGE_WIDTH as f32).floor() as i32,
+            (uv.y * IMAGE_HEIGHT as f32).floor() as i32,
+        )
+    }
+
+    /// Palette index of the flag pixel under `pos`.
+    fn index_at<'a>(&self, rect: Rect, pos: egui::Pos2, indices: &'a [usize]) -> Option<&'a usize> {
+        let (x, y) = self.pixel_at(rect, pos);
+        if !(0..IMAGE_WIDTH as i32).contains(&x) || !(0..IMAGE_HEIGHT as i32).contains(&y) {
+            return None;
+        }
+        indices.get(y as usize * IMAGE_WIDTH as usize + x as usize)
+    }
+
+    /// Screen rectangle of flag pixel (`x`, `y`) in a view drawn in `rect`.
+    fn pixel_rect(&self, rect: Rect, x: i32, y: i32) -> Rect {
+        let view = self.view();
+        let to_screen = |uv: egui::Pos2| rect.min + (uv - view.min) / view.size() * rect.size();
+        let (w, h) = (IMAGE_WIDTH as f32, IMAGE_HEIGHT as f32);
+        Rect::from_min_max(
+            to_screen(egui::pos2(x as f32 / w, y as f32 / h)),
+            to_screen(egui::pos2((x + 1) as f32 / w, (y + 1) as f32 / h)),
+        )
+    }
+
+    /// Tracks a shape drag, stamping the shape into `edits` on release;
+    /// returns the pixels of a shape still being dragged, to preview.
+    fn shape(
+        &mut self,
+        response: &Response,
+        rect: Rect,
+        edits: &mut PixelEdits,
+    ) -> Vec<(i32, i32)> {
+        let Some(pos) = response.interact_pointer_pos() else {
+            return Vec::new();
+        };
+        let pixel = self.pixel_at(rect, pos);
+        if response.drag_started_by(PointerButton::Primary) {
+            self.shape_start = Some(pixel);
+        }
+        let clicked = response.clicked().then_some(pixel);
+        let Some(start) = self.shape_start.or(clicked) else {
+            return Vec::new();
+        };
+        let pixels = edit::shape(self.tool, start, pixel, self.filled);
+        if response.drag_stopped() || response.clicked() {
+            self.shape_start = None;
+            for &(x, y) in &pixels {
+                edits.set(x, y, self.color);
+            }
+            return Vec::new();
+        }
+        pixels
+    }
+
+    /// Paints the pixels a click or primary-button drag passes over.
+    fn pencil(&mut self, response: &Response, rect: Rect, edits: &mut PixelEdits) {
+        let stroke = response.clicked() || response.dragged_by(PointerButton::Primary);
+        let Some(pos) = response.interact_pointer_pos().filter(|_| stroke) else {
+            self.last_painted = None;
+            return;
+        };
+        let pixel = self.pixel_at(rect, pos);
+        for (x, y) in edit::line(self.last_painted.unwrap_or(pixel), pixel) {
+            edits.set(x, y, self.color);
+        }
+        self.last_painted = Some(pixel);
+    }
+
+    /// Arrow keys move the cursor and Space or Enter uses the tool on it, so
+    /// the flag can be edited without a mouse; shapes run from one press to
+    /// the next, and the pencil draws while Space is held. Returns the shape
+    /// being placed, to preview.
+    fn keyboard(
+        &mut self,
+        ui: &egui::Ui,
+        indices: &[usize],
+        palette: &Palette,
+        edits: &mut PixelEdits,
+    ) -> Vec<(i32, i32)> {
+        let (step, used, drawing, cancel) = ui.input_mut(|i| {
+            let mut key = |key| i.consume_key(egui::Modifiers::NONE, key) as i32;
+            let step = (
+                key(egui::Key::ArrowRight) - key(egui::Key::ArrowLeft),
+                key(egui::Key::ArrowDown) - key(egui::Key::ArrowUp),
+            );
+            let used = i.key_pressed(egui::Key::Space) || i.key_pressed(egui::Key::Enter);
+            (
+                step,
+                used,
+                i.key_down(egui::Key::Space),
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+        let from = self.cursor;
+        self.cursor = (
+            (from.0 + step.0).clamp(0, IMAGE_WIDTH as i32 - 1),
+            (from.1 + step.1).clamp(0, IMAGE_HEIGHT as i32 - 1),
+        );
+        let (x, y) = self.cursor;
+        // Keep the cursor in view when zoomed in.
+        let view = self.view();
+        let uv = egui::pos2(
+            (x as f32 + 0.5) / IMAGE_WIDTH as f32,
+            (y as f32 + 0.5) / IMAGE_HEIGHT as f32,
+        );
+        if !view.contains(uv) {
+            self.center += uv - view.clamp(uv);
+            self.clamp_center();
+        }
+
+        if cancel {
+            self.shape_start = None;
+        }
+        match self.tool {
+            Tool::Pencil if used || (drawing && self.cursor != from) => {
+                for (x, y) in edit::line(if used { self.cursor } else { from }, self.cursor) {
+                    edits.set(x, y, self.color);
+                }
+            }
+            Tool::Fill if used => {
+                for (x, y) in edit::fill(indices, x, y, self.contiguous) {
+                    edits.set(x, y, self.color);
+                }
+            }
+            Tool::Eyedropper if used => {
+                if let Some(&idx) = indices.get(y as usize * IMAGE_WIDTH as usize + x as usize) {
+                    self.color = idx.min(palette.colors.len() - 1);
+                }
+            }
+            Tool::Line | Tool::Rectangle | Tool::Ellipse => match self.shape_start {
+                Some(start) if used => {
+                    self.shape_start = None;
+                    for (x, y) in edit::shape(self.tool, start, self.cursor, self.filled) {
+                        edits.set(x, y, self.color);
+                    }
+                }
+                Some(start) => return edit::shape(self.tool, start, self.cursor, self.filled),
+                None if used => self.shape_start = Some(self.cursor),
+                None => {}
+            },
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// The part of the texture in view.
+    fn view(&self) -> Rect {
+        Rect::from_center_size(self.center, egui::Vec2::splat(1.0 / self.zoom))
+    }
+
+    /// Zooms keeping texture point `anchor` where it is on screen.
+    fn set_zoom(&mut self, zoom: f32, anchor: egui::Pos2) {
+        let zoom = zoom.clamp(1.0, MAX_ZOOM);
+        self.center = anchor + (self.center - anchor) * (self.zoom / zoom);
+        self.zoom = zoom;
+        self.clamp_center();
+    }
+
+    /// Keeps the view inside the texture.
+    fn clamp_center(&mut self) {
+        let half = 0.5 / self.zoom;
+        self.center.x = self.center.x.clamp(half, 1.0 - half);
+        self.center.y = self.center.y.clamp(half, 1.0 - half);
+    }
+
+    /// Ctrl+wheel zoom toward the pointer, and panning by dragging: with
+    /// either button, or only the secondary one when `primary_pans` is off.
+    fn navigate(&mut self, ui: &egui::Ui, response: &Response, rect: Rect, primary_pans: bool) {
+        let view = self.view();
+        let to_uv = |pos: egui::Pos2| view.min + (pos - rect.min) / rect.size() * view.size();
+
+        if let Some(hover) = response.hover_pos() {
+            let zoom = ui.input(|i| i.zoom_delta());
+            if zoom != 1.0 {
+                self.set_zoom(self.zoom * zoom, to_uv(hover));
+            }
+        }
+        if response.dragged_by(PointerButton::Secondary)
+            || (primary_pans && response.dragged_by(PointerButton::Primary))
+        {
+            self.center -= response.drag_delta() / rect.size() * view.size();
+            self.clamp_center();
+        }
+
+        if response.clicked() || response.drag_started() {
+            response.request_focus();
+        }
+        if response.has_focus() {
+            ui.memory_mut(|memory| {
+                memory.set_focus_lock_filter(
+                    response.id,
+                    egui::EventFilter {
+                        horizontal_arrows: true,
+                        vertical_arrows: true,
+                        ..Default::default()
+                    },
+                )
+            });
+            // Without modifiers, leaving Ctrl+plus and Ctrl+minus to the UI
+            // scale.
+            let (pan, zoom) = ui.input_mut(|input| {
+                let mut key = |key| input.consume_key(egui::Modifiers::NONE, key) as i32 as f32;
+                (
+                    egui::vec2(
+                        key(egui::Key::ArrowRight) - key(egui::Key::ArrowLeft),
+                        key(egui::Key::ArrowDown) - key(egui::Key::ArrowUp),
+                    ),
+                    key(egui::Key::Plus) + key(egui::Key::Equals) - key(egui::Key::Minus),
+                )
+            });
+            if pan != egui::Vec2::ZERO {
+                self.center += pan * PAN_STEP * view.size();
+                self.clamp_center();
+            }
+            if zoom != 0.0 {
+                self.set_zoom(self.zoom * ZOOM_STEP.powf(zoom), self.center);
+            }
+        }
+    }
+
+    /// Draws the visible part of `texture` into `rect` as waving cloth,
+    /// rippled across and darkened in the folds.
+    fn paint_cloth(&self, ui: &egui::Ui, rect: Rect, texture: egui::TextureId) {
+        mat(ui, rect);
+        let view = self.view();
+        let painter = ui.painter_at(rect);
+        let rect = rect.shrink2(egui::vec2(0.0, CLOTH_AMPLITUDE * rect.height()));
+        let mut mesh = Mesh::with_texture(texture);
+        for row in 0..=CLOTH_ROWS {
+            for col in 0..=CLOTH_COLUMNS {
+                let t = egui::vec2(
+                    col as f32 / CLOTH_COLUMNS as f32,
+                    row as f32 / CLOTH_ROWS as f32,
+                );
+                let phase = (t.x * CLOTH_WAVES - t.y * 0.25) * TAU;
+                // Folds swing wider away from the pole on the left.
+                let lift = CLOTH_AMPLITUDE * rect.height() * t.x * phase.sin();
+                let shade = 1.0 - CLOTH_SHADE * t.x * (0.5 + 0.5 * phase.cos());
+                mesh.vertices.push(Vertex {
+                    pos: rect.min + t * rect.size() + egui::vec2(0.0, lift),
+                    uv: view.min + t * view.size(),
+                    color: Color32::from_gray((255.0 * shade) as u8),
+                });
+            }
+        }
+        let stride = CLOTH_COLUMNS + 1;
+        for row in 0..CLOTH_ROWS {
+            for col in 0..CLOTH_COLUMNS {
+                let corner = row * stride + col;
+                mesh.add_triangle(corner, corner + 1, corner + stride);
+                mesh.add_triangle(corner + 1, corner + stride + 1, corner + stride);
+            }
+        }
+        painter.add(mesh);
+    }
+
+    /// Draws the visible part of `texture` into `rect`, with the pixel grid
+    /// when zoomed in far enough and any guides; only the part inside `clip`
+    /// shows.
+    fn paint(&self, ui: &egui::Ui, rect: Rect, clip: Rect, texture: egui::TextureId) {
+        mat(ui, rect);
+        let view = self.view();
+        let painter = ui.painter_at(clip);
+        painter.image(texture, rect, view, Color32::WHITE);
+
+        let to_screen = |uv: egui::Pos2| rect.min + (uv - view.min) / view.size() * rect.size();
+        let cell = rect.size() / view.size() / egui::vec2(IMAGE_WIDTH as f32, IMAGE_HEIGHT as f32);
+        if self.grid && cell.x >= GRID_MIN_CELL {
+            let stroke = Stroke::new(1.0, GRID_COLOR);
+            let first_x = (view.min.x * IMAGE_WIDTH as f32).ceil() as u32;
+            let last_x = (view.max.x * IMAGE_WIDTH as f32).floor() as u32;
+            for x in first_x..=last_x {
+                let screen = to_screen(egui::pos2(x as f32 / IMAGE_WIDTH as f32, 0.0)).x;
+                painter.vline(screen, rect.y_range(), stroke);
+            }
+            let first_y = (view.min.y * IMAGE_HEIGHT as f32).ceil() as u32;
+            let last_y = (view.max.y * IMAGE_HEIGHT as f32).floor() as u32;
+            for y in first_y..=last_y {
+                let screen = to_screen(egui::pos2(0.0, y as f32 / IMAGE_HEIGHT as f32)).y;
+                painter.hline(rect.x_range(), screen, stroke);
+            }
+        }
+
+        if self.guides.enabled {
+            for (x, color) in self.guides.lines(IMAGE_WIDTH) {
+                let screen = to_screen(egui::pos2(x / IMAGE_WIDTH as f32, 0.0)).x;
+                painter.vline(screen, rect.y_range(), Stroke::new(1.0, color));
+            }
+            for (y, color) in self.guides.lines(IMAGE_HEIGHT) {
+                let screen = to_screen(egui::pos2(0.0, y / IMAGE_HEIGHT as f32)).y;
+                painter.hline(rect.x_range(), screen, Stroke::new(1.0, color));
+            }
+        }
+    }
+}
+
+/// Outlines a view that has keyboard focus.
+fn focus_ring(ui: &egui::Ui, response: &Response) {
+    if response.has_focus() {
+        ui.painter()
+            .rect_stroke(response.rect, 0.0, ui.visuals().selection.stroke);
+    }
+}
+
+/// A two-tone frame around `rect`, contrasting with the flag's edge rather
+/// than the panel.
+fn mat(ui: &egui::Ui, rect: Rect) {
+    let painter = ui.painter();
+    painter.rect_stroke(
+        rect.expand(MAT_WIDTH / 2.0),
+        0.0,
+        Stroke::new(MAT_WIDTH, MAT_LIGHT),
+    );
+    painter.rect_stroke(
+        rect.expand(MAT_WIDTH * 1.5),
+        0.0,
+        Stroke::new(MAT_WIDTH, MAT_DARK),
+    );
+}
+
+/// One line describing the hovered flag pixel: its palette cell, color and
+/// the entry written for it into the flag data.
+fn inspector(ui: &mut egui::Ui, hovered: Option<(u32, u32)>, palette: &Palette, indices: &[usize]) {
+    let cell = hovered.and_then(|(x, y)| {
+        let idx = *indices.get((y * IMAGE_WIDTH + x) as usize)?;
+        Some((x, y, idx.min(palette.colors.len() - 1)))
+    });
+    let Some((x, y, idx)) = cell else {
+        ui.weak("Hover the flag to inspect a pixel");
+        return;
+    };
+    let [r, g, b] = palette.colors[idx];
+    // Entries run up each column in turn, starting at the bottom.
+    let entry = x * IMAGE_HEIGHT + (IMAGE_HEIGHT - 1 - y) + 1;
+    ui.horizontal(|ui| {
+        ui.colored_label(Color32::from_rgb(r, g, b), "■");
+        ui.monospace(format!(
+            "x {x}, y {y} · cell {idx} (row {}, col {}) · RGB {r},{g},{b} · entry {entry} “{}”",
+            idx as u32 / palette.cols,
+            idx as u32 % palette.cols,
+            palette.uv_string(idx),
+        ));
+    });
+}
@@ -0,0 +1,211 @@
+//! Bundled bitmap fonts for the text overlay. Glyphs are column-major: one
+//! byte per column, bit 0 the top row.
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Font {
+    /// 5×7, full printable ASCII.
+    #[default]
+    Classic,
+    /// 3×5, capitals (lowercase is drawn as capitals), digits and basic
+    /// punctuation, for squeezing a clan tag into one line.
+    Tiny,
+}
+
+impl Font {
+    pub const ALL: [Font; 2] = [Font::Classic, Font::Tiny];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Font::Classic => "Classic 5×7",
+            Font::Tiny => "Tiny 3×5",
+        }
+    }
+
+    pub fn width(self) -> u32 {
+        match self {
+            Font::Classic => 5,
+            Font::Tiny => 3,
+        }
+    }
+
+    pub fn height(self) -> u32 {
+        match self {
+            Font::Classic => 7,
+            Font::Tiny => 5,
+        }
+    }
+
+    /// Column bitmaps for `c`, falling back to `?` for anything the font
+    /// lacks.
+    pub fn glyph(self, c: char) -> &'static [u8] {
+        match self {
+            Font::Classic => {
+                let index = (c as u32).wrapping_sub(' ' as u32) as usize;
+                CLASSIC
+                    .get(index)
+                    .unwrap_or(&CLASSIC[(b'?' - b' ') as usize])
+            }
+            Font::Tiny => {
+                let c = c.to_ascii_uppercase();
+                TINY.iter()
+                    .find(|(glyph, _)| *glyph == c)
+                    .or_else(|| TINY.iter().find(|(glyph, _)| *glyph == '?'))
+                    .map(|(_, columns)| columns.as_slice())
+                    .unwrap_or(&[])
+            }
+        }
+    }
+}
+
+// ' ' through '~'.
+const CLASSIC: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+const TINY: [(char, [u8; 3]); 51] = [
+    (' ', [0x00, 0x00, 0x00]),
+    ('A', [0x1E, 0x05, 0x1E]),
+    ('B', [0x1F, 0x15, 0x0A]),
+    ('C', [0x0E, 0x11, 0x11]),
+    ('D', [0x1F, 0x11, 0x0E]),
+    ('E', [0x1F, 0x15, 0x11]),
+    ('F', [0x1F, 0x05, 0x01]),
+    ('G', [0x0E, 0x11, 0x1D]),
+    ('H', [0x1F, 0x04, 0x1F]),
+    ('I', [0x11, 0x1F, 0x11]),
+    ('J', [0x08, 0x10, 0x0F]),
+    ('K', [0x1F, 0x04, 0x1B]),
+    ('L', [0x1F, 0x10, 0x10]),
+    ('M', [0x1F, 0x02, 0x1F]),
+    ('N', [0x1F, 0x01, 0x1E]),
+    ('O', [0x0E, 0x11, 0x0E]),
+    ('P', [0x1F, 0x05, 0x02]),
+    ('Q', [0x0E, 0x19, 0x1E]),
+    ('R', [0x1F, 0x05, 0x1A]),
+    ('S', [0x12, 0x15, 0x09]),
+    ('T', [0x01, 0x1F, 0x01]),
+    ('U', [0x1F, 0x10, 0x1F]),
+    ('V', [0x0F, 0x10, 0x0F]),
+    ('W', [0x1F, 0x08, 0x1F]),
+    ('X', [0x1B, 0x04, 0x1B]),
+    ('Y', [0x03, 0x1C, 0x03]),
+    ('Z', [0x19, 0x15, 0x13]),
+    ('0', [0x1F, 0x11, 0x1F]),
+    ('1', [0x12, 0x1F, 0x10]),
+    ('2', [0x1D, 0x15, 0x17]),
+    ('3', [0x11, 0x15, 0x1F]),
+    ('4', [0x07, 0x04, 0x1F]),
+    ('5', [0x17, 0x15, 0x1D]),
+    ('6', [0x1F, 0x15, 0x1D]),
+    ('7', [0x01, 0x01, 0x1F]),
+    ('8', [0x1F, 0x15, 0x1F]),
+    ('9', [0x17, 0x15, 0x1F]),
+    ('!', [0x00, 0x17, 0x00]),
+    ('?', [0x01, 0x15, 0x02]),
+    ('.', [0x00, 0x10, 0x00]),
+    (',', [0x10, 0x08, 0x00]),
+    (':', [0x00, 0x0A, 0x00]),
+    ('\'', [0x00, 0x03, 0x00]),
+    ('-', [0x04, 0x04, 0x04]),
+    ('+', [0x04, 0x0E, 0x04]),
+    ('/', [0x18, 0x04, 0x03]),
+    ('(', [0x00, 0x0E, 0x11]),
+    (')', [0x11, 0x0E, 0x00]),
+    ('[', [0x00, 0x1F, 0x11]),
+    (']', [0x11, 0x1F, 0x00]),
+    ('#', [0x0A, 0x1F, 0x0A]),
+];
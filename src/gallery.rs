@@ -0,0 +1,194 @@
+//! A folder of images as thumbnails beside their quantized flags, loaded on
+//! a background thread, for picking which one becomes the source.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::egui;
+use egui::{ColorImage, TextureHandle, TextureOptions, WidgetType};
+use image::imageops;
+
+use crate::edit::PixelEdits;
+use crate::palettes::Palette;
+use crate::pipeline::Pipeline;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH, Settings, a11y, icc, render_indices, source};
+
+// Longer side of a source thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 96;
+
+/// One image of the folder, decoded and encoded with the gallery's
+/// settings.
+struct Entry {
+    path: PathBuf,
+    name: String,
+    images: Result<(ColorImage, ColorImage), String>,
+}
+
+/// An entry's uploaded textures, or why it could not be loaded.
+enum Shown {
+    Images(TextureHandle, TextureHandle),
+    Error(String),
+}
+
+pub struct Gallery {
+    pub dir: PathBuf,
+    entries: Vec<(PathBuf, String, Shown)>,
+    loaded: Arc<Mutex<Vec<Entry>>>,
+    total: usize,
+    stop: Arc<AtomicBool>,
+}
+
+impl Gallery {
+    /// Starts decoding and encoding every image directly in `dir`.
+    pub fn open(dir: &Path, palette: Palette, settings: Settings) -> Result<Self, String> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|e| format!("{}: {e}", dir.display()))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && source::is_image(path))
+            .collect();
+        paths.sort();
+        if paths.is_empty() {
+            return Err(format!("{}: no images", dir.display()));
+        }
+
+        let loaded = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let total = paths.len();
+        // A crop or hand edits made on the current source mean nothing for
+        // these.
+        let settings = Settings {
+            crop: None,
+            pixel_edits: PixelEdits::default(),
+            ..settings
+        };
+        let (thread_loaded, thread_stop) = (Arc::clone(&loaded), Arc::clone(&stop));
+        thread::spawn(move || {
+            let mut pipeline = Pipeline::new(palette);
+            for path in paths {
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let images = source::open(&path).and_then(|mut incoming| {
+                    icc::to_srgb(&mut incoming.image, incoming.icc.as_deref());
+                    let thumbnail = {
+                        let (w, h) = incoming.image.dimensions();
+                        let scale = THUMBNAIL_SIZE as f32 / w.max(h) as f32;
+                        let (tw, th) = ((w as f32 * scale) as u32, (h as f32 * scale) as u32);
+                        imageops::thumbnail(&incoming.image, tw.max(1), th.max(1))
+                    };
+                    pipeline.set_source(incoming.image);
+                    let encoded = pipeline.run(&settings).ok_or("Nothing to encode")?;
+                    Ok((
+                        ColorImage::from_rgba_unmultiplied(
+                            [thumbnail.width() as usize, thumbnail.height() as usize],
+                            &thumbnail,
+                        ),
+                        render_indices(&encoded.indices, &pipeline.palette().colors),
+                    ))
+                });
+                let name = path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                thread_loaded
+                    .lock()
+                    .unwrap()
+                    .push(Entry { path, name, images });
+            }
+        });
+
+        Ok(Self {
+            dir: dir.to_owned(),
+            entries: Vec::new(),
+            loaded,
+            total,
+            stop,
+        })
+    }
+
+    /// Draws the grid; returns the image that was clicked.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<PathBuf> {
+        for entry in self.loaded.lock().unwrap().drain(..) {
+            let shown = match entry.images {
+                Ok((thumbnail, flag)) => {
+                    let ctx = ui.ctx();
+                    let id = entry.path.display().to_string();
+                    Shown::Images(
+                        ctx.load_texture(
+                            format!("gallery_source_{id}"),
+                            thumbnail,
+                            TextureOptions::LINEAR,
+                        ),
+                        ctx.load_texture(
+                            format!("gallery_flag_{id}"),
+                            flag,
+                            TextureOptions::NEAREST,
+                        ),
+                    )
+                }
+                Err(error) => Shown::Error(error),
+            };
+            self.entries.push((entry.path, entry.name, shown));
+        }
+        if self.entries.len() < self.total {
+            ui.label(format!(
+                "Loading {} of {}…",
+                self.entries.len() + 1,
+                self.total
+            ));
+            ui.ctx().request_repaint();
+        }
+
+        let mut picked = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (path, name, shown) in &self.entries {
+                    ui.group(|ui| {
+                        ui.vertical(|ui| {
+                            ui.set_width(THUMBNAIL_SIZE as f32 + 8.0);
+                            match shown {
+                                Shown::Images(thumbnail, flag) => {
+                                    let size = egui::vec2(IMAGE_WIDTH as f32, IMAGE_HEIGHT as f32);
+                                    let source = ui.add(egui::ImageButton::new((
+                                        thumbnail.id(),
+                                        thumbnail.size_vec2(),
+                                    )));
+                                    a11y::describe(&source, WidgetType::ImageButton, name);
+                                    let flag = ui.add(egui::ImageButton::new((flag.id(), size)));
+                                    a11y::describe(
+                                        &flag,
+                                        WidgetType::ImageButton,
+                                        format!("{name} as a flag"),
+                                    );
+                                    if source.clicked() || flag.clicked() {
+                                        picked = Some(path.clone());
+                                    }
+                                }
+                                Shown::Error(error) => {
+                                    a11y::status(
+                                        ui,
+                                        egui::RichText::new(error)
+                                            .color(ui.visuals().error_fg_color),
+                                        false,
+                                    );
+                                }
+                            }
+                            ui.add(
+                                egui::Label::new(egui::RichText::new(name).small()).truncate(true),
+                            );
+                        });
+                    });
+                }
+            });
+        });
+        picked
+    }
+}
+
+impl Drop for Gallery {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
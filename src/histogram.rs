@@ -0,0 +1,96 @@
+//! RGB and luminance histograms of the flag-sized source and the quantized
+//! flag, to catch clipped highlights and crushed shadows.
+
+use eframe::egui;
+use egui::{Color32, ColorImage, Rect, Sense, Stroke, WidgetType};
+
+use crate::a11y;
+
+// Levels per histogram bar; a flag has too few pixels for all 256.
+const BINS: usize = 64;
+// Size of one plot, in points.
+const PLOT_SIZE: egui::Vec2 = egui::vec2(200.0, 80.0);
+
+/// Pixel counts per level band of red, green, blue and luminance.
+#[derive(Clone)]
+pub struct Histogram {
+    bins: [[u32; BINS]; 4],
+    /// Pixels with a channel at 0, and at 255.
+    clipped: [u32; 2],
+    pixels: u32,
+}
+
+impl Histogram {
+    pub fn new(image: &ColorImage) -> Self {
+        let mut histogram = Self {
+            bins: [[0; BINS]; 4],
+            clipped: [0; 2],
+            pixels: image.pixels.len() as u32,
+        };
+        for pixel in &image.pixels {
+            let [r, g, b, _] = pixel.to_array();
+            let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8;
+            for (channel, value) in [r, g, b, luma].into_iter().enumerate() {
+                histogram.bins[channel][value as usize * BINS / 256] += 1;
+            }
+            // A single saturated channel is just a vivid color, not lost
+            // detail.
+            histogram.clipped[0] += (luma == 0 || [r, g, b] == [0; 3]) as u32;
+            histogram.clipped[1] += (luma == 255 || [r, g, b] == [255; 3]) as u32;
+        }
+        histogram
+    }
+
+    /// Plots the channels over each other, with the share of clipped pixels
+    /// underneath.
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let (rect, response) = ui.allocate_exact_size(PLOT_SIZE, Sense::hover());
+        a11y::describe(&response, WidgetType::Other, "RGB and luminance histogram");
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, Color32::from_gray(24));
+
+        let peak = self
+            .bins
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1) as f32;
+        let bar = rect.width() / BINS as f32;
+        let colors = [
+            Color32::from_rgba_unmultiplied(255, 60, 60, 90),
+            Color32::from_rgba_unmultiplied(60, 255, 60, 90),
+            Color32::from_rgba_unmultiplied(60, 120, 255, 90),
+        ];
+        for (bins, color) in self.bins.iter().zip(colors) {
+            for (i, &count) in bins.iter().enumerate() {
+                let x = rect.left() + i as f32 * bar;
+                let top = rect.bottom() - count as f32 / peak * rect.height();
+                painter.rect_filled(
+                    Rect::from_min_max(egui::pos2(x, top), egui::pos2(x + bar, rect.bottom())),
+                    0.0,
+                    color,
+                );
+            }
+        }
+        let luma: Vec<egui::Pos2> = self.bins[3]
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| {
+                egui::pos2(
+                    rect.left() + (i as f32 + 0.5) * bar,
+                    rect.bottom() - count as f32 / peak * rect.height(),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(luma, Stroke::new(1.0, Color32::WHITE)));
+
+        let share = |count: u32| count as f32 * 100.0 / self.pixels.max(1) as f32;
+        ui.label(format!(
+            "Clipped: {:.1}% shadows, {:.1}% highlights",
+            share(self.clipped[0]),
+            share(self.clipped[1])
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+//! Images in the Windows clipboard history (Win+V), read on a background
+//! thread for picking an older entry as the source.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Local};
+use eframe::egui;
+use egui::{ColorImage, TextureHandle, TextureOptions, WidgetType};
+use windows::ApplicationModel::DataTransfer::{
+    Clipboard, ClipboardHistoryItem, ClipboardHistoryItemsResultStatus, StandardDataFormats,
+};
+use windows::Storage::Streams::DataReader;
+use windows::core::HSTRING;
+
+use crate::a11y;
+use crate::source::{self, Incoming};
+
+// Height thumbnails are drawn at.
+const THUMBNAIL_HEIGHT: f32 = 72.0;
+// WinRT timestamps count 100 ns ticks from 1601; Unix time starts this
+// many ticks later.
+const UNIX_EPOCH_TICKS: i64 = 116_444_736_000_000_000;
+
+/// When each image was copied, and the image.
+type Images = Vec<(String, Incoming)>;
+
+/// A WinRT error as shown in the picker.
+fn error(e: windows::core::Error) -> String {
+    e.message().to_string()
+}
+
+/// Every bitmap in the clipboard history, newest first, with when it was
+/// copied. Entries that cannot be read or decoded are left out rather than
+/// failing the whole list.
+fn images() -> Result<Images, String> {
+    let result = Clipboard::GetHistoryItemsAsync()
+        .and_then(|operation| operation.get())
+        .map_err(error)?;
+    match result.Status().map_err(error)? {
+        ClipboardHistoryItemsResultStatus::Success => {}
+        ClipboardHistoryItemsResultStatus::ClipboardHistoryDisabled => {
+            return Err("Clipboard history is off; turn it on in Settings → Clipboard".to_owned());
+        }
+        _ => return Err("Windows denied access to the clipboard history".to_owned()),
+    }
+
+    let bitmap = StandardDataFormats::Bitmap().map_err(error)?;
+    let mut images = Vec::new();
+    for item in result.Items().map_err(error)? {
+        if let Ok(Some(image)) = image(&item, &bitmap) {
+            images.push(image);
+        }
+    }
+    Ok(images)
+}
+
+/// The bitmap in one history entry with when it was copied, or `None` when
+/// the entry holds no bitmap.
+fn image(
+    item: &ClipboardHistoryItem,
+    bitmap: &HSTRING,
+) -> Result<Option<(String, Incoming)>, String> {
+    let content = item.Content().map_err(error)?;
+    if !content.Contains(bitmap).map_err(error)? {
+        return Ok(None);
+    }
+    let stream = content
+        .GetBitmapAsync()
+        .and_then(|operation| operation.get())
+        .and_then(|reference| reference.OpenReadAsync())
+        .and_then(|operation| operation.get())
+        .map_err(error)?;
+    let size = stream.Size().map_err(error)? as u32;
+    let reader = DataReader::CreateDataReader(&stream).map_err(error)?;
+    reader
+        .LoadAsync(size)
+        .and_then(|operation| operation.get())
+        .map_err(error)?;
+    let mut bytes = vec![0u8; size as usize];
+    reader.ReadBytes(&mut bytes).map_err(error)?;
+
+    let ticks = item.Timestamp().map_err(error)?.UniversalTime;
+    let when = DateTime::from_timestamp((ticks - UNIX_EPOCH_TICKS) / 10_000_000, 0)
+        .map(|utc| utc.with_timezone(&Local).format("%d %b %H:%M").to_string())
+        .unwrap_or_default();
+    let incoming = source::decode_bytes(bytes, format!("Clipboard history ({when})"))?;
+    Ok(Some((when, incoming)))
+}
+
+/// Thumbnails of the clipboard history's images.
+pub struct Picker {
+    loading: Arc<Mutex<Option<Result<Images, String>>>>,
+    images: Vec<(String, Incoming, TextureHandle)>,
+    error: Option<String>,
+}
+
+impl Picker {
+    pub fn open() -> Self {
+        let loading = Arc::new(Mutex::new(None));
+        let thread_loading = Arc::clone(&loading);
+        thread::spawn(move || *thread_loading.lock().unwrap() = Some(images()));
+        Self {
+            loading,
+            images: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Draws the thumbnails; returns the image that was clicked.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<Incoming> {
+        match self.loading.lock().unwrap().take() {
+            Some(Ok(images)) => {
+                self.images = images
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (when, incoming))| {
+                        let image = &incoming.image;
+                        let color = ColorImage::from_rgba_unmultiplied(
+                            [image.width() as usize, image.height() as usize],
+                            image.as_raw(),
+                        );
+                        let texture = ui.ctx().load_texture(
+                            format!("clipboard_history_{i}"),
+                            color,
+                            TextureOptions::LINEAR,
+                        );
+                        (when, incoming, texture)
+                    })
+                    .collect();
+                if self.images.is_empty() {
+                    self.error = Some("No images in the clipboard history".to_owned());
+                }
+            }
+            Some(Err(error)) => self.error = Some(error),
+            None if self.images.is_empty() && self.error.is_none() => {
+                ui.label("Reading clipboard history…");
+                ui.ctx().request_repaint();
+            }
+            None => {}
+        }
+        if let Some(error) = &self.error {
+            a11y::status(ui, error, true);
+        }
+
+        let mut picked = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (when, incoming, texture) in &self.images {
+                    ui.vertical(|ui| {
+                        let aspect = texture.aspect_ratio();
+                        let size = egui::vec2(THUMBNAIL_HEIGHT * aspect, THUMBNAIL_HEIGHT);
+                        let button = ui.add(egui::ImageButton::new((texture.id(), size)));
+                        a11y::describe(
+                            &button,
+                            WidgetType::ImageButton,
+                            format!("Captured {when}"),
+                        );
+                        if button.clicked() {
+                            picked = Some(incoming.clone());
+                        }
+                        ui.small(when);
+                    });
+                }
+            });
+        });
+        picked
+    }
+}
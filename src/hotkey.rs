@@ -0,0 +1,127 @@
+//! A system-wide hotkey, registered on its own thread so it fires while the
+//! game has focus.
+
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, RegisterHotKey, UnregisterHotKey,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{MSG, PM_REMOVE, PeekMessageW, WM_HOTKEY};
+
+use crate::capture::CaptureTarget;
+
+const HOTKEY_ID: i32 = 1;
+// How often the hotkey thread checks for presses and configuration changes.
+const HOTKEY_POLL: Duration = Duration::from_millis(50);
+// Virtual-key codes of F1 and A; the keys offered run F1–F12 and A–Z.
+const VK_F1: u32 = 0x70;
+const VK_A: u32 = 0x41;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub enabled: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Virtual-key code.
+    pub key: u32,
+    pub target: CaptureTarget,
+}
+
+impl Default for Hotkey {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ctrl: true,
+            alt: false,
+            shift: true,
+            key: VK_F1 + 11,
+            target: CaptureTarget::default(),
+        }
+    }
+}
+
+impl Hotkey {
+    pub fn keys() -> impl Iterator<Item = u32> {
+        (VK_F1..VK_F1 + 12).chain(VK_A..VK_A + 26)
+    }
+
+    pub fn key_name(key: u32) -> String {
+        match key {
+            _ if (VK_F1..VK_F1 + 12).contains(&key) => format!("F{}", key - VK_F1 + 1),
+            _ => char::from_u32(key).map_or_else(|| format!("#{key}"), String::from),
+        }
+    }
+
+    /// E.g. "Ctrl+Shift+F12".
+    pub fn label(&self) -> String {
+        let mut parts: Vec<String> = [
+            (self.ctrl, "Ctrl"),
+            (self.alt, "Alt"),
+            (self.shift, "Shift"),
+        ]
+        .into_iter()
+        .filter(|&(held, _)| held)
+        .map(|(_, name)| name.to_owned())
+        .collect();
+        parts.push(Self::key_name(self.key));
+        parts.join("+")
+    }
+
+    fn modifiers(&self) -> u32 {
+        let mut modifiers = MOD_NOREPEAT;
+        for (held, flag) in [
+            (self.ctrl, MOD_CONTROL),
+            (self.alt, MOD_ALT),
+            (self.shift, MOD_SHIFT),
+        ] {
+            if held {
+                modifiers |= flag;
+            }
+        }
+        modifiers
+    }
+}
+
+/// Keeps the hotkey `config` returns registered, calling `pressed` with the
+/// current configuration on each press and `failed` when registering fails.
+pub fn spawn(
+    mut config: impl FnMut() -> Hotkey + Send + 'static,
+    mut pressed: impl FnMut(Hotkey) + Send + 'static,
+    mut failed: impl FnMut(String) + Send + 'static,
+) {
+    thread::spawn(move || {
+        let mut registered: Option<Hotkey> = None;
+        let mut attempted: Option<Hotkey> = None;
+        loop {
+            let wanted = config();
+            if attempted != Some(wanted) {
+                attempted = Some(wanted);
+                if registered.take().is_some() {
+                    unsafe { UnregisterHotKey(0, HOTKEY_ID) };
+                }
+                if wanted.enabled {
+                    let ok =
+                        unsafe { RegisterHotKey(0, HOTKEY_ID, wanted.modifiers(), wanted.key) };
+                    if ok != 0 {
+                        registered = Some(wanted);
+                    } else {
+                        failed(format!("{} is already in use", wanted.label()));
+                    }
+                }
+            }
+
+            let mut msg: MSG = unsafe { std::mem::zeroed() };
+            while unsafe { PeekMessageW(&mut msg, 0, 0, 0, PM_REMOVE) } != 0 {
+                if msg.message == WM_HOTKEY
+                    && let Some(hotkey) = registered
+                {
+                    pressed(hotkey);
+                }
+            }
+            thread::sleep(HOTKEY_POLL);
+        }
+    });
+}
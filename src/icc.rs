@@ -0,0 +1,265 @@
+//! Minimal ICC support: matrix/TRC RGB profiles (what displays and
+//! screenshot tools embed), enough to bring wide-gamut sources into sRGB
+//! before palette matching. Anything else is treated as sRGB.
+
+use image::RgbaImage;
+
+use crate::color::linear_to_srgb;
+use crate::dib::CF_DIBV5;
+
+// D50 XYZ → linear sRGB, Bradford-adapted (ICC profile connection space is
+// always D50).
+const XYZ_D50_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133856, -1.6168667, -0.4906146],
+    [-0.9787684, 1.9161415, 0.0334540],
+    [0.0719453, -0.2289914, 1.4052427],
+];
+
+// sRGB primaries in D50, as columns of its RGB → XYZ matrix.
+const SRGB_PRIMARIES: [[f32; 3]; 3] = [
+    [0.4361, 0.2225, 0.0139],
+    [0.3851, 0.7169, 0.0971],
+    [0.1431, 0.0606, 0.7141],
+];
+
+// Primaries this close to sRGB's are left alone.
+const SRGB_TOLERANCE: f32 = 0.003;
+
+// Registered clipboard format name browsers and editors put PNG data under.
+const CLIPBOARD_PNG_FORMAT: &str = "PNG";
+// BITMAPV5HEADER: bV5CSType, bV5ProfileData and bV5ProfileSize offsets, and
+// the 'MBED' color space tag for an embedded profile.
+const DIBV5_CS_TYPE: usize = 56;
+const DIBV5_PROFILE_DATA: usize = 112;
+const DIBV5_PROFILE_SIZE: usize = 116;
+const PROFILE_EMBEDDED: u32 = 0x4D42_4544;
+
+/// A parsed RGB matrix/TRC profile.
+pub struct Profile {
+    pub description: String,
+    /// Columns are the red, green and blue primaries in D50 XYZ.
+    primaries: [[f32; 3]; 3],
+    /// Per-channel encoded → linear tables.
+    curves: [[f32; 256]; 3],
+}
+
+impl Profile {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 132
+            || &data[36..40] != b"acsp"
+            || &data[16..20] != b"RGB "
+            || &data[20..24] != b"XYZ "
+        {
+            return None;
+        }
+
+        let tag = |signature: &[u8; 4]| -> Option<&[u8]> {
+            let count = u32_at(data, 128)? as usize;
+            (0..count).find_map(|i| {
+                let entry = 132 + i * 12;
+                if data.get(entry..entry + 4)? != signature {
+                    return None;
+                }
+                let offset = u32_at(data, entry + 4)? as usize;
+                let size = u32_at(data, entry + 8)? as usize;
+                data.get(offset..offset.checked_add(size)?)
+            })
+        };
+
+        let primaries = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|signature| tag(signature).and_then(xyz));
+        let curves = [b"rTRC", b"gTRC", b"bTRC"].map(|signature| tag(signature).and_then(curve));
+        let [Some(r), Some(g), Some(b)] = primaries else {
+            return None;
+        };
+        let [Some(cr), Some(cg), Some(cb)] = curves else {
+            return None;
+        };
+
+        Some(Self {
+            description: tag(b"desc").and_then(description).unwrap_or_default(),
+            primaries: [r, g, b],
+            curves: [cr, cg, cb],
+        })
+    }
+
+    /// Close enough to sRGB that converting would only add rounding.
+    pub fn is_srgb(&self) -> bool {
+        self.primaries
+            .iter()
+            .flatten()
+            .zip(SRGB_PRIMARIES.iter().flatten())
+            .all(|(a, b)| (a - b).abs() <= SRGB_TOLERANCE)
+    }
+
+    /// Re-encodes `img` from this profile to sRGB, clipping out-of-gamut
+    /// colors.
+    pub fn convert_to_srgb(&self, img: &mut RgbaImage) {
+        // Combined linear RGB → D50 XYZ → linear sRGB.
+        let matrix: [[f32; 3]; 3] = std::array::from_fn(|row| {
+            std::array::from_fn(|col| {
+                (0..3)
+                    .map(|k| XYZ_D50_TO_SRGB[row][k] * self.primaries[col][k])
+                    .sum()
+            })
+        });
+
+        for pixel in img.pixels_mut() {
+            let linear: [f32; 3] = std::array::from_fn(|c| self.curves[c][pixel.0[c] as usize]);
+            for (row, weights) in matrix.iter().enumerate() {
+                let value: f32 = weights.iter().zip(linear).map(|(w, v)| w * v).sum();
+                pixel.0[row] = linear_to_srgb(value);
+            }
+        }
+    }
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn s15_fixed16(data: &[u8], at: usize) -> Option<f32> {
+    Some(u32_at(data, at)? as i32 as f32 / 65536.0)
+}
+
+fn xyz(tag: &[u8]) -> Option<[f32; 3]> {
+    if tag.get(0..4)? != b"XYZ " {
+        return None;
+    }
+    Some([
+        s15_fixed16(tag, 8)?,
+        s15_fixed16(tag, 12)?,
+        s15_fixed16(tag, 16)?,
+    ])
+}
+
+/// A `curv` or `para` tone curve sampled at the 256 8-bit code values.
+fn curve(tag: &[u8]) -> Option<[f32; 256]> {
+    let eval: Box<dyn Fn(f32) -> f32> = match tag.get(0..4)? {
+        b"curv" => {
+            let count = u32_at(tag, 8)? as usize;
+            match count {
+                0 => Box::new(|x| x),
+                1 => {
+                    let gamma = u16_at(tag, 12)? as f32 / 256.0;
+                    Box::new(move |x: f32| x.powf(gamma))
+                }
+                _ => {
+                    let table: Vec<f32> = (0..count)
+                        .map(|i| u16_at(tag, 12 + i * 2).map(|v| v as f32 / 65535.0))
+                        .collect::<Option<_>>()?;
+                    Box::new(move |x: f32| {
+                        let position = x * (table.len() - 1) as f32;
+                        let i = (position as usize).min(table.len() - 2);
+                        let t = position - i as f32;
+                        table[i] + (table[i + 1] - table[i]) * t
+                    })
+                }
+            }
+        }
+        b"para" => {
+            let function = u16_at(tag, 8)?;
+            let params = match function {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return None,
+            };
+            let mut p = [0.0f32; 7];
+            for (i, value) in p.iter_mut().enumerate().take(params) {
+                *value = s15_fixed16(tag, 12 + i * 4)?;
+            }
+            let [g, a, b, c, d, e, f] = p;
+            Box::new(move |x: f32| match function {
+                0 => x.powf(g),
+                1 if x >= -b / a => (a * x + b).powf(g),
+                1 => 0.0,
+                2 if x >= -b / a => (a * x + b).powf(g) + c,
+                2 => c,
+                3 if x >= d => (a * x + b).powf(g),
+                3 => c * x,
+                _ if x >= d => (a * x + b).powf(g) + e,
+                _ => c * x + f,
+            })
+        }
+        _ => return None,
+    };
+    Some(std::array::from_fn(|i| {
+        eval(i as f32 / 255.0).clamp(0.0, 1.0)
+    }))
+}
+
+/// The profile name from a v2 `desc` or v4 `mluc` tag.
+fn description(tag: &[u8]) -> Option<String> {
+    match tag.get(0..4)? {
+        b"desc" => {
+            let length = u32_at(tag, 8)? as usize;
+            let text = tag.get(12..12 + length)?;
+            Some(
+                String::from_utf8_lossy(text)
+                    .trim_end_matches('\0')
+                    .to_owned(),
+            )
+        }
+        b"mluc" => {
+            let length = u32_at(tag, 20)? as usize;
+            let offset = u32_at(tag, 24)? as usize;
+            let units: Vec<u16> = tag
+                .get(offset..offset + length)?
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+/// Converts `img` to sRGB when `data` is a profile other than sRGB, and
+/// returns that profile's description.
+pub fn to_srgb(img: &mut RgbaImage, data: Option<&[u8]>) -> Option<String> {
+    let profile = Profile::parse(data?).filter(|profile| !profile.is_srgb())?;
+    profile.convert_to_srgb(img);
+    Some(profile.description)
+}
+
+/// The ICC profile attached to the image currently on the clipboard: the
+/// `iCCP` chunk of a "PNG" clipboard entry, or a profile embedded in a
+/// CF_DIBV5 bitmap.
+pub fn clipboard_profile() -> Option<Vec<u8>> {
+    use image::ImageDecoder;
+    use image::codecs::png::PngDecoder;
+
+    let _clipboard = clipboard_win::Clipboard::new_attempts(10).ok()?;
+    let mut data = Vec::new();
+
+    if let Some(png) = clipboard_win::raw::register_format(CLIPBOARD_PNG_FORMAT)
+        && clipboard_win::raw::is_format_avail(png.get())
+        && clipboard_win::raw::get_vec(png.get(), &mut data).is_ok()
+    {
+        let mut decoder = PngDecoder::new(std::io::Cursor::new(&data)).ok()?;
+        return decoder.icc_profile().ok().flatten();
+    }
+
+    data.clear();
+    if clipboard_win::raw::is_format_avail(CF_DIBV5)
+        && clipboard_win::raw::get_vec(CF_DIBV5, &mut data).is_ok()
+        && u32::from_le_bytes(
+            data.get(DIBV5_CS_TYPE..DIBV5_CS_TYPE + 4)?
+                .try_into()
+                .ok()?,
+        ) == PROFILE_EMBEDDED
+    {
+        let field = |at: usize| -> Option<usize> {
+            Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+        };
+        let (offset, size) = (field(DIBV5_PROFILE_DATA)?, field(DIBV5_PROFILE_SIZE)?);
+        return data.get(offset..offset + size).map(<[u8]>::to_vec);
+    }
+    None
+}
@@ -0,0 +1,90 @@
+/// A static 3-d tree over palette colors for exact Euclidean nearest-neighbor
+/// queries. Ties resolve to the lowest palette index, matching a linear scan.
+pub struct KdTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+struct Node {
+    point: [f32; 3],
+    index: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl KdTree {
+    pub fn new(points: &[[f32; 3]]) -> Self {
+        let mut items: Vec<(usize, [f32; 3])> = points.iter().copied().enumerate().collect();
+        let mut tree = Self {
+            nodes: Vec::with_capacity(points.len()),
+            root: None,
+        };
+        tree.root = tree.build(&mut items, 0);
+        tree
+    }
+
+    fn build(&mut self, items: &mut [(usize, [f32; 3])], depth: usize) -> Option<usize> {
+        if items.is_empty() {
+            return None;
+        }
+
+        let axis = depth % 3;
+        items.sort_by(|a, b| a.1[axis].total_cmp(&b.1[axis]));
+        let mid = items.len() / 2;
+        let (index, point) = items[mid];
+
+        let (below, rest) = items.split_at_mut(mid);
+        let left = self.build(below, depth + 1);
+        let right = self.build(&mut rest[1..], depth + 1);
+
+        self.nodes.push(Node {
+            point,
+            index,
+            axis,
+            left,
+            right,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Returns the index of the point closest to `target`.
+    pub fn nearest(&self, target: [f32; 3]) -> usize {
+        let mut best = (usize::MAX, f32::INFINITY);
+        self.search(self.root, target, &mut best);
+        best.0
+    }
+
+    fn search(&self, node: Option<usize>, target: [f32; 3], best: &mut (usize, f32)) {
+        let Some(id) = node else {
+            return;
+        };
+        let node = &self.nodes[id];
+
+        let dist = distance_sq(node.point, target);
+        if dist < best.1 || (dist == best.1 && node.index < best.0) {
+            *best = (node.index, dist);
+        }
+
+        let diff = target[node.axis] - node.point[node.axis];
+        let (near, far) = if diff < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+
+        self.search(near, target, best);
+        // `<=` so equidistant points across the split still get a chance to
+        // win the lower-index tie-break.
+        if diff * diff <= best.1 {
+            self.search(far, target, best);
+        }
+    }
+}
+
+fn distance_sq(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d0 = a[0] - b[0];
+    let d1 = a[1] - b[1];
+    let d2 = a[2] - b[2];
+    d0 * d0 + d1 * d1 + d2 * d2
+}
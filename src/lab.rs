@@ -0,0 +1,201 @@
+use std::time::{Duration, Instant};
+
+use palette::Lab;
+
+use crate::color::{linear_table, to_lab};
+
+// sRGB (D65) → XYZ, pre-divided by the D65 white point so the result is the
+// normalized `t` that feeds the Lab companding function.
+const XYZ_MATRIX: [[f32; 3]; 3] = [
+    [
+        0.4124564 / 0.95047,
+        0.3575761 / 0.95047,
+        0.1804375 / 0.95047,
+    ],
+    [0.2126729, 0.7151522, 0.072175],
+    [0.0193339 / 1.08883, 0.119192 / 1.08883, 0.9503041 / 1.08883],
+];
+
+const EPSILON: f32 = 216.0 / 24389.0;
+const KAPPA: f32 = 841.0 / 108.0;
+const DELTA: f32 = 4.0 / 29.0;
+
+// Pixels converted per path when benchmarking.
+const BENCHMARK_PIXELS: usize = 1 << 20;
+
+/// Converts a scanline of sRGB pixels to Lab in one batch, four pixels per
+/// SSE2 vector on x86_64 with a scalar tail (and scalar fallback elsewhere).
+pub fn convert_row(rgb: &[[u8; 3]], out: &mut [Lab]) {
+    assert_eq!(rgb.len(), out.len());
+
+    // SAFETY: SSE2 is part of the x86_64 baseline, so it is always present.
+    #[cfg(target_arch = "x86_64")]
+    let done = unsafe { sse2::convert(rgb, out) };
+    #[cfg(not(target_arch = "x86_64"))]
+    let done = 0;
+
+    for (pixel, lab) in rgb[done..].iter().zip(&mut out[done..]) {
+        *lab = convert_scalar(*pixel);
+    }
+}
+
+fn convert_scalar(rgb: [u8; 3]) -> Lab {
+    let table = linear_table();
+    let [r, g, b] = rgb.map(|c| table[c as usize]);
+    let [x, y, z] = XYZ_MATRIX.map(|m| m[0] * r + m[1] * g + m[2] * b);
+
+    let f = |t: f32| {
+        if t > EPSILON {
+            t.cbrt()
+        } else {
+            KAPPA * t + DELTA
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    Lab::new(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    use palette::Lab;
+
+    use super::{DELTA, EPSILON, KAPPA, XYZ_MATRIX};
+    use crate::color::linear_table;
+
+    /// Converts as many whole groups of four pixels as fit and returns how
+    /// many pixels were written.
+    #[target_feature(enable = "sse2")]
+    pub fn convert(rgb: &[[u8; 3]], out: &mut [Lab]) -> usize {
+        let table = linear_table();
+        let groups = rgb.len() / 4;
+
+        for (pixels, labs) in rgb.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+            let channel = |c: usize| {
+                _mm_setr_ps(
+                    table[pixels[0][c] as usize],
+                    table[pixels[1][c] as usize],
+                    table[pixels[2][c] as usize],
+                    table[pixels[3][c] as usize],
+                )
+            };
+            let (r, g, b) = (channel(0), channel(1), channel(2));
+
+            let [x, y, z] = XYZ_MATRIX.map(|m| {
+                _mm_add_ps(
+                    _mm_add_ps(
+                        _mm_mul_ps(r, _mm_set1_ps(m[0])),
+                        _mm_mul_ps(g, _mm_set1_ps(m[1])),
+                    ),
+                    _mm_mul_ps(b, _mm_set1_ps(m[2])),
+                )
+            });
+            let (fx, fy, fz) = (companding(x), companding(y), companding(z));
+
+            let l = _mm_sub_ps(_mm_mul_ps(fy, _mm_set1_ps(116.0)), _mm_set1_ps(16.0));
+            let a = _mm_mul_ps(_mm_sub_ps(fx, fy), _mm_set1_ps(500.0));
+            let bb = _mm_mul_ps(_mm_sub_ps(fy, fz), _mm_set1_ps(200.0));
+
+            let (l, a, bb) = (unpack(l), unpack(a), unpack(bb));
+            for i in 0..4 {
+                labs[i] = Lab::new(l[i], a[i], bb[i]);
+            }
+        }
+
+        groups * 4
+    }
+
+    /// Lab `f(t)`: cube root above `EPSILON`, linear segment below.
+    #[target_feature(enable = "sse2")]
+    fn companding(t: __m128) -> __m128 {
+        let linear = _mm_add_ps(_mm_mul_ps(t, _mm_set1_ps(KAPPA)), _mm_set1_ps(DELTA));
+        let mask = _mm_cmpgt_ps(t, _mm_set1_ps(EPSILON));
+        _mm_or_ps(_mm_and_ps(mask, cbrt(t)), _mm_andnot_ps(mask, linear))
+    }
+
+    /// Cube root from an exponent-thirding bit trick refined by three Newton
+    /// steps (~1e-7 relative error for the positive inputs used here).
+    #[target_feature(enable = "sse2")]
+    fn cbrt(x: __m128) -> __m128 {
+        let third = _mm_set1_ps(1.0 / 3.0);
+        let bits = _mm_cvtepi32_ps(_mm_castps_si128(x));
+        let guess = _mm_add_epi32(
+            _mm_cvtps_epi32(_mm_mul_ps(bits, third)),
+            _mm_set1_epi32(0x2a51_4067),
+        );
+        let mut y = _mm_castsi128_ps(guess);
+
+        for _ in 0..3 {
+            let y2 = _mm_mul_ps(y, y);
+            y = _mm_mul_ps(_mm_add_ps(_mm_add_ps(y, y), _mm_div_ps(x, y2)), third);
+        }
+
+        y
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn unpack(v: __m128) -> [f32; 4] {
+        let mut out = [0.0f32; 4];
+        // SAFETY: `out` is exactly four f32s and `_mm_storeu_ps` has no
+        // alignment requirement.
+        unsafe { _mm_storeu_ps(out.as_mut_ptr(), v) };
+        out
+    }
+}
+
+// === BENCHMARK ===
+pub struct LabBenchmark {
+    pub pixels: usize,
+    pub batch: Duration,
+    pub palette_crate: Duration,
+    /// Largest per-component difference between the two paths.
+    pub max_error: f32,
+}
+
+/// Times `convert_row` against per-pixel `palette` conversions on the same
+/// pseudo-random input, for the debug panel.
+pub fn benchmark() -> LabBenchmark {
+    let mut seed = 0x2545_f491u32;
+    let rgb: Vec<[u8; 3]> = (0..BENCHMARK_PIXELS)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            [seed as u8, (seed >> 8) as u8, (seed >> 16) as u8]
+        })
+        .collect();
+
+    let mut batch = vec![Lab::new(0.0, 0.0, 0.0); rgb.len()];
+    let started = Instant::now();
+    for (pixels, labs) in rgb
+        .chunks(crate::IMAGE_WIDTH as usize)
+        .zip(batch.chunks_mut(crate::IMAGE_WIDTH as usize))
+    {
+        convert_row(pixels, labs);
+    }
+    let batch_time = started.elapsed();
+
+    let started = Instant::now();
+    let reference: Vec<Lab> = rgb.iter().map(|pixel| to_lab(*pixel)).collect();
+    let palette_time = started.elapsed();
+
+    let max_error = batch
+        .iter()
+        .zip(&reference)
+        .map(|(a, b)| {
+            (a.l - b.l)
+                .abs()
+                .max((a.a - b.a).abs())
+                .max((a.b - b.b).abs())
+        })
+        .fold(0.0, f32::max);
+
+    LabBenchmark {
+        pixels: rgb.len(),
+        batch: batch_time,
+        palette_crate: palette_time,
+        max_error,
+    }
+}
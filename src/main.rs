@@ -1,59 +1,2026 @@
+mod a11y;
+mod adjust;
+mod alpha;
+mod border;
+mod calibrate;
+mod capture;
+mod chroma;
+mod color;
+mod colorblind;
+mod composite;
+mod config;
+mod contrast;
+mod crop;
+mod denoise;
+mod dib;
+mod dither;
+mod edit;
+mod filter;
+mod fit;
+mod flag_view;
+mod font;
+mod gallery;
+mod histogram;
+mod history;
+mod hotkey;
+mod icc;
+mod kdtree;
+mod lab;
+mod mockup;
+mod onboarding;
+mod overlay;
+mod palettes;
+mod pipeline;
+mod quality;
+mod remap;
+mod resize;
+mod saliency;
+mod segment;
+mod source;
+mod steam;
+mod svg;
+mod text;
+mod transform;
+mod uv;
+mod webcam;
+mod wic;
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use adjust::{Adjustments, Equalize, PosterizeSettings, SharpenSettings};
+use alpha::{AlphaSettings, OutlineSettings};
 use arboard::Clipboard;
+use border::{Border, BorderStyle};
+use calibrate::Calibration;
+use capture::CaptureTarget;
+use chroma::ChromaKey;
 use chrono::{DateTime, Local};
+use color::{ColorMetric, LabWeights, MatchParams, TieBreak};
+use colorblind::Vision;
+use composite::Layers;
+use config::{Config, UI_SCALE_MAX, UI_SCALE_MIN};
+use contrast::ContrastSettings;
+use crop::{CropDrag, CropRect};
+use denoise::{DenoiseMode, DenoiseSettings};
+use dither::{DitherMode, DitherSettings};
+use edit::PixelEdits;
 use eframe::{App, CreationContext, egui};
-use image::{DynamicImage, GenericImageView, Pixel, RgbaImage, imageops::FilterType};
-use palette::{FromColor, Lab, Srgb};
+use egui::{Color32, ColorImage, TextureHandle, TextureOptions};
+use filter::{FilterMode, FilterSettings, VignetteSettings};
+use fit::{FitMode, FitSettings};
+use font::Font;
+use histogram::Histogram;
+use hotkey::Hotkey;
+use image::{DynamicImage, RgbaImage};
+use palettes::Palette;
+use pipeline::Pipeline;
+use quality::QualityReport;
+use remap::RemapRule;
+use resize::{PixelArt, ResizeMode, ResizeSettings, UpscaleMode};
+use saliency::SaliencySettings;
+use segment::{BackgroundRemoval, ModelStatus};
+use text::{Placement, TextOverlay};
+use transform::{Orientation, Symmetry};
 use winreg::enums::{HKEY_CURRENT_USER, RegType};
 use winreg::{RegKey, RegValue};
 
 // === CONFIG ===
 const IMAGE_WIDTH: u32 = 100;
 const IMAGE_HEIGHT: u32 = 66;
+// Grid of the built-in palette image.
 const PALETTE_COLS: u32 = 7;
 const PALETTE_ROWS: u32 = 6;
+// Its lightest and darkest cells, the defaults wherever two colors must
+// stand out from each other.
+const PALETTE_LIGHTEST: usize = 35;
+const PALETTE_DARKEST: usize = 41;
 
 const REGISTRY_PATH: &str = "Software\\jrsjams\\MageArena";
 const REGISTRY_VALUE_NAME: &str = "flagGrid_h3042110417";
+// MageFlag's own preferences.
+const SETTINGS_REGISTRY_PATH: &str = "Software\\MageFlag";
+
+// Defaults of the matching `Config` fields.
+const CLIPBOARD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const WORKER_TICK: Duration = Duration::from_millis(100);
+
+// Wait before trying again to watch a folder that could not be watched.
+const WATCH_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+// Time for the window to minimize before the screen is grabbed.
+const CAPTURE_DELAY: Duration = Duration::from_millis(300);
+// Default time each frame of an animated flag stays in the registry.
+const ANIMATION_INTERVAL: Duration = Duration::from_millis(500);
+// Longest frame time the animation slider offers.
+const ANIMATION_INTERVAL_MAX: Duration = Duration::from_secs(5);
 
 const EMBEDDED_PALETTE: &[u8] = include_bytes!("palette.png");
 
+const PREVIEW_SCALE: f32 = 2.0;
+// Largest whole-pixel scale of the flag preview under the source controls.
+const FLAG_PREVIEW_MAX_SCALE: f32 = 6.0;
+// Longest edge of the source thumbnail behind the crop editor, in pixels.
+const SOURCE_PREVIEW_MAX: f32 = 480.0;
+// ΔE at which the error heatmap saturates to solid red.
+const HEATMAP_MAX_DELTA_E: f32 = 20.0;
+// Zoom factors closer than this count as equal.
+const ZOOM_EPSILON: f32 = 0.001;
+// Keyboard shortcuts for the main window's actions.
+const OPEN_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::O);
+const APPLY_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Enter);
+const SETTINGS_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Comma);
+
 // === UI STATE ===
+/// Everything that affects the encoded flag; a change triggers a re-encode.
+#[derive(Clone, PartialEq)]
+struct Settings {
+    orientation: Orientation,
+    /// Region of the source to use; `None` takes the whole image.
+    crop: Option<CropRect>,
+    outline: OutlineSettings,
+    layers: Layers,
+    alpha: AlphaSettings,
+    background_removal: BackgroundRemoval,
+    chroma_key: ChromaKey,
+    fit: FitSettings,
+    resize: ResizeSettings,
+    sharpen: SharpenSettings,
+    symmetry: Symmetry,
+    denoise: DenoiseSettings,
+    adjustments: Adjustments,
+    filter: FilterSettings,
+    vignette: VignetteSettings,
+    posterize: PosterizeSettings,
+    dither: DitherSettings,
+    /// `None` picks a metric per image (see `ColorMetric::resolve`).
+    metric: Option<ColorMetric>,
+    weights: LabWeights,
+    tie_break: TieBreak,
+    /// Match through a precomputed RGB lookup table instead of searching the
+    /// palette per pixel.
+    use_lut: bool,
+    /// Palette indices the quantizer must never emit.
+    disabled_colors: BTreeSet<usize>,
+    /// Source→palette overrides applied before nearest-color matching.
+    remap_rules: Vec<RemapRule>,
+    contrast: ContrastSettings,
+    saliency: SaliencySettings,
+    text_overlay: TextOverlay,
+    border: Border,
+    calibration: Calibration,
+    /// Flag pixels painted in the editor, drawn over the encoded flag.
+    pixel_edits: PixelEdits,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            orientation: Orientation::default(),
+            crop: None,
+            outline: OutlineSettings::default(),
+            layers: Layers::default(),
+            alpha: AlphaSettings::default(),
+            background_removal: BackgroundRemoval::default(),
+            chroma_key: ChromaKey::default(),
+            fit: FitSettings::default(),
+            resize: ResizeSettings::default(),
+            sharpen: SharpenSettings::default(),
+            symmetry: Symmetry::Off,
+            denoise: DenoiseSettings::default(),
+            adjustments: Adjustments::default(),
+            filter: FilterSettings::default(),
+            vignette: VignetteSettings::default(),
+            posterize: PosterizeSettings::default(),
+            dither: DitherSettings::default(),
+            metric: None,
+            weights: LabWeights::default(),
+            tie_break: TieBreak::default(),
+            use_lut: true,
+            disabled_colors: BTreeSet::new(),
+            remap_rules: Vec::new(),
+            contrast: ContrastSettings::default(),
+            saliency: SaliencySettings::default(),
+            text_overlay: TextOverlay::default(),
+            border: Border::default(),
+            calibration: Calibration::default(),
+            pixel_edits: PixelEdits::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn match_params(&self, palette: &[[u8; 3]], metric: ColorMetric) -> MatchParams {
+        let mut allowed: Vec<usize> = (0..palette.len())
+            .filter(|idx| !self.disabled_colors.contains(idx))
+            .collect();
+        // Excluding every cell would leave nothing to match against.
+        if allowed.is_empty() {
+            allowed = (0..palette.len()).collect();
+        }
+
+        MatchParams {
+            metric,
+            weights: self.weights,
+            tie_break: self.tie_break,
+            allowed,
+        }
+    }
+}
+
+/// Renders of the last image, published by the watcher thread.
+struct Preview {
+    /// Flag-sized source as fed to quantization (after adjustments).
+    resized: ColorImage,
+    /// Flag-sized source without pre-downscale sharpening, when it is on.
+    unsharpened: Option<ColorImage>,
+    /// Result with the current Lab weights.
+    weighted: ColorImage,
+    /// Same settings with neutral weights, for comparison.
+    unweighted: ColorImage,
+    /// `weighted` in the colors the calibration curve predicts in game.
+    in_game: Option<ColorImage>,
+    /// Per-pixel ΔE of `weighted` against `resized`, as a translucent overlay.
+    heatmap: ColorImage,
+    /// Row-major palette indices of `weighted`, for the editor's tools.
+    indices: Vec<usize>,
+    /// `weighted` with the pixels that differ from the flag in the registry
+    /// standing out; `None` when no flag is saved.
+    diff: Option<ColorImage>,
+    /// Flag pixels that differ from the one in the registry.
+    changed: usize,
+    /// Of `resized` and of `weighted`.
+    histograms: [Histogram; 2],
+}
+
+/// A downscaled copy of the current source for the crop editor.
+struct SourcePreview {
+    image: ColorImage,
+    /// Full-size source dimensions.
+    size: [u32; 2],
+    /// Suggested crop around the most detailed region.
+    subject: CropRect,
+}
+
+impl SourcePreview {
+    fn new(source: &RgbaImage) -> Self {
+        let (w, h) = source.dimensions();
+        let scale = (SOURCE_PREVIEW_MAX / w.max(h) as f32).min(1.0);
+        let thumbnail = image::imageops::thumbnail(
+            source,
+            ((w as f32 * scale) as u32).max(1),
+            ((h as f32 * scale) as u32).max(1),
+        );
+        Self {
+            image: ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width() as usize, thumbnail.height() as usize],
+                &thumbnail,
+            ),
+            size: [w, h],
+            subject: crop::subject(&thumbnail),
+        }
+    }
+}
+
 #[derive(Default)]
 struct AppState {
     last_update: Option<String>,
+    settings: Settings,
+    source_preview: Option<SourcePreview>,
+    /// Bumped whenever `source_preview` is replaced.
+    source_version: u64,
+    preview: Option<Preview>,
+    /// Bumped whenever `preview` is replaced so the UI re-uploads textures.
+    preview_version: u64,
+    /// Wall time of the last quantize + CSV encode, for the debug readout.
+    last_encode_time: Option<Duration>,
+    /// The last image was already made of palette colors and mapped 1:1.
+    last_exact_match: bool,
+    last_quality: Option<QualityReport>,
+    /// Flag pixels per palette cell in the last encode.
+    cell_usage: Vec<usize>,
+    /// Name of the non-sRGB color profile the current source was converted
+    /// from.
+    source_profile: Option<String>,
+    /// Where the current source came from: "Clipboard" or a file name.
+    source_name: Option<String>,
+    /// Why the last requested file could not be loaded.
+    source_error: Option<String>,
+    /// Frames in an animated source; 0 for still images.
+    frame_count: usize,
+    frame_index: usize,
+    sharpest_frame: usize,
+    /// A frame of the animated source the UI picked, for the worker to
+    /// quantize.
+    frame_request: Option<usize>,
+    /// Set by the UI to cycle every frame of the animated source through the
+    /// registry.
+    animate: bool,
+    animation_interval: Duration,
+    /// The worker is cycling frames; cleared once the still flag is back.
+    animating: bool,
+    /// Encode new sources and settings without writing the registry until
+    /// the UI asks to apply.
+    review: bool,
+    /// The previewed flag differs from what was last written.
+    staged: bool,
+    /// Set by the UI; the worker writes the staged flag.
+    apply_requested: bool,
+    watch_clipboard: bool,
+    /// Download images from copied https URLs, on a thread of their own.
+    fetch_urls: bool,
+    poll_interval: Duration,
+    /// A file the UI asked to load as the source, for the worker to pick up.
+    open_request: Option<PathBuf>,
+    /// Set by the UI; the worker decodes the saved flag as the source.
+    load_flag_requested: bool,
+    /// Folder whose new images become the source.
+    watch_folder: Option<PathBuf>,
+    watch_error: Option<String>,
+    /// An image the UI captured, for the worker to use as the source.
+    captured: Option<source::Incoming>,
+    hotkey: Hotkey,
+    hotkey_error: Option<String>,
+    /// Size of the image pinned as the compositing background.
+    pinned_background: Option<[u32; 2]>,
+    /// Set by the UI; the worker pins the current source and clears it.
+    pin_background_requested: bool,
+    /// A palette the UI switched to, for the worker to pick up.
+    palette_request: Option<Palette>,
+    /// The palette file changed on disk and was re-sampled by the worker,
+    /// or failed to load.
+    palette_reload: Option<Result<Palette, String>>,
+    /// Set by the UI; the worker writes the calibration test flag.
+    calibration_pattern_requested: bool,
+    /// Set by the UI; the worker measures the test flag in the current
+    /// source.
+    calibration_measure_requested: bool,
+    calibration_status: Option<String>,
+    /// Why the game's registry key could not be opened or written.
+    registry_error: Option<String>,
+    /// False while the setup walkthrough is open; the worker neither reads
+    /// the clipboard nor touches the registry until it is through.
+    setup_done: bool,
     quit_requested: bool,
 }
 
+struct PreviewTextures {
+    resized: TextureHandle,
+    unsharpened: Option<TextureHandle>,
+    weighted: TextureHandle,
+    unweighted: TextureHandle,
+    in_game: Option<TextureHandle>,
+    /// `weighted` and `in_game` sampled bilinearly, as the game draws them.
+    weighted_filtered: TextureHandle,
+    in_game_filtered: Option<TextureHandle>,
+    heatmap: TextureHandle,
+    diff: Option<TextureHandle>,
+    /// `weighted` as seen with each of `Vision::SIMULATED`.
+    colorblind: [TextureHandle; 3],
+}
+
+impl PreviewTextures {
+    /// The encoded flag, as in game when that is asked for and calibrated,
+    /// and smoothed by bilinear filtering when `filtered`.
+    fn flag(&self, in_game: bool, filtered: bool) -> &TextureHandle {
+        match (&self.in_game, &self.in_game_filtered) {
+            (Some(texture), _) if in_game && !filtered => texture,
+            (_, Some(texture)) if in_game && filtered => texture,
+            _ if filtered => &self.weighted_filtered,
+            _ => &self.weighted,
+        }
+    }
+}
+
 struct MageFlagApp {
     state: Arc<Mutex<AppState>>,
+    palette: Palette,
+    /// Grid (cols, rows) a palette image is sampled with when loaded.
+    palette_grid: [u32; 2],
+    palette_error: Option<String>,
+    /// Palettes saved in the palettes folder, for the profile dropdown.
+    palette_profiles: Vec<PathBuf>,
+    /// Copy of the palette being edited, and the name to save it under.
+    palette_draft: Option<Palette>,
+    palette_draft_name: String,
+    preview_textures: Option<PreviewTextures>,
+    preview_version: u64,
+    source_texture: Option<(TextureHandle, [u32; 2])>,
+    subject_crop: Option<CropRect>,
+    source_version: u64,
+    crop_drag: Option<CropDrag>,
+    crop_lock_aspect: bool,
+    show_heatmap: bool,
+    compare_sharpen: bool,
+    /// Show previews in the calibrated in-game colors.
+    show_in_game: bool,
+    flag_view: flag_view::FlagView,
+    /// The last rendered mockup and its texture.
+    mockup: Option<(RgbaImage, TextureHandle)>,
+    mockup_error: Option<String>,
+    /// The setup walkthrough, shown on first run.
+    onboarding: Option<onboarding::Wizard>,
+    config: Config,
+    /// `config` as last saved, to save again when it changes.
+    saved_config: Config,
+    config_open: bool,
+    config_error: Option<String>,
+    /// Windows' text size, read at startup, and the zoom factor the window
+    /// was last sized for.
+    text_scale: f32,
+    applied_zoom: f32,
+    /// The Lab benchmark, filled in by its thread once it finishes.
+    lab_benchmark: Option<Arc<Mutex<Option<lab::LabBenchmark>>>>,
+    /// When to grab the screen for a region capture, once minimized.
+    capture_at: Option<Instant>,
+    region_picker: Option<capture::RegionPicker>,
+    /// Windows offered for `CaptureTarget::Window`, with their titles.
+    capture_windows: Vec<(windows_sys::Win32::Foundation::HWND, String)>,
+    webcam: webcam::Panel,
+    /// A folder of images being browsed for the source.
+    gallery: Option<gallery::Gallery>,
+    clipboard_history: Option<history::Picker>,
 }
 
 impl App for MageFlagApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // eframe resets the visuals when Windows switches theme.
+        let dark = self.config.theme.is_dark(frame.info().system_theme);
+        if ctx.style().visuals.dark_mode != dark {
+            ctx.set_visuals(if dark {
+                egui::Visuals::dark()
+            } else {
+                egui::Visuals::light()
+            });
+        }
+        // A new zoom factor takes effect a frame after it is set, whether by
+        // the UI scale setting or egui's Ctrl+plus/minus. The window then
+        // grows or shrinks with it so the layout keeps its room.
+        let zoom = ctx.zoom_factor();
+        if (zoom - self.applied_zoom).abs() > ZOOM_EPSILON {
+            self.config.ui_scale = (zoom / self.text_scale).clamp(UI_SCALE_MIN, UI_SCALE_MAX);
+            let size = ctx.screen_rect().size() * zoom / self.applied_zoom;
+            let monitor = ctx.input(|i| i.viewport().monitor_size);
+            let size = monitor.map_or(size, |monitor| size.min(monitor));
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+            self.applied_zoom = zoom;
+        }
+        let wanted = self.config.ui_scale * self.text_scale;
+        if (wanted - self.applied_zoom).abs() > ZOOM_EPSILON {
+            ctx.set_zoom_factor(wanted);
+        }
+
         let mut state = self.state.lock().unwrap();
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("📋 Clipboard Watcher");
-            ui.label(
-                "This tool watches your clipboard for images and writes them to the registry.",
-            );
-            if let Some(ref status) = state.last_update {
-                ui.label(format!("✅ Last update: {status}"));
-            } else {
-                ui.label("No clipboard image captured yet.");
+        if state.preview_version != self.preview_version {
+            self.preview_version = state.preview_version;
+            self.preview_textures = state.preview.as_ref().map(|preview| {
+                let load = |name: &str, image: &ColorImage| {
+                    ctx.load_texture(name, image.clone(), TextureOptions::NEAREST)
+                };
+                let load_filtered = |name: &str, image: &ColorImage| {
+                    ctx.load_texture(name, image.clone(), TextureOptions::LINEAR)
+                };
+                PreviewTextures {
+                    resized: load("preview_resized", &preview.resized),
+                    unsharpened: preview
+                        .unsharpened
+                        .as_ref()
+                        .map(|image| load("preview_unsharpened", image)),
+                    weighted: load("preview_weighted", &preview.weighted),
+                    unweighted: load("preview_unweighted", &preview.unweighted),
+                    in_game: preview
+                        .in_game
+                        .as_ref()
+                        .map(|image| load("preview_in_game", image)),
+                    weighted_filtered: load_filtered(
+                        "preview_weighted_filtered",
+                        &preview.weighted,
+                    ),
+                    in_game_filtered: preview
+                        .in_game
+                        .as_ref()
+                        .map(|image| load_filtered("preview_in_game_filtered", image)),
+                    heatmap: load("preview_heatmap", &preview.heatmap),
+                    diff: preview
+                        .diff
+                        .as_ref()
+                        .map(|image| load("preview_diff", image)),
+                    colorblind: Vision::SIMULATED.map(|vision| {
+                        load(
+                            "preview_colorblind",
+                            &colorblind::simulate(&preview.weighted, vision),
+                        )
+                    }),
+                }
+            });
+        }
+
+        if let Some(reload) = state.palette_reload.take() {
+            match reload {
+                Ok(palette) => {
+                    self.palette = palette;
+                    self.palette_error = None;
+                }
+                Err(error) => self.palette_error = Some(error),
             }
+        }
 
-            ui.add_space(10.0);
-            if ui.button("Quit").clicked() {
-                state.quit_requested = true;
+        if state.source_version != self.source_version {
+            self.source_version = state.source_version;
+            self.source_texture = state.source_preview.as_ref().map(|preview| {
+                let texture = ctx.load_texture(
+                    "source_preview",
+                    preview.image.clone(),
+                    TextureOptions::LINEAR,
+                );
+                (texture, preview.size)
+            });
+            self.subject_crop = state.source_preview.as_ref().map(|preview| preview.subject);
+        }
+
+        let preview_size = egui::vec2(IMAGE_WIDTH as f32, IMAGE_HEIGHT as f32) * PREVIEW_SCALE;
+
+        let mut browse_folder = None;
+        if let Some(path) = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone())) {
+            if path.is_dir() {
+                browse_folder = Some(path);
+            } else {
+                state.open_request = Some(path);
+            }
+        }
+        if let Some(at) = self.capture_at {
+            let now = Instant::now();
+            if now >= at {
+                self.capture_at = None;
+                match capture::screen() {
+                    Ok(screen) => {
+                        self.region_picker = Some(capture::RegionPicker::new(ctx, screen))
+                    }
+                    Err(error) => state.source_error = Some(error),
+                }
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            } else {
+                ctx.request_repaint_after(at - now);
             }
+        }
+        if let Some(picker) = &mut self.region_picker {
+            match picker.show(ctx) {
+                capture::RegionOutcome::Open => {}
+                capture::RegionOutcome::Cancelled => self.region_picker = None,
+                capture::RegionOutcome::Selected(image) => {
+                    state.captured = Some(source::Incoming::new(image, "Screen region"));
+                    self.region_picker = None;
+                }
+            }
+        }
+
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let screen = ctx.screen_rect();
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("drop_target"),
+            ));
+            painter.rect_filled(screen, 0.0, Color32::from_black_alpha(160));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop an image to use it as the source, or a folder to browse it",
+                egui::FontId::proportional(18.0),
+                Color32::WHITE,
+            );
+        }
+
+        let mut pin_background = false;
+        let mut new_palette = None;
+        let mut write_test_pattern = false;
+        let mut measure_calibration = false;
+        let mut hotkey = state.hotkey;
+        let mut snapped = None;
+        let open_shortcut = ctx.input_mut(|i| i.consume_shortcut(&OPEN_SHORTCUT));
+        if ctx.input_mut(|i| i.consume_shortcut(&SETTINGS_SHORTCUT)) {
+            self.config_open = true;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&APPLY_SHORTCUT)) && state.staged {
+            state.apply_requested = true;
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("📋 Clipboard Watcher");
+                ui.label(
+                    "This tool watches your clipboard for images and writes them to the registry.",
+                );
+                match &state.last_update {
+                    Some(status) => a11y::status(ui, format!("✅ Last update: {status}"), false),
+                    None => a11y::status(ui, "No clipboard image captured yet.", false),
+                };
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.review, "Review before apply")
+                        .on_hover_text("Preview new images without writing them to the game");
+                    if state.staged {
+                        if ui
+                            .button("Apply")
+                            .on_hover_text(format!("Write this flag to the game ({})", ctx.format_shortcut(&APPLY_SHORTCUT)))
+                            .clicked()
+                        {
+                            state.apply_requested = true;
+                        }
+                        match state.preview.as_ref().filter(|p| p.diff.is_some()) {
+                            Some(preview) => a11y::status(
+                                ui,
+                                format!(
+                                    "Not written to the game yet; {} pixels would change",
+                                    preview.changed
+                                ),
+                                false,
+                            ),
+                            None => a11y::status(ui, "Not written to the game yet", false),
+                        };
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    if ui
+                        .button("Settings…")
+                        .on_hover_text(format!("Preferences kept between sessions ({})", ctx.format_shortcut(&SETTINGS_SHORTCUT)))
+                        .clicked()
+                    {
+                        self.config_open = true;
+                    }
+                    if (ui
+                        .button("Open image…")
+                        .on_hover_text(format!("Use an image file as the source ({})", ctx.format_shortcut(&OPEN_SHORTCUT)))
+                        .clicked()
+                        || open_shortcut)
+                        && let Some(path) = rfd::FileDialog::new()
+                            .set_title("Open source image")
+                            .add_filter("Images", source::EXTENSIONS)
+                            .pick_file()
+                    {
+                        state.open_request = Some(path);
+                    }
+                    if ui.button("Browse folder…").clicked()
+                        && let Some(dir) = rfd::FileDialog::new()
+                            .set_title("Browse a folder of images")
+                            .pick_folder()
+                    {
+                        browse_folder = Some(dir);
+                    }
+                    if ui.button("Clipboard history…").clicked() {
+                        self.clipboard_history = Some(history::Picker::open());
+                    }
+                    if ui
+                        .button("Load in-game flag")
+                        .on_hover_text("Decode the flag the game currently has saved")
+                        .clicked()
+                    {
+                        state.load_flag_requested = true;
+                    }
+                    if ui.button("Capture region").clicked() {
+                        self.capture_at = Some(Instant::now() + self.config.capture_delay());
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                    }
+                    if ui.button("Watch folder…").clicked()
+                        && let Some(dir) = rfd::FileDialog::new()
+                            .set_title("Watch folder for new images")
+                            .pick_folder()
+                    {
+                        state.watch_error = None;
+                        state.watch_folder = Some(dir);
+                    }
+                    if let Some(name) = &state.source_name {
+                        ui.label(format!("Source: {name}"));
+                    }
+                });
+                if let Some(dir) = &state.watch_folder {
+                    let dir = dir.display().to_string();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Watching {dir}"));
+                        if ui.small_button("Stop").clicked() {
+                            state.watch_folder = None;
+                        }
+                    });
+                }
+                if let Some(error) = &state.watch_error {
+                    a11y::status(ui, error, true);
+                }
+                if let Some(error) = &state.source_error {
+                    a11y::status(ui, error, true);
+                }
+                if let Some(error) = &state.registry_error {
+                    a11y::status(ui, error, true);
+                }
+                if let Some(error) = &self.config_error {
+                    a11y::status(ui, format!("Could not save settings: {error}"), true);
+                }
+                if let Some(profile) = &state.source_profile {
+                    ui.label(format!("Converted to sRGB from “{profile}”"));
+                }
+                if state.frame_count > 1 {
+                    let mut frame = state.frame_index;
+                    ui.horizontal(|ui| {
+                        let slider = egui::Slider::new(&mut frame, 0..=state.frame_count - 1)
+                            .text("Frame");
+                        ui.add(slider);
+                        if ui
+                            .button("Sharpest")
+                            .on_hover_text("The frame with the most fine detail")
+                            .clicked()
+                        {
+                            frame = state.sharpest_frame;
+                        }
+                    });
+                    if frame != state.frame_index {
+                        state.frame_index = frame;
+                        state.frame_request = Some(frame);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut state.animate, "Animate flag").on_hover_text(
+                            "Rewrite the flag with each frame in turn; \
+                             stopping puts the picked frame back",
+                        );
+                        let mut millis = state.animation_interval.as_millis() as u64;
+                        let range = WORKER_TICK.as_millis() as u64
+                            ..=ANIMATION_INTERVAL_MAX.as_millis() as u64;
+                        ui.add(
+                            egui::Slider::new(&mut millis, range)
+                                .logarithmic(true)
+                                .suffix(" ms")
+                                .text("Per frame"),
+                        );
+                        state.animation_interval = Duration::from_millis(millis);
+                    });
+                    if state.animating {
+                        ui.label(format!(
+                            "Cycling {} frames in the registry",
+                            state.frame_count
+                        ));
+                    }
+                }
+                if let Some(report) = state.last_quality {
+                    ui.label(format!(
+                        "Quality: mean ΔE {:.1}, max ΔE {:.1} ({})",
+                        report.mean,
+                        report.max,
+                        report.verdict()
+                    ));
+                }
+                if let Some(textures) = &self.preview_textures {
+                    let AppState {
+                        preview, settings, ..
+                    } = &mut *state;
+                    let indices = preview.as_ref().map_or(&[][..], |p| &p.indices);
+                    self.flag_view.ui(
+                        ui,
+                        textures,
+                        self.show_in_game,
+                        &self.palette,
+                        indices,
+                        &mut settings.pixel_edits,
+                    );
+                }
+
+                let pinned_background = state.pinned_background;
+                let calibration_status = state.calibration_status.clone();
+                let cell_usage = state.cell_usage.clone();
+                let hotkey_error = state.hotkey_error.clone();
+                let settings = &mut state.settings;
+
+                ui.collapsing("Hotkey capture", |ui| {
+                    ui.checkbox(&mut hotkey.enabled, "Capture with a global hotkey");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut hotkey.ctrl, "Ctrl");
+                        ui.checkbox(&mut hotkey.alt, "Alt");
+                        ui.checkbox(&mut hotkey.shift, "Shift");
+                        egui::ComboBox::new("hotkey_key", "Key")
+                            .selected_text(Hotkey::key_name(hotkey.key))
+                            .show_ui(ui, |ui| {
+                                for key in Hotkey::keys() {
+                                    ui.selectable_value(&mut hotkey.key, key, Hotkey::key_name(key));
+                                }
+                            });
+                    });
+                    egui::ComboBox::from_label("Grabs")
+                        .selected_text(hotkey.target.label())
+                        .show_ui(ui, |ui| {
+                            for target in CaptureTarget::ALL {
+                                ui.selectable_value(&mut hotkey.target, target, target.label());
+                            }
+                            let chosen = matches!(hotkey.target, CaptureTarget::Window(_));
+                            if ui
+                                .selectable_label(chosen, CaptureTarget::Window(0).label())
+                                .clicked()
+                                && !chosen
+                            {
+                                self.capture_windows = capture::windows();
+                                let first = self.capture_windows.first();
+                                hotkey.target =
+                                    CaptureTarget::Window(first.map_or(0, |&(window, _)| window));
+                            }
+                        });
+                    if let CaptureTarget::Window(chosen) = &mut hotkey.target {
+                        ui.horizontal(|ui| {
+                            let title = capture::window_title(*chosen)
+                                .unwrap_or_else(|| "Pick a window".to_owned());
+                            egui::ComboBox::new("capture_window", "Window")
+                                .selected_text(title)
+                                .width(240.0)
+                                .show_ui(ui, |ui| {
+                                    for (window, title) in &self.capture_windows {
+                                        ui.selectable_value(chosen, *window, title);
+                                    }
+                                });
+                            if ui.button("Refresh").clicked() {
+                                self.capture_windows = capture::windows();
+                            }
+                        });
+                    }
+                    if let Some(error) = &hotkey_error {
+                        a11y::status(ui, error, true);
+                    }
+                });
+
+                let webcam = ui.collapsing("Webcam", |ui| {
+                    snapped = self.webcam.ui(ui);
+                });
+                if webcam.body_returned.is_none() {
+                    self.webcam.hide();
+                }
+
+                ui.collapsing("Rotate / flip", |ui| {
+                    let orientation = &mut settings.orientation;
+                    ui.horizontal(|ui| {
+                        if ui.button("⟲ 90°").clicked() {
+                            orientation.rotate_counterclockwise();
+                        }
+                        if ui.button("⟳ 90°").clicked() {
+                            orientation.rotate_clockwise();
+                        }
+                        ui.toggle_value(&mut orientation.flip_horizontal, "⇔ Flip H");
+                        ui.toggle_value(&mut orientation.flip_vertical, "⇕ Flip V");
+                        if ui.button("Reset").clicked() {
+                            *orientation = Orientation::default();
+                        }
+                    });
+                });
+
+                ui.collapsing("Crop", |ui| {
+                    let Some((texture, size)) = &self.source_texture else {
+                        ui.label("Copy an image to crop it.");
+                        return;
+                    };
+                    let [w, h] = *size;
+
+                    ui.horizontal(|ui| {
+                        let mut enabled = settings.crop.is_some();
+                        if ui.checkbox(&mut enabled, "Crop source").changed() {
+                            settings.crop = enabled.then(|| CropRect::flag_aspect(w, h));
+                        }
+                        ui.checkbox(&mut self.crop_lock_aspect, "Lock 100:66");
+                        if ui.button("Reset").clicked() {
+                            settings.crop = Some(CropRect::flag_aspect(w, h));
+                        }
+                        if let Some(subject) = self.subject_crop
+                            && ui
+                                .button("Auto-crop to subject")
+                                .on_hover_text("Frame the most detailed part of the image")
+                                .clicked()
+                        {
+                            settings.crop = Some(subject);
+                        }
+                    });
+
+                    if let Some(rect) = &mut settings.crop {
+                        crop::editor(
+                            ui,
+                            texture,
+                            *size,
+                            rect,
+                            &mut self.crop_drag,
+                            self.crop_lock_aspect,
+                        );
+                        let (_, _, crop_w, crop_h) = rect.to_pixels(w, h);
+                        ui.label(format!(
+                            "{crop_w}×{crop_h} of {w}×{h}. Drag to move, corners to resize, arrows to nudge."
+                        ));
+                    }
+                });
+
+                ui.collapsing("Layers", |ui| {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(
+                                self.source_texture.is_some(),
+                                egui::Button::new("Pin current image as background"),
+                            )
+                            .clicked()
+                        {
+                            pin_background = true;
+                        }
+                        match pinned_background {
+                            Some([w, h]) => ui.label(format!("Pinned: {w}×{h}")),
+                            None => ui.label("Nothing pinned"),
+                        };
+                    });
+
+                    let layers = &mut settings.layers;
+                    ui.add_enabled(
+                        pinned_background.is_some(),
+                        egui::Checkbox::new(
+                            &mut layers.enabled,
+                            "Draw the clipboard image over the pinned background",
+                        ),
+                    );
+                    ui.add_enabled_ui(layers.enabled, |ui| {
+                        for (name, placement) in [
+                            ("Background", &mut layers.background),
+                            ("Foreground", &mut layers.foreground),
+                        ] {
+                            ui.label(name);
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut placement.x)
+                                        .clamp_range(-0.5..=1.5)
+                                        .speed(0.005)
+                                        .prefix("x "),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut placement.y)
+                                        .clamp_range(-0.5..=1.5)
+                                        .speed(0.005)
+                                        .prefix("y "),
+                                );
+                                ui.add(
+                                    egui::Slider::new(&mut placement.scale, 0.1..=4.0)
+                                        .logarithmic(true)
+                                        .text("Scale"),
+                                );
+                            });
+                        }
+                    });
+                });
+
+                ui.collapsing("Transparency", |ui| {
+                    let alpha = &mut settings.alpha;
+                    ui.horizontal(|ui| {
+                        ui.label("Background:");
+                        ui.color_edit_button_srgb(&mut alpha.background);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut alpha.cutout, "Hard edges");
+                        ui.add_enabled(
+                            alpha.cutout,
+                            egui::Slider::new(&mut alpha.threshold, 1..=255).text("Alpha threshold"),
+                        );
+                    });
+                    ui.checkbox(
+                        &mut alpha.detect_checkerboard,
+                        "Treat a painted-in checkerboard as transparent",
+                    );
+
+                    let outline = &mut settings.outline;
+                    ui.checkbox(&mut outline.enabled, "Outline the subject");
+                    ui.add_enabled_ui(outline.enabled, |ui| {
+                        ui.add(egui::Slider::new(&mut outline.width, 1..=5).text("Width").suffix(" px"));
+                        ui.horizontal(|ui| {
+                            ui.label("Color:");
+                            palette_cell_picker(ui, &self.palette, &mut outline.color);
+                        });
+                    });
+                });
+
+                ui.collapsing("Background removal", |ui| {
+                    let removal = &mut settings.background_removal;
+                    let status = segment::status();
+                    ui.add_enabled(
+                        status == ModelStatus::Ready,
+                        egui::Checkbox::new(&mut removal.enabled, "Cut out the subject"),
+                    );
+                    match status {
+                        ModelStatus::Unsupported => {
+                            ui.label("This build has no segmentation support.");
+                        }
+                        ModelStatus::Missing => {
+                            if ui.button("Download model (~5 MB)").clicked() {
+                                segment::download();
+                            }
+                        }
+                        ModelStatus::Downloading => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label("Downloading model…");
+                            });
+                        }
+                        ModelStatus::Failed(error) => {
+                            ui.colored_label(ui.visuals().warn_fg_color, error);
+                            if ui.button("Retry download").clicked() {
+                                segment::download();
+                            }
+                        }
+                        ModelStatus::Ready => {}
+                    }
+                    ui.add_enabled_ui(removal.enabled, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Fill:");
+                            palette_cell_picker(ui, &self.palette, &mut removal.background);
+                        });
+                    });
+                });
+
+                ui.collapsing("Chroma key", |ui| {
+                    let key = &mut settings.chroma_key;
+                    ui.checkbox(&mut key.enabled, "Replace key color");
+                    ui.add_enabled_ui(key.enabled, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Key:");
+                            ui.color_edit_button_srgb(&mut key.key);
+                            ui.add(
+                                egui::DragValue::new(&mut key.tolerance)
+                                    .clamp_range(0.0..=100.0)
+                                    .speed(0.5)
+                                    .prefix("ΔE ≤ "),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Background:");
+                            palette_cell_picker(ui, &self.palette, &mut key.background);
+                        });
+                    });
+                });
+
+                ui.collapsing("Resampling", |ui| {
+                    egui::ComboBox::from_label("Fit")
+                        .selected_text(settings.fit.mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in FitMode::ALL {
+                                ui.selectable_value(&mut settings.fit.mode, mode, mode.label());
+                            }
+                        });
+                    if settings.fit.mode == FitMode::Letterbox {
+                        ui.horizontal(|ui| {
+                            ui.label("Bars:");
+                            palette_cell_picker(ui, &self.palette, &mut settings.fit.background);
+                        });
+                    }
+                    egui::ComboBox::from_label("Filter")
+                        .selected_text(settings.resize.mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in ResizeMode::ALL {
+                                ui.selectable_value(&mut settings.resize.mode, mode, mode.label());
+                            }
+                        });
+                    ui.checkbox(
+                        &mut settings.resize.linear_light,
+                        "Gamma-correct (linear light) downscaling",
+                    );
+                    egui::ComboBox::from_label("Enlarge with")
+                        .selected_text(settings.resize.upscale.label())
+                        .show_ui(ui, |ui| {
+                            for mode in UpscaleMode::ALL {
+                                ui.selectable_value(&mut settings.resize.upscale, mode, mode.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Used when the source is smaller than the flag");
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_label("Pixel art")
+                            .selected_text(settings.resize.pixel_art.label())
+                            .show_ui(ui, |ui| {
+                                for mode in PixelArt::ALL {
+                                    ui.selectable_value(
+                                        &mut settings.resize.pixel_art,
+                                        mode,
+                                        mode.label(),
+                                    );
+                                }
+                            });
+                        if settings.resize.pixel_art == PixelArt::Fixed {
+                            ui.add(
+                                egui::DragValue::new(&mut settings.resize.pixel_size)
+                                    .clamp_range(1..=64)
+                                    .suffix(" px"),
+                            );
+                        }
+                    });
+
+                    if let Some(textures) = &self.preview_textures {
+                        ui.image((textures.resized.id(), preview_size));
+                    }
+                });
+
+                ui.collapsing("Sharpening", |ui| {
+                    ui.checkbox(
+                        &mut settings.sharpen.enabled,
+                        "Unsharp mask before downscaling",
+                    );
+                    ui.add_enabled_ui(settings.sharpen.enabled, |ui| {
+                        ui.add(
+                            egui::Slider::new(&mut settings.sharpen.amount, 0.0..=3.0)
+                                .text("Amount"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut settings.sharpen.radius, 0.5..=5.0)
+                                .text("Radius")
+                                .suffix(" px"),
+                        );
+                        ui.checkbox(&mut self.compare_sharpen, "Compare with unsharpened");
+                    });
+
+                    if let Some(textures) = &self.preview_textures {
+                        match &textures.unsharpened {
+                            Some(unsharpened) if self.compare_sharpen => {
+                                ui.horizontal(|ui| {
+                                    ui.vertical(|ui| {
+                                        ui.label("Sharpened");
+                                        ui.image((textures.resized.id(), preview_size));
+                                    });
+                                    ui.vertical(|ui| {
+                                        ui.label("Unsharpened");
+                                        ui.image((unsharpened.id(), preview_size));
+                                    });
+                                });
+                            }
+                            _ => {
+                                ui.image((textures.resized.id(), preview_size));
+                            }
+                        }
+                    }
+                });
+
+                ui.collapsing("Symmetry", |ui| {
+                    egui::ComboBox::from_label("Symmetry")
+                        .selected_text(settings.symmetry.label())
+                        .show_ui(ui, |ui| {
+                            for symmetry in Symmetry::ALL {
+                                ui.selectable_value(
+                                    &mut settings.symmetry,
+                                    symmetry,
+                                    symmetry.label(),
+                                );
+                            }
+                        });
+
+                    if let Some(textures) = &self.preview_textures {
+                        ui.image((textures.flag(self.show_in_game, false).id(), preview_size));
+                    }
+                });
+
+                ui.collapsing("Denoise", |ui| {
+                    egui::ComboBox::new("denoise_filter", "Filter")
+                        .selected_text(settings.denoise.mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in DenoiseMode::ALL {
+                                ui.selectable_value(&mut settings.denoise.mode, mode, mode.label());
+                            }
+                        });
+                    ui.add_enabled_ui(settings.denoise.mode != DenoiseMode::Off, |ui| {
+                        ui.add(
+                            egui::Slider::new(&mut settings.denoise.radius, 1..=3).text("Radius"),
+                        );
+                    });
+                    ui.add_enabled_ui(settings.denoise.mode == DenoiseMode::Bilateral, |ui| {
+                        ui.add(
+                            egui::Slider::new(&mut settings.denoise.color_sigma, 4.0..=80.0)
+                                .text("Edge tolerance"),
+                        );
+                    });
+
+                    if let Some(textures) = &self.preview_textures {
+                        ui.image((textures.resized.id(), preview_size));
+                    }
+                });
+
+                ui.collapsing("Adjustments", |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("✨ Auto enhance").clicked() {
+                            settings.adjustments.auto_white_balance = true;
+                            settings.adjustments.auto_levels = true;
+                        }
+                        ui.checkbox(
+                            &mut settings.adjustments.auto_white_balance,
+                            "White balance",
+                        );
+                        ui.checkbox(&mut settings.adjustments.auto_levels, "Levels");
+                    });
+                    egui::ComboBox::from_label("Equalize histogram")
+                        .selected_text(settings.adjustments.equalize.label())
+                        .show_ui(ui, |ui| {
+                            for mode in Equalize::ALL {
+                                ui.selectable_value(
+                                    &mut settings.adjustments.equalize,
+                                    mode,
+                                    mode.label(),
+                                );
+                            }
+                        });
+                    let percent = |v: f64, _| format!("{:+.0}%", v * 100.0);
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.exposure, -3.0..=3.0)
+                            .text("Exposure")
+                            .custom_formatter(|v, _| format!("{v:+.1} EV")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.gamma, 0.2..=3.0)
+                            .text("Gamma")
+                            .logarithmic(true),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.temperature, -1.0..=1.0)
+                            .text("Temperature")
+                            .custom_formatter(|v, _| match v {
+                                v if v < 0.0 => format!("{:.0}% cool", -v * 100.0),
+                                v if v > 0.0 => format!("{:.0}% warm", v * 100.0),
+                                _ => "neutral".to_owned(),
+                            }),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.hue_shift, -180.0..=180.0)
+                            .text("Hue shift")
+                            .suffix("°"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.brightness, -1.0..=1.0)
+                            .text("Brightness")
+                            .custom_formatter(percent),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.contrast, -1.0..=1.0)
+                            .text("Contrast")
+                            .custom_formatter(percent),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.adjustments.saturation, -1.0..=1.0)
+                            .text("Saturation")
+                            .custom_formatter(percent),
+                    );
+                    if ui.button("Reset").clicked() {
+                        settings.adjustments = Adjustments::default();
+                    }
+
+                    if let Some(textures) = &self.preview_textures {
+                        ui.image((textures.resized.id(), preview_size));
+                    }
+                });
+
+                ui.collapsing("Filter", |ui| {
+                    let filter = &mut settings.filter;
+                    egui::ComboBox::new("color_filter", "Style")
+                        .selected_text(filter.mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in FilterMode::ALL {
+                                ui.selectable_value(&mut filter.mode, mode, mode.label());
+                            }
+                        });
+                    if filter.mode == FilterMode::Duotone {
+                        ui.horizontal(|ui| {
+                            ui.label("Shadows:");
+                            palette_cell_picker(ui, &self.palette, &mut filter.dark);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Highlights:");
+                            palette_cell_picker(ui, &self.palette, &mut filter.light);
+                        });
+                    }
+                });
+
+                ui.collapsing("Vignette", |ui| {
+                    let vignette = &mut settings.vignette;
+                    ui.checkbox(&mut vignette.enabled, "Darken the edges");
+                    ui.add_enabled_ui(vignette.enabled, |ui| {
+                        ui.add(
+                            egui::Slider::new(&mut vignette.strength, 0.0..=1.0)
+                                .text("Strength")
+                                .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut vignette.feather, 0.05..=1.0)
+                                .text("Feather")
+                                .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                        );
+                    });
+                });
+
+                ui.collapsing("Posterize", |ui| {
+                    ui.checkbox(&mut settings.posterize.enabled, "Posterize before matching");
+                    ui.add_enabled(
+                        settings.posterize.enabled,
+                        egui::Slider::new(&mut settings.posterize.levels, 2..=32)
+                            .text("Levels per channel"),
+                    );
+
+                    if let Some(textures) = &self.preview_textures {
+                        ui.image((textures.resized.id(), preview_size));
+                    }
+                });
+
+                egui::ComboBox::from_label("Dithering")
+                    .selected_text(settings.dither.mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in DitherMode::ALL {
+                            ui.selectable_value(&mut settings.dither.mode, mode, mode.label());
+                        }
+                    });
+                ui.checkbox(&mut settings.dither.serpentine, "Serpentine scan");
+                ui.add(
+                    egui::Slider::new(&mut settings.dither.strength, 0.0..=1.0)
+                        .text("Strength")
+                        .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                );
+                ui.checkbox(
+                    &mut settings.dither.selective,
+                    "Dither gradients only (keep flat fills solid)",
+                );
+                ui.add_enabled(
+                    settings.dither.selective,
+                    egui::Slider::new(&mut settings.dither.sensitivity, 0.0..=1.0)
+                        .text("Gradient sensitivity")
+                        .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                );
+
+                egui::ComboBox::from_label("Color metric")
+                    .selected_text(settings.metric.map_or("Auto", ColorMetric::label))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut settings.metric, None, "Auto");
+                        for metric in ColorMetric::ALL {
+                            ui.selectable_value(&mut settings.metric, Some(metric), metric.label());
+                        }
+                    });
+
+                egui::ComboBox::from_label("Tie-break")
+                    .selected_text(settings.tie_break.label())
+                    .show_ui(ui, |ui| {
+                        for tie_break in TieBreak::ALL {
+                            ui.selectable_value(
+                                &mut settings.tie_break,
+                                tie_break,
+                                tie_break.label(),
+                            );
+                        }
+                    });
+
+                ui.checkbox(&mut settings.use_lut, "Fast color lookup (32³ table)");
+
+                ui.checkbox(&mut settings.contrast.enabled, "Preserve local contrast");
+                ui.add_enabled(
+                    settings.contrast.enabled,
+                    egui::Slider::new(&mut settings.contrast.threshold, 2.0..=40.0)
+                        .text("Edge threshold (ΔE)"),
+                );
+
+                ui.collapsing("Saliency", |ui| {
+                    ui.checkbox(
+                        &mut settings.saliency.enabled,
+                        "Favor accuracy in salient regions",
+                    );
+                    ui.add_enabled_ui(settings.saliency.enabled, |ui| {
+                        ui.add(
+                            egui::Slider::new(&mut settings.saliency.amount, 0.0..=1.0)
+                                .text("Amount")
+                                .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut settings.saliency.center_bias, 0.0..=1.0)
+                                .text("Center bias")
+                                .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                        );
+                    });
+                });
+
+                ui.collapsing("Text", |ui| {
+                    let overlay = &mut settings.text_overlay;
+                    ui.checkbox(&mut overlay.enabled, "Draw caption");
+                    ui.add_enabled_ui(overlay.enabled, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut overlay.text)
+                                .desired_rows(2)
+                                .hint_text("Clan name"),
+                        );
+                        egui::ComboBox::from_label("Font")
+                            .selected_text(overlay.font.label())
+                            .show_ui(ui, |ui| {
+                                for font in Font::ALL {
+                                    ui.selectable_value(&mut overlay.font, font, font.label());
+                                }
+                            });
+                        ui.add(egui::Slider::new(&mut overlay.scale, 1..=4).text("Size").suffix("×"));
+                        egui::ComboBox::from_label("Placement")
+                            .selected_text(overlay.placement.label())
+                            .show_ui(ui, |ui| {
+                                for placement in Placement::ALL {
+                                    ui.selectable_value(
+                                        &mut overlay.placement,
+                                        placement,
+                                        placement.label(),
+                                    );
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            ui.label("Color:");
+                            palette_cell_picker(ui, &self.palette, &mut overlay.color);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut overlay.outline, "Outline:");
+                            ui.add_enabled_ui(overlay.outline, |ui| {
+                                palette_cell_picker(ui, &self.palette, &mut overlay.outline_color);
+                            });
+                        });
+                    });
+                });
+
+                ui.collapsing("Border", |ui| {
+                    let border = &mut settings.border;
+                    egui::ComboBox::from_label("Style")
+                        .selected_text(border.style.label())
+                        .show_ui(ui, |ui| {
+                            for style in BorderStyle::ALL {
+                                ui.selectable_value(&mut border.style, style, style.label());
+                            }
+                        });
+                    ui.add_enabled_ui(border.style != BorderStyle::Off, |ui| {
+                        ui.add(egui::Slider::new(&mut border.width, 1..=4).text("Width").suffix(" px"));
+                        ui.horizontal(|ui| {
+                            ui.label("Color:");
+                            palette_cell_picker(ui, &self.palette, &mut border.color);
+                        });
+                        ui.add_enabled_ui(
+                            matches!(border.style, BorderStyle::Double | BorderStyle::Corners),
+                            |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Accent:");
+                                    palette_cell_picker(ui, &self.palette, &mut border.accent);
+                                });
+                            },
+                        );
+                    });
+                });
+
+                ui.collapsing("Palette", |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_label("Profile")
+                            .selected_text(self.palette.name())
+                            .show_ui(ui, |ui| {
+                                if ui
+                                    .selectable_label(self.palette.source.is_none(), "Built-in")
+                                    .clicked()
+                                    && self.palette.source.is_some()
+                                {
+                                    new_palette = Some(Palette::embedded());
+                                }
+                                for path in &self.palette_profiles {
+                                    let selected = self.palette.source.as_ref() == Some(path);
+                                    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                                    if ui.selectable_label(selected, name).clicked() && !selected {
+                                        match Palette::load_saved(path) {
+                                            Ok(palette) => new_palette = Some(palette),
+                                            Err(error) => self.palette_error = Some(error),
+                                        }
+                                    }
+                                }
+                            });
+                        if ui
+                            .small_button("⟳")
+                            .on_hover_text("Rescan the palettes folder")
+                            .clicked()
+                        {
+                            self.palette_profiles = palettes::profiles();
+                        }
+                    });
+                    if let Some(path) = &self.palette.source {
+                        ui.label(format!("From {}", path.display()))
+                            .on_hover_text("Re-sampled automatically when the file changes");
+                    }
+                    ui.horizontal(|ui| {
+                        let [cols, rows] = &mut self.palette_grid;
+                        ui.add(egui::DragValue::new(cols).clamp_range(1..=palettes::GRID_MAX).prefix("cols "));
+                        ui.add(egui::DragValue::new(rows).clamp_range(1..=palettes::GRID_MAX).prefix("rows "));
+                        if ui.button("Load palette…").clicked()
+                            && let Some(path) = rfd::FileDialog::new()
+                                .set_title("Palette grid image")
+                                .add_filter("Images", &["png", "jpg", "jpeg", "bmp"])
+                                .pick_file()
+                        {
+                            match Palette::load(&path, *cols, *rows) {
+                                Ok(palette) => new_palette = Some(palette),
+                                Err(error) => self.palette_error = Some(error),
+                            }
+                        }
+                        if ui.button("Import JSON…").clicked()
+                            && let Some(path) = rfd::FileDialog::new()
+                                .set_title("Import palette")
+                                .add_filter("Palette", &["json"])
+                                .pick_file()
+                        {
+                            match Palette::import(&path) {
+                                Ok((palette, curve)) => {
+                                    new_palette = Some(palette);
+                                    if curve.is_some() {
+                                        settings.calibration = Calibration {
+                                            enabled: true,
+                                            curve,
+                                        };
+                                    }
+                                }
+                                Err(error) => self.palette_error = Some(error),
+                            }
+                        }
+                        if ui.button("Export JSON…").clicked()
+                            && let Some(path) = rfd::FileDialog::new()
+                                .set_title("Export palette")
+                                .add_filter("Palette", &["json"])
+                                .set_file_name(format!("{}.json", self.palette.name()))
+                                .save_file()
+                        {
+                            self.palette_error = self
+                                .palette
+                                .export(&path, settings.calibration.curve)
+                                .err();
+                        }
+                    });
+                    if let Some(error) = &self.palette_error {
+                        a11y::status(ui, error, true);
+                    }
+
+                    ui.label("Click a cell to exclude it from matching.");
+                    egui::Grid::new("palette_grid")
+                        .spacing([2.0, 2.0])
+                        .show(ui, |ui| {
+                            for (idx, &[r, g, b]) in self.palette.colors.iter().enumerate() {
+                                let enabled = !settings.disabled_colors.contains(&idx);
+                                let fill = Color32::from_rgb(r, g, b);
+                                let mark = egui::RichText::new(if enabled { "" } else { "✕" })
+                                    .color(contrast_text(fill));
+                                let cell = egui::Button::new(mark)
+                                    .fill(fill)
+                                    .min_size(egui::vec2(28.0, 20.0));
+
+                                let hex = format!("#{r:02X}{g:02X}{b:02X}");
+                                let cols = self.palette.cols as usize;
+                                let response = ui.add(cell);
+                                response.widget_info(|| {
+                                    egui::WidgetInfo::selected(
+                                        egui::WidgetType::Checkbox,
+                                        enabled,
+                                        format!(
+                                            "Row {}, column {}, {hex}, {}",
+                                            idx / cols + 1,
+                                            idx % cols + 1,
+                                            if enabled { "included" } else { "excluded" },
+                                        ),
+                                    )
+                                });
+                                let response = response.on_hover_text(hex);
+                                if response.clicked() {
+                                    if enabled {
+                                        settings.disabled_colors.insert(idx);
+                                    } else {
+                                        settings.disabled_colors.remove(&idx);
+                                    }
+                                }
+
+                                if (idx + 1) % self.palette.cols as usize == 0 {
+                                    ui.end_row();
+                                }
+                            }
+                        });
+                    if ui.button("Enable all").clicked() {
+                        settings.disabled_colors.clear();
+                    }
+                });
+
+                ui.collapsing("Palette inspector", |ui| {
+                    palette_inspector(ui, &self.palette);
+                });
+
+                ui.collapsing("Cell usage", |ui| {
+                    cell_usage_chart(ui, &self.palette, &cell_usage);
+                });
+
+                ui.collapsing("Palette editor", |ui| {
+                    let Some(draft) = &mut self.palette_draft else {
+                        if ui.button("Edit a copy of the current palette").clicked() {
+                            self.palette_draft = Some(self.palette.clone());
+                            self.palette_draft_name = self.palette.name();
+                        }
+                        return;
+                    };
+
+                    ui.horizontal(|ui| {
+                        let (mut cols, mut rows) = (draft.cols, draft.rows);
+                        let resized = ui
+                            .add(egui::DragValue::new(&mut cols).clamp_range(1..=palettes::GRID_MAX).prefix("cols "))
+                            .changed()
+                            | ui
+                                .add(egui::DragValue::new(&mut rows).clamp_range(1..=palettes::GRID_MAX).prefix("rows "))
+                                .changed();
+                        if resized {
+                            draft.reshape(cols, rows);
+                        }
+                    });
+
+                    egui::Grid::new("palette_editor")
+                        .spacing([2.0, 2.0])
+                        .show(ui, |ui| {
+                            let cols = draft.cols as usize;
+                            for (idx, color) in draft.colors.iter_mut().enumerate() {
+                                let [r, g, b] = *color;
+                                ui.color_edit_button_srgb(color).on_hover_text(format!(
+                                    "row {}, col {}: #{r:02X}{g:02X}{b:02X}",
+                                    idx / cols + 1,
+                                    idx % cols + 1
+                                ));
+                                if (idx + 1) % cols == 0 {
+                                    ui.end_row();
+                                }
+                            }
+                        });
+
+                    let mut close = false;
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        ui.text_edit_singleline(&mut self.palette_draft_name);
+                    });
+                    ui.horizontal(|ui| {
+                        let name = self.palette_draft_name.trim();
+                        let valid = !name.is_empty()
+                            && !name.contains(['\\', '/', ':', '*', '?', '"', '<', '>', '|']);
+                        if ui
+                            .add_enabled(valid, egui::Button::new("Save and use"))
+                            .clicked()
+                        {
+                            match draft.save(name) {
+                                Ok(saved) => {
+                                    new_palette = Some(saved);
+                                    close = true;
+                                }
+                                Err(error) => self.palette_error = Some(error),
+                            }
+                        }
+                        if ui.button("Discard").clicked() {
+                            close = true;
+                        }
+                    });
+                    if close {
+                        self.palette_draft = None;
+                    }
+                });
+
+                ui.collapsing("Calibration", |ui| {
+                    ui.label(
+                        "1. Write the test flag and view it in game.\n\
+                         2. Take a screenshot of it, then copy the screenshot.\n\
+                         3. Crop the source to just the flag and measure.",
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Write test flag").clicked() {
+                            write_test_pattern = true;
+                        }
+                        if ui
+                            .add_enabled(
+                                self.source_texture.is_some(),
+                                egui::Button::new("Measure screenshot"),
+                            )
+                            .clicked()
+                        {
+                            measure_calibration = true;
+                        }
+                    });
+                    if let Some(status) = &calibration_status {
+                        ui.label(status);
+                    }
+
+                    let calibration = &mut settings.calibration;
+                    if let Some(curve) = calibration.curve {
+                        ui.checkbox(&mut calibration.enabled, "Match against in-game colors");
+                        let [r, g, b] = curve.gain;
+                        let [ro, go, bo] = curve.offset;
+                        ui.label(format!(
+                            "Gain R {r:.2} G {g:.2} B {b:.2}, offset R {ro:+.3} G {go:+.3} B {bo:+.3}"
+                        ));
+                        if ui.button("Clear calibration").clicked() {
+                            *calibration = Calibration::default();
+                        }
+
+                        ui.checkbox(&mut self.show_in_game, "Preview flags as in game");
+                        if let Some(textures) = &self.preview_textures
+                            && let Some(in_game) = &textures.in_game
+                        {
+                            ui.horizontal(|ui| {
+                                ui.vertical(|ui| {
+                                    ui.label("Palette colors");
+                                    ui.image((textures.weighted.id(), preview_size));
+                                });
+                                ui.vertical(|ui| {
+                                    ui.label("In game");
+                                    ui.image((in_game.id(), preview_size));
+                                });
+                            });
+                        }
+                    }
+                });
+
+                ui.collapsing("Remap rules", |ui| {
+                    ui.label("Colors within the tolerance of a rule's source map to its cell.");
+                    let mut removed = None;
+
+                    for (i, rule) in settings.remap_rules.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_srgb(&mut rule.source);
+                            ui.label(format!(
+                                "#{:02X}{:02X}{:02X} →",
+                                rule.source[0], rule.source[1], rule.source[2]
+                            ));
+
+                            palette_cell_picker(ui, &self.palette, &mut rule.target);
+
+                            ui.add(
+                                egui::DragValue::new(&mut rule.tolerance)
+                                    .clamp_range(0.0..=50.0)
+                                    .speed(0.5)
+                                    .prefix("ΔE ≤ "),
+                            );
+                            if ui.button("🗑").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+                    }
+
+                    if let Some(i) = removed {
+                        settings.remap_rules.remove(i);
+                    }
+                    if ui.button("Add rule").clicked() {
+                        settings.remap_rules.push(RemapRule::default());
+                    }
+                });
+
+                ui.collapsing("Lab weights", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut settings.weights.l, 0.0..=4.0).text("L (lightness)"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.weights.a, 0.0..=4.0).text("a (green–red)"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.weights.b, 0.0..=4.0)
+                            .text("b (blue–yellow)"),
+                    );
+                    if ui.button("Reset").clicked() {
+                        settings.weights = LabWeights::default();
+                    }
+
+                    if let Some(textures) = &self.preview_textures {
+                        ui.horizontal(|ui| {
+                            ui.vertical(|ui| {
+                                ui.label("Weighted");
+                                ui.image((textures.weighted.id(), preview_size));
+                            });
+                            ui.vertical(|ui| {
+                                ui.label("Unweighted");
+                                ui.image((textures.unweighted.id(), preview_size));
+                            });
+                        });
+                    }
+                });
+
+                ui.collapsing("Histograms", |ui| match &state.preview {
+                    Some(preview) => {
+                        ui.horizontal(|ui| {
+                            for (histogram, title) in
+                                preview.histograms.iter().zip(["Source", "Flag"])
+                            {
+                                ui.vertical(|ui| {
+                                    ui.label(title);
+                                    histogram.ui(ui);
+                                });
+                            }
+                        });
+                    }
+                    None => {
+                        ui.label("Nothing encoded yet.");
+                    }
+                });
+
+                ui.collapsing("Mockup", |ui| {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(state.preview.is_some(), egui::Button::new("Render"))
+                            .on_hover_text("Hang the flag on a pole, as a picture to share")
+                            .clicked()
+                            && let Some(preview) = &state.preview
+                        {
+                            let flag = match &preview.in_game {
+                                Some(in_game) if self.show_in_game => in_game,
+                                _ => &preview.weighted,
+                            };
+                            let image = mockup::render(flag);
+                            let texture = ctx.load_texture(
+                                "mockup",
+                                mockup::to_color_image(&image),
+                                TextureOptions::LINEAR,
+                            );
+                            self.mockup = Some((image, texture));
+                            self.mockup_error = None;
+                        }
+                        if let Some((image, _)) = &self.mockup
+                            && ui.button("Save PNG…").clicked()
+                            && let Some(path) = rfd::FileDialog::new()
+                                .set_title("Save mockup")
+                                .add_filter("PNG", &["png"])
+                                .set_file_name("flag mockup.png")
+                                .save_file()
+                        {
+                            self.mockup_error = mockup::save(image, &path).err();
+                        }
+                    });
+                    if let Some(error) = &self.mockup_error {
+                        a11y::status(ui, error, true);
+                    }
+                    if let Some((image, texture)) = &self.mockup {
+                        let width = ui.available_width().min(image.width() as f32);
+                        let aspect = image.height() as f32 / image.width() as f32;
+                        ui.image((texture.id(), egui::vec2(width, width * aspect)));
+                    }
+                });
+
+                ui.collapsing("Error heatmap", |ui| {
+                    ui.checkbox(&mut self.show_heatmap, "Overlay ΔE heatmap");
+                    ui.label(format!(
+                        "Transparent = exact, red = ΔE {HEATMAP_MAX_DELTA_E:.0} or worse."
+                    ));
+
+                    if let Some(textures) = &self.preview_textures {
+                        let rect = ui.image((textures.weighted.id(), preview_size)).rect;
+                        if self.show_heatmap {
+                            ui.painter().image(
+                                textures.heatmap.id(),
+                                rect,
+                                egui::Rect::from_min_max(
+                                    egui::pos2(0.0, 0.0),
+                                    egui::pos2(1.0, 1.0),
+                                ),
+                                Color32::WHITE,
+                            );
+                        }
+                    }
+                });
+
+                ui.collapsing("Debug", |ui| {
+                    match state.last_encode_time {
+                        Some(time) => ui.label(format!("Encode time: {:.2} ms", millis(time))),
+                        None => ui.label("Encode time: –"),
+                    };
+                    if state.last_exact_match {
+                        ui.label("Source uses only palette colors: mapped losslessly.");
+                    }
+
+                    if ui.button("Run setup again").clicked() {
+                        self.onboarding = Some(onboarding::Wizard::new());
+                    }
+                    let running = self
+                        .lab_benchmark
+                        .as_ref()
+                        .is_some_and(|bench| bench.lock().unwrap().is_none());
+                    if ui
+                        .add_enabled(!running, egui::Button::new("Benchmark Lab conversion"))
+                        .clicked()
+                    {
+                        let bench = Arc::new(Mutex::new(None));
+                        let thread_bench = Arc::clone(&bench);
+                        thread::spawn(move || *thread_bench.lock().unwrap() = Some(lab::benchmark()));
+                        self.lab_benchmark = Some(bench);
+                    }
+                    if let Some(bench) = &self.lab_benchmark {
+                        match &*bench.lock().unwrap() {
+                            Some(bench) => ui.label(format!(
+                                "{} px — batch: {:.2} ms, palette crate: {:.2} ms (max Δ {:.4})",
+                                bench.pixels,
+                                millis(bench.batch),
+                                millis(bench.palette_crate),
+                                bench.max_error
+                            )),
+                            None => {
+                                ctx.request_repaint_after(Duration::from_millis(100));
+                                ui.label("Benchmarking…")
+                            }
+                        };
+                    }
+                });
+
+                ui.add_space(10.0);
+                if ui.button("Quit").clicked() {
+                    state.quit_requested = true;
+                }
+            });
         });
 
-        if state.quit_requested {
+        if let Some(dir) = browse_folder {
+            match gallery::Gallery::open(&dir, self.palette.clone(), state.settings.clone()) {
+                Ok(gallery) => self.gallery = Some(gallery),
+                Err(error) => state.source_error = Some(error),
+            }
+        }
+        if let Some(gallery) = &mut self.gallery {
+            let mut open = true;
+            let title = format!("Gallery: {}", gallery.dir.display());
+            let picked = egui::Window::new(title)
+                .id(egui::Id::new("gallery"))
+                .open(&mut open)
+                .default_size([560.0, 420.0])
+                .show(ctx, |ui| gallery.ui(ui))
+                .and_then(|response| response.inner.flatten());
+            if let Some(path) = picked {
+                state.open_request = Some(path);
+            }
+            if !open {
+                self.gallery = None;
+            }
+        }
+        if let Some(wizard) = &mut self.onboarding {
+            let mut open = true;
+            let finished = egui::Window::new("Welcome to MageFlag")
+                .open(&mut open)
+                .collapsible(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    wizard.ui(ui, &self.palette, &mut state.review, &mut hotkey)
+                })
+                .and_then(|response| response.inner)
+                .unwrap_or(false);
+            if finished {
+                self.config.onboarding_done = true;
+            }
+            if finished || !open {
+                self.onboarding = None;
+            }
+        }
+        state.setup_done = self.onboarding.is_none();
+        // The config mirrors what the main window edits too.
+        self.config.review = state.review;
+        self.config.watch_folder = state.watch_folder.clone();
+        self.config.hotkey = hotkey;
+        if self.config_open {
+            egui::Window::new("Settings")
+                .open(&mut self.config_open)
+                .show(ctx, |ui| self.config.ui(ui));
+        }
+        state.review = self.config.review;
+        if state.watch_folder != self.config.watch_folder {
+            state.watch_folder = self.config.watch_folder.clone();
+            state.watch_error = None;
+        }
+        state.watch_clipboard = self.config.watch_clipboard;
+        state.fetch_urls = self.config.fetch_urls;
+        state.poll_interval = self.config.poll_interval();
+        // Not while a slider is dragged, which would write every frame.
+        if self.config != self.saved_config && !ctx.input(|i| i.pointer.any_down()) {
+            self.config_error = self.config.save().err();
+            self.saved_config = self.config.clone();
+        }
+
+        if let Some(picker) = &mut self.clipboard_history {
+            let mut open = true;
+            let picked = egui::Window::new("Clipboard history")
+                .open(&mut open)
+                .default_size([480.0, 320.0])
+                .show(ctx, |ui| picker.ui(ui))
+                .and_then(|response| response.inner.flatten());
+            if let Some(incoming) = picked {
+                state.captured = Some(incoming);
+                open = false;
+            }
+            if !open {
+                self.clipboard_history = None;
+            }
+        }
+
+        if pin_background {
+            state.pin_background_requested = true;
+        }
+        if let Some(image) = snapped {
+            state.captured = Some(source::Incoming::new(image, "Webcam"));
+        }
+        if hotkey != state.hotkey {
+            state.hotkey = hotkey;
+            state.hotkey_error = None;
+        }
+        state.calibration_pattern_requested |= write_test_pattern;
+        state.calibration_measure_requested |= measure_calibration;
+        if let Some(palette) = new_palette {
+            self.config.palette = palette.choice();
+            self.palette = palette.clone();
+            self.palette_profiles = palettes::profiles();
+            state.palette_request = Some(palette);
+        }
+
+        // Let the worker put the still flag back before the process ends.
+        if state.animating && ctx.input(|i| i.viewport().close_requested()) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            state.quit_requested = true;
+        }
+        if state.quit_requested && !state.animating {
             std::process::exit(0);
         }
 
@@ -63,72 +2030,548 @@ impl App for MageFlagApp {
 
 // === MAIN ENTRYPOINT ===
 fn main() -> eframe::Result<()> {
-    let state = Arc::new(Mutex::new(AppState::default()));
+    let config = Config::load();
+    let state = Arc::new(Mutex::new(AppState {
+        settings: Settings {
+            dither: DitherSettings {
+                mode: config.dither,
+                ..DitherSettings::default()
+            },
+            fit: FitSettings {
+                mode: config.fit,
+                ..FitSettings::default()
+            },
+            ..Settings::default()
+        },
+        watch_folder: config.watch_folder.clone(),
+        animation_interval: config.animation_interval(),
+        review: config.review,
+        watch_clipboard: config.watch_clipboard,
+        fetch_urls: config.fetch_urls,
+        poll_interval: config.poll_interval(),
+        hotkey: config.hotkey,
+        setup_done: config.onboarding_done,
+        ..AppState::default()
+    }));
     let ui_state = Arc::clone(&state);
-    let palette_image =
-        image::load_from_memory(EMBEDDED_PALETTE).expect("Invalid embedded palette");
-    let palette = sample_palette(&palette_image);
+
+    let (config_state, press_state, error_state) =
+        (Arc::clone(&state), Arc::clone(&state), Arc::clone(&state));
+    hotkey::spawn(
+        move || config_state.lock().unwrap().hotkey,
+        move |hotkey| {
+            let capture = capture::foreground(hotkey.target);
+            let mut state = press_state.lock().unwrap();
+            match capture {
+                Ok(capture) => {
+                    state.captured = Some(source::Incoming::new(
+                        capture.image,
+                        format!("{} capture", hotkey.label()),
+                    ))
+                }
+                Err(error) => state.source_error = Some(error),
+            }
+        },
+        move |error| error_state.lock().unwrap().hotkey_error = Some(error),
+    );
+    let palette = palettes::restore(config.palette.as_ref());
+    let ui_palette = palette.clone();
 
     // Spawn clipboard watcher thread
     thread::spawn(move || {
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let (key, _) = hkcu
-            .create_subkey(REGISTRY_PATH)
-            .expect("Failed to open registry key");
+        // Opened on the first poll, and retried on later ones until it is.
+        let mut key: Option<RegKey> = None;
+        let report = |result: Result<(), String>| {
+            state.lock().unwrap().registry_error = result.err();
+        };
 
-        let mut clipboard = Clipboard::new().unwrap();
+        // Opened on the first poll that watches it, and retried until it is.
+        let mut clipboard: Option<Clipboard> = None;
         let mut last_hash: u64 = 0;
+        let mut last_settings = Settings::default();
+        let mut last_poll: Option<Instant> = None;
+        let mut pipeline = Pipeline::new(palette);
+        // Modification time of the palette file when it was last sampled.
+        let mut palette_modified = None;
+        let mut folder_watch: Option<source::FolderWatch> = None;
+        // The folder last tried for `folder_watch`, and when.
+        let mut watch_attempt: Option<(PathBuf, Instant)> = None;
+        let mut animation: Option<source::Animation> = None;
+        let mut frame_index = 0;
+        // Registry CSV of the picked frame, and of every frame for the
+        // current settings while animating.
+        let mut still_flag: Option<String> = None;
+        // Registry CSV of the flag last written outside an animation, which
+        // may be older than `still_flag` while reviewing.
+        let mut applied_flag: Option<String> = None;
+        let mut frame_flags: Vec<String> = Vec::new();
+        let mut animating = false;
+        // The source was just decoded from the game's own saved flag.
+        let mut loaded_from_game = false;
+        let mut next_frame = 0;
+        let mut frame_written: Option<Instant> = None;
 
         loop {
-            if let Ok(image) = clipboard.get_image() {
-                let current_hash = calculate_image_hash(&image.bytes);
-                if current_hash != last_hash {
-                    last_hash = current_hash;
-
-                    let raw = RgbaImage::from_raw(
-                        image.width as u32,
-                        image.height as u32,
-                        image.bytes.to_vec(),
-                    )
-                    .expect("Invalid clipboard image");
-
-                    let resized: DynamicImage = DynamicImage::ImageRgba8(raw).resize_exact(
-                        IMAGE_WIDTH,
-                        IMAGE_HEIGHT,
-                        FilterType::Nearest,
-                    );
-
-                    let csv = encode_uv_csv(&resized, &palette);
-                    let reg_value = RegValue {
-                        vtype: RegType::REG_BINARY,
-                        bytes: csv.into_bytes(),
-                    };
+            let (setup_done, quit) = {
+                let state = state.lock().unwrap();
+                (state.setup_done, state.quit_requested)
+            };
+            if !setup_done {
+                if quit {
+                    break;
+                }
+                thread::sleep(WORKER_TICK);
+                continue;
+            }
 
-                    key.set_raw_value(REGISTRY_VALUE_NAME, &reg_value)
-                        .expect("Failed to write to registry");
+            let (mut settings, pin_requested, new_palette, pattern_requested, measure_requested) = {
+                let mut state = state.lock().unwrap();
+                (
+                    state.settings.clone(),
+                    std::mem::take(&mut state.pin_background_requested),
+                    state.palette_request.take(),
+                    std::mem::take(&mut state.calibration_pattern_requested),
+                    std::mem::take(&mut state.calibration_measure_requested),
+                )
+            };
+            let (review, apply_requested, watch_clipboard, fetch_urls, poll_interval) = {
+                let mut state = state.lock().unwrap();
+                (
+                    state.review,
+                    std::mem::take(&mut state.apply_requested),
+                    state.watch_clipboard,
+                    state.fetch_urls,
+                    state.poll_interval,
+                )
+            };
+            let mut changed = settings != last_settings;
+            if let Some(palette) = new_palette {
+                pipeline.set_palette(palette);
+                palette_modified = None;
+                changed = true;
+            }
+            let mut new_image = false;
+            let mut incoming = None;
+            let reoriented = settings.orientation != last_settings.orientation;
+            last_settings = settings.clone();
+
+            if last_poll.is_none_or(|t| t.elapsed() >= poll_interval) {
+                last_poll = Some(Instant::now());
 
+                if key.is_none() {
+                    let opened = hkcu.create_subkey(REGISTRY_PATH);
                     let mut state = state.lock().unwrap();
+                    match opened {
+                        Ok((opened, _)) => {
+                            key = Some(opened);
+                            state.registry_error = None;
+                        }
+                        Err(error) => {
+                            state.registry_error =
+                                Some(format!("Could not open the game's registry key: {error}"))
+                        }
+                    }
+                }
+
+                // Re-sample the palette file whenever it is saved again.
+                let palette = pipeline.palette();
+                if let Some(path) = palette.source.clone() {
+                    let modified = std::fs::metadata(&path)
+                        .and_then(|meta| meta.modified())
+                        .ok();
+                    if palette_modified.is_some() && modified != palette_modified {
+                        let reload = Palette::load(&path, palette.cols, palette.rows);
+                        if let Ok(palette) = &reload {
+                            pipeline.set_palette(palette.clone());
+                            changed = true;
+                        }
+                        state.lock().unwrap().palette_reload = Some(reload);
+                    }
+                    palette_modified = modified;
+                }
 
-                    let now = std::time::SystemTime::now();
-                    let now_local: DateTime<Local> = now.into();
-                    state.last_update = Some(now_local.format("%Y-%m-%d %H:%M:%S").to_string());
+                if watch_clipboard && clipboard.is_none() {
+                    match Clipboard::new() {
+                        Ok(opened) => clipboard = Some(opened),
+                        Err(error) => {
+                            state.lock().unwrap().source_error =
+                                Some(format!("Could not open the clipboard: {error}"))
+                        }
+                    }
+                }
+                if watch_clipboard && let Some(clipboard) = &mut clipboard {
+                    // Bitmaps arboard cannot read are decoded from the DIB.
+                    let image = clipboard
+                        .get_image()
+                        .ok()
+                        .and_then(|image| {
+                            RgbaImage::from_raw(
+                                image.width as u32,
+                                image.height as u32,
+                                image.bytes.into_owned(),
+                            )
+                        })
+                        .or_else(dib::clipboard_image);
+                    if let Some(image) = image {
+                        let current_hash = calculate_image_hash(image.as_raw());
+                        if current_hash != last_hash {
+                            last_hash = current_hash;
+
+                            incoming = Some(source::Incoming {
+                                icc: icc::clipboard_profile(),
+                                ..source::Incoming::new(image, "Clipboard")
+                            });
+                        }
+                    } else if let Some(reference) = source::clipboard_reference(clipboard) {
+                        let current_hash = calculate_image_hash(reference.key());
+                        if current_hash != last_hash {
+                            last_hash = current_hash;
+                            match reference {
+                                // Downloads may take a while; the result
+                                // comes back as a capture.
+                                source::Reference::Url(url) => {
+                                    if fetch_urls {
+                                        let state = Arc::clone(&state);
+                                        thread::spawn(move || {
+                                            let fetched = source::fetch(&url);
+                                            let mut state = state.lock().unwrap();
+                                            match fetched {
+                                                Ok(image) => state.captured = Some(image),
+                                                Err(error) => state.source_error = Some(error),
+                                            }
+                                        });
+                                    }
+                                }
+                                reference => match reference.load(pipeline.palette()) {
+                                    Ok(file) => incoming = Some(file),
+                                    Err(error) => state.lock().unwrap().source_error = Some(error),
+                                },
+                            }
+                        }
+                    }
                 }
             }
 
+            let (open_request, watch_folder, captured, frame_request, load_flag) = {
+                let mut state = state.lock().unwrap();
+                (
+                    state.open_request.take(),
+                    state.watch_folder.clone(),
+                    state.captured.take(),
+                    state.frame_request.take(),
+                    std::mem::take(&mut state.load_flag_requested),
+                )
+            };
+            if captured.is_some() {
+                incoming = captured;
+            }
+            if load_flag {
+                let palette = pipeline.palette();
+                match saved_flag(key.as_ref(), palette) {
+                    Ok(indices) => {
+                        incoming = Some(source::Incoming::decoded(
+                            indices,
+                            &palette.colors,
+                            "In-game flag",
+                        ));
+                        loaded_from_game = true;
+                    }
+                    Err(error) => state.lock().unwrap().source_error = Some(error),
+                }
+            }
+            if folder_watch.as_ref().map(|watch| &watch.dir) != watch_folder.as_ref() {
+                folder_watch = None;
+                // A folder that cannot be watched yet, like a synced drive
+                // still mounting, stays configured and is tried again.
+                if let Some(dir) = &watch_folder
+                    && watch_attempt.as_ref().is_none_or(|(tried, at)| {
+                        tried != dir || at.elapsed() >= WATCH_RETRY_INTERVAL
+                    })
+                {
+                    watch_attempt = Some((dir.clone(), Instant::now()));
+                    let watch = source::FolderWatch::new(dir);
+                    let mut state = state.lock().unwrap();
+                    match watch {
+                        Ok(watch) => {
+                            folder_watch = Some(watch);
+                            state.watch_error = None;
+                        }
+                        Err(error) => state.watch_error = Some(error),
+                    }
+                }
+            }
+            let watched = folder_watch.as_mut().and_then(source::FolderWatch::poll);
+            if let Some(path) = open_request.or(watched) {
+                match source::open(&path) {
+                    Ok(file) => {
+                        incoming = Some(file);
+                        loaded_from_game = false;
+                    }
+                    Err(error) => state.lock().unwrap().source_error = Some(error),
+                }
+            }
+
+            let mut new_frame = false;
+            if let Some(mut incoming) = incoming {
+                // Wide-gamut screenshots carry their display's profile;
+                // matching works in sRGB.
+                let profile = icc::to_srgb(&mut incoming.image, incoming.icc.as_deref());
+                animation = incoming.animation;
+                let mut state = state.lock().unwrap();
+                if let Some(animation) = &mut animation {
+                    for frame in &mut animation.frames {
+                        icc::to_srgb(frame, incoming.icc.as_deref());
+                    }
+                    state.frame_count = animation.frames.len();
+                    state.sharpest_frame = animation.sharpest;
+                } else {
+                    state.frame_count = 0;
+                    state.sharpest_frame = 0;
+                }
+                state.frame_index = state.sharpest_frame;
+                frame_index = state.sharpest_frame;
+                state.source_profile = profile;
+                state.source_name = Some(incoming.name);
+                state.source_error = None;
+
+                pipeline.set_source(incoming.image);
+                if let Some(indices) = incoming.indices {
+                    pipeline.set_flag(indices);
+                }
+                new_image = true;
+                changed = true;
+            } else if let Some(frame) = frame_request
+                && let Some(image) = animation.as_ref().and_then(|a| a.frames.get(frame))
+            {
+                pipeline.set_source(image.clone());
+                frame_index = frame;
+                new_frame = true;
+            }
+
+            if pin_requested && let Some(size) = pipeline.pin_background(settings.orientation) {
+                state.lock().unwrap().pinned_background = Some(size);
+                changed = true;
+            }
+
+            if pattern_requested {
+                let pattern = pipeline.test_pattern();
+                let status = match write_flag(key.as_ref(), &pattern) {
+                    Ok(()) => {
+                        applied_flag = Some(pattern);
+                        "Test flag written; it is replaced by the next copied image.".to_owned()
+                    }
+                    Err(error) => error,
+                };
+                state.lock().unwrap().calibration_status = Some(status);
+            }
+
+            if measure_requested {
+                let measurement = pipeline.measure_calibration(&settings);
+                let mut state = state.lock().unwrap();
+                state.calibration_status = Some(match measurement {
+                    None => "Copy a screenshot of the test flag first.".to_owned(),
+                    Some(Err(error)) => error,
+                    Some(Ok(measurement)) => {
+                        state.settings.calibration = Calibration {
+                            enabled: true,
+                            curve: Some(measurement.curve),
+                        };
+                        settings = state.settings.clone();
+                        last_settings = settings.clone();
+                        changed = true;
+                        format!(
+                            "Mean ΔE to the screenshot: {:.1} uncorrected, {:.1} calibrated",
+                            measurement.before, measurement.after
+                        )
+                    }
+                });
+            }
+
+            if (new_image || new_frame || reoriented)
+                && let Some(source) = pipeline.oriented_source(settings.orientation)
+            {
+                let preview = SourcePreview::new(source);
+                let mut state = state.lock().unwrap();
+
+                // A crop drawn on a differently shaped image rarely fits, so
+                // start again from the flag aspect.
+                let reshaped = state
+                    .source_preview
+                    .as_ref()
+                    .is_none_or(|old| old.size != preview.size);
+                if (new_image || reshaped) && state.settings.crop.is_some() {
+                    let [w, h] = preview.size;
+                    state.settings.crop = Some(CropRect::flag_aspect(w, h));
+                    settings = state.settings.clone();
+                    last_settings = settings.clone();
+                }
+                // Hand edits were made for the previous image.
+                if new_image && !state.settings.pixel_edits.is_empty() {
+                    state.settings.pixel_edits.clear();
+                    settings = state.settings.clone();
+                    last_settings = settings.clone();
+                }
+
+                state.source_preview = Some(preview);
+                state.source_version += 1;
+            }
+
+            // Settings changes re-encode the last image so tweaks apply live.
+            if (changed || new_frame)
+                && let Some(encoded) = pipeline.run(&settings)
             {
+                // The game already holds a flag just read from it; the
+                // first edit writes it back.
+                let unchanged = std::mem::take(&mut loaded_from_game);
+                // While animating, the cycle owns the registry value.
+                let mut written = false;
+                if unchanged {
+                    applied_flag = Some(encoded.csv.clone());
+                } else if !animating && !review {
+                    let result = write_flag(key.as_ref(), &encoded.csv);
+                    if result.is_ok() {
+                        applied_flag = Some(encoded.csv.clone());
+                        written = true;
+                    }
+                    report(result);
+                }
+                still_flag = Some(encoded.csv);
+
+                let mut state = state.lock().unwrap();
+                if !animating {
+                    state.staged = review && !unchanged;
+                }
+                let saved = saved_flag(key.as_ref(), pipeline.palette()).ok();
+                let resized = render_rgba(&encoded.prepared);
+                let weighted = render_indices(&encoded.indices, &pipeline.palette().colors);
+                state.preview = Some(Preview {
+                    histograms: [Histogram::new(&resized), Histogram::new(&weighted)],
+                    resized,
+                    unsharpened: encoded.unsharpened.as_ref().map(render_rgba),
+                    weighted,
+                    unweighted: render_indices(&encoded.unweighted, &pipeline.palette().colors),
+                    in_game: settings.calibration.curve.map(|curve| {
+                        let colors: Vec<[u8; 3]> = pipeline
+                            .palette()
+                            .colors
+                            .iter()
+                            .map(|&c| curve.apply(c))
+                            .collect();
+                        render_indices(&encoded.indices, &colors)
+                    }),
+                    heatmap: render_heatmap(&encoded.errors),
+                    indices: encoded.indices.clone(),
+                    diff: saved.as_ref().map(|saved| {
+                        render_diff(saved, &encoded.indices, &pipeline.palette().colors)
+                    }),
+                    changed: saved.as_ref().map_or(0, |saved| {
+                        saved
+                            .iter()
+                            .zip(&encoded.indices)
+                            .filter(|(a, b)| a != b)
+                            .count()
+                    }),
+                });
+                state.preview_version += 1;
+                state.last_encode_time = Some(encoded.encode_time);
+                state.last_exact_match = encoded.exact;
+                state.last_quality = Some(QualityReport::from_errors(&encoded.errors));
+                state.cell_usage = vec![0; pipeline.palette().colors.len()];
+                for &idx in &encoded.indices {
+                    state.cell_usage[idx] += 1;
+                }
+
+                if written {
+                    state.last_update = Some(timestamp());
+                }
+            }
+
+            if apply_requested
+                && !animating
+                && let Some(csv) = &still_flag
+            {
+                let written = write_flag(key.as_ref(), csv);
+                let mut state = state.lock().unwrap();
+                match written {
+                    Ok(()) => {
+                        applied_flag = Some(csv.clone());
+                        state.staged = false;
+                        state.last_update = Some(timestamp());
+                        // The registry now holds the previewed flag.
+                        if let Some(preview) = &mut state.preview {
+                            let colors = &pipeline.palette().colors;
+                            preview.diff =
+                                Some(render_diff(&preview.indices, &preview.indices, colors));
+                            preview.changed = 0;
+                            state.preview_version += 1;
+                        }
+                    }
+                    Err(error) => state.registry_error = Some(error),
+                }
+            }
+
+            // Any change but a frame pick re-encodes every frame.
+            if changed {
+                frame_flags.clear();
+            }
+            let (animate, interval) = {
                 let state = state.lock().unwrap();
-                if state.quit_requested {
-                    break;
+                (state.animate, state.animation_interval)
+            };
+            let cycle = animation.as_ref().filter(|_| animate);
+            if let Some(animation) = cycle {
+                // Remember what the game showed before the first frame, in
+                // case nothing was written this session.
+                if !animating && applied_flag.is_none() {
+                    applied_flag = saved_csv(key.as_ref()).ok();
+                }
+                if frame_flags.is_empty() {
+                    for frame in &animation.frames {
+                        pipeline.set_source(frame.clone());
+                        frame_flags.extend(pipeline.run(&settings).map(|encoded| encoded.csv));
+                    }
+                    pipeline.set_source(animation.frames[frame_index].clone());
+                }
+                if !frame_flags.is_empty() && frame_written.is_none_or(|t| t.elapsed() >= interval)
+                {
+                    report(write_flag(
+                        key.as_ref(),
+                        &frame_flags[next_frame % frame_flags.len()],
+                    ));
+                    next_frame = (next_frame + 1) % frame_flags.len();
+                    frame_written = Some(Instant::now());
                 }
+                animating = true;
             }
 
-            thread::sleep(Duration::from_secs(1));
+            let quit = state.lock().unwrap().quit_requested;
+            if animating && (cycle.is_none() || quit) {
+                // Leave the game with the flag it would hold without the
+                // animation, not whichever frame the cycle stopped on: the
+                // picked frame, or while reviewing the last applied flag.
+                if !review {
+                    applied_flag = still_flag.clone().or(applied_flag);
+                }
+                if let Some(csv) = &applied_flag {
+                    report(write_flag(key.as_ref(), csv));
+                }
+                animating = false;
+                next_frame = 0;
+                frame_written = None;
+            }
+            state.lock().unwrap().animating = animating;
+            if quit {
+                break;
+            }
+
+            thread::sleep(WORKER_TICK);
         }
     });
 
     let native_options = eframe::NativeOptions {
         viewport: egui::viewport::ViewportBuilder::default()
-            .with_inner_size([400.0, 160.0])
+            .with_inner_size([440.0, 480.0])
             .with_title("MageFlag Clipboard Watcher"),
         ..Default::default()
     };
@@ -136,12 +2579,83 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "MageFlag Clipboard Watcher",
         native_options,
-        Box::new(|_cc: &CreationContext| Box::new(MageFlagApp { state: ui_state })),
+        Box::new(|_cc: &CreationContext| {
+            Box::new(MageFlagApp {
+                state: ui_state,
+                palette_grid: [ui_palette.cols, ui_palette.rows],
+                palette: ui_palette,
+                palette_error: None,
+                palette_profiles: palettes::profiles(),
+                palette_draft: None,
+                palette_draft_name: String::new(),
+                preview_textures: None,
+                preview_version: 0,
+                source_texture: None,
+                subject_crop: None,
+                source_version: 0,
+                crop_drag: None,
+                crop_lock_aspect: true,
+                show_heatmap: true,
+                compare_sharpen: false,
+                show_in_game: false,
+                flag_view: flag_view::FlagView::default(),
+                mockup: None,
+                mockup_error: None,
+                onboarding: (!config.onboarding_done).then(onboarding::Wizard::new),
+                saved_config: config.clone(),
+                config,
+                config_open: false,
+                config_error: None,
+                text_scale: config::windows_text_scale(),
+                applied_zoom: 1.0,
+                capture_at: None,
+                region_picker: None,
+                webcam: webcam::Panel::default(),
+                capture_windows: Vec::new(),
+                gallery: None,
+                clipboard_history: None,
+                lab_benchmark: None,
+            })
+        }),
     )
 }
 
 // === SUPPORT ===
 
+/// Stores `csv` as the game's flag; `key` is `None` while the game's
+/// registry key cannot be opened.
+fn write_flag(key: Option<&RegKey>, csv: &str) -> Result<(), String> {
+    let key = key.ok_or("The game's registry key is not open")?;
+    let reg_value = RegValue {
+        vtype: RegType::REG_BINARY,
+        bytes: csv.as_bytes().to_vec(),
+    };
+    key.set_raw_value(REGISTRY_VALUE_NAME, &reg_value)
+        .map_err(|e| format!("Could not write the flag to the registry: {e}"))
+}
+
+/// The flag the game has saved, as registry CSV.
+fn saved_csv(key: Option<&RegKey>) -> Result<String, String> {
+    let value = key
+        .ok_or("The game's registry key is not open")?
+        .get_raw_value(REGISTRY_VALUE_NAME)
+        .map_err(|e| format!("No saved flag: {e}"))?;
+    // Unity writes PlayerPrefs strings with a trailing NUL.
+    let text = String::from_utf8_lossy(&value.bytes);
+    Ok(text.trim_end_matches('\0').to_owned())
+}
+
+/// Palette indices of the flag the game has saved.
+fn saved_flag(key: Option<&RegKey>, palette: &Palette) -> Result<Vec<usize>, String> {
+    uv::decode(&saved_csv(key)?, palette)
+}
+
+/// The local time, as shown for the last flag written.
+fn timestamp() -> String {
+    let now: DateTime<Local> = std::time::SystemTime::now().into();
+    now.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
 fn calculate_image_hash(data: &[u8]) -> u64 {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
@@ -151,82 +2665,185 @@ fn calculate_image_hash(data: &[u8]) -> u64 {
     hasher.finish()
 }
 
-fn sample_palette(img: &DynamicImage) -> Vec<[u8; 3]> {
-    let (w, h) = img.dimensions();
-    let cell_w = w as f32 / PALETTE_COLS as f32;
-    let cell_h = h as f32 / PALETTE_ROWS as f32;
-
-    let mut colors = Vec::with_capacity((PALETTE_COLS * PALETTE_ROWS) as usize);
-
-    for row in 0..PALETTE_ROWS {
-        for col in 0..PALETTE_COLS {
-            let cx = ((col as f32 + 0.5) * cell_w).round() as u32;
-            let cy = ((row as f32 + 0.5) * cell_h).round() as u32;
-            let pixel = average_patch(img, cx.min(w - 1), cy.min(h - 1));
-            colors.push(pixel);
-        }
-    }
-
-    colors
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
 }
 
-fn average_patch(img: &DynamicImage, cx: u32, cy: u32) -> [u8; 3] {
-    let mut r = 0u32;
-    let mut g = 0u32;
-    let mut b = 0u32;
-    let mut count = 0u32;
+/// Every palette cell with its hex, Lab, grid position and emitted UV.
+fn palette_inspector(ui: &mut egui::Ui, palette: &Palette) {
+    const HEADER: [&str; 5] = ["Index", "Row, col", "Hex", "L* a* b*", "UV"];
 
-    for dx in -1..=1 {
-        for dy in -1..=1 {
-            let x = (cx as i32 + dx).clamp(0, img.width() as i32 - 1) as u32;
-            let y = (cy as i32 + dy).clamp(0, img.height() as i32 - 1) as u32;
-            let pixel = img.get_pixel(x, y).to_rgb();
-            r += pixel[0] as u32;
-            g += pixel[1] as u32;
-            b += pixel[2] as u32;
-            count += 1;
+    let rows: Vec<[String; 5]> = palette
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(idx, &[r, g, b])| {
+            let lab = color::to_lab([r, g, b]);
+            [
+                idx.to_string(),
+                format!(
+                    "{}, {}",
+                    idx as u32 / palette.cols + 1,
+                    idx as u32 % palette.cols + 1
+                ),
+                format!("#{r:02X}{g:02X}{b:02X}"),
+                format!("{:.1} {:.1} {:.1}", lab.l, lab.a, lab.b),
+                palette.uv_string(idx),
+            ]
+        })
+        .collect();
+
+    if ui.button("Copy as table").clicked() {
+        let mut table = HEADER.join("\t");
+        for row in &rows {
+            table.push('\n');
+            table.push_str(&row.join("\t"));
         }
+        ui.output_mut(|o| o.copied_text = table);
     }
 
-    [(r / count) as u8, (g / count) as u8, (b / count) as u8]
+    egui::ScrollArea::vertical()
+        .id_source("palette_inspector")
+        .max_height(300.0)
+        .show(ui, |ui| {
+            egui::Grid::new("palette_inspector_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    for heading in HEADER {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+
+                    for (&[r, g, b], row) in palette.colors.iter().zip(&rows) {
+                        let (rect, _) =
+                            ui.allocate_exact_size(egui::vec2(20.0, 14.0), egui::Sense::hover());
+                        ui.painter()
+                            .rect_filled(rect, 2.0, Color32::from_rgb(r, g, b));
+                        for cell in row {
+                            ui.monospace(cell);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
 }
 
-fn encode_uv_csv(img: &DynamicImage, palette: &[[u8; 3]]) -> String {
-    let mut result = Vec::with_capacity((IMAGE_WIDTH * IMAGE_HEIGHT) as usize);
+/// The used palette cells, most used first, with their share of the flag.
+fn cell_usage_chart(ui: &mut egui::Ui, palette: &Palette, usage: &[usize]) {
+    let total: usize = usage.iter().sum();
+    if total == 0 || usage.len() != palette.colors.len() {
+        ui.label("Nothing encoded yet.");
+        return;
+    }
 
-    for x in 0..IMAGE_WIDTH {
-        for y in (0..IMAGE_HEIGHT).rev() {
-            let pixel = img.get_pixel(x, y);
-            let rgb = [pixel[0], pixel[1], pixel[2]];
+    let mut used: Vec<(usize, usize)> = usage
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    used.sort_by_key(|&(idx, count)| (std::cmp::Reverse(count), idx));
+    let top3: usize = used.iter().take(3).map(|&(_, count)| count).sum();
+    ui.label(format!(
+        "{} of {} cells used; the top 3 cover {:.0}% of the flag.",
+        used.len(),
+        usage.len(),
+        top3 as f32 * 100.0 / total as f32
+    ));
 
-            let (idx, _) = palette
-                .iter()
-                .enumerate()
-                .map(|(i, color)| (i, lab_distance(rgb, *color)))
-                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-                .unwrap();
+    egui::Grid::new("cell_usage").show(ui, |ui| {
+        for (idx, count) in used {
+            let [r, g, b] = palette.colors[idx];
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(20.0, 14.0), egui::Sense::hover());
+            ui.painter()
+                .rect_filled(rect, 2.0, Color32::from_rgb(r, g, b));
+            ui.monospace(format!(
+                "{}, {}",
+                idx as u32 / palette.cols + 1,
+                idx as u32 % palette.cols + 1
+            ));
+            let share = count as f32 / total as f32;
+            ui.add(
+                egui::ProgressBar::new(share)
+                    .desired_width(160.0)
+                    .text(format!("{count} px ({:.1}%)", share * 100.0)),
+            );
+            ui.end_row();
+        }
+    });
+}
 
-            let raw_row = idx as u32 / PALETTE_COLS;
-            let row = PALETTE_ROWS - 1 - raw_row;
-            let col = idx as u32 % PALETTE_COLS;
+/// Row/column pickers (1-based, as in the palette grid) for a palette index,
+/// followed by a swatch of the chosen color.
+fn palette_cell_picker(ui: &mut egui::Ui, palette: &Palette, idx: &mut usize) {
+    let mut row = *idx as u32 / palette.cols + 1;
+    let mut col = *idx as u32 % palette.cols + 1;
+    ui.add(
+        egui::DragValue::new(&mut row)
+            .clamp_range(1..=palette.rows)
+            .prefix("row "),
+    );
+    ui.add(
+        egui::DragValue::new(&mut col)
+            .clamp_range(1..=palette.cols)
+            .prefix("col "),
+    );
+    *idx = (((row - 1) * palette.cols + col - 1) as usize).min(palette.colors.len() - 1);
 
-            let u = (col as f32 + 0.5) / PALETTE_COLS as f32;
-            let v = (row as f32 + 0.5) / PALETTE_ROWS as f32;
+    let [r, g, b] = palette.colors[*idx];
+    let (swatch, _) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+    ui.painter()
+        .rect_filled(swatch, 2.0, Color32::from_rgb(r, g, b));
+}
 
-            result.push(format!("{u:.2}:{v:.2}"));
-        }
+/// Black or white, whichever reads better on `fill`.
+fn contrast_text(fill: Color32) -> Color32 {
+    let luma = 0.299 * fill.r() as f32 + 0.587 * fill.g() as f32 + 0.114 * fill.b() as f32;
+    if luma > 140.0 {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
     }
+}
 
-    result.join(",")
+fn render_rgba(img: &DynamicImage) -> ColorImage {
+    let rgba = img.to_rgba8();
+    ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], &rgba)
 }
 
-fn lab_distance(a: [u8; 3], b: [u8; 3]) -> f32 {
-    let lab_a: Lab = Lab::from_color(Srgb::new(a[0], a[1], a[2]).into_format());
-    let lab_b: Lab = Lab::from_color(Srgb::new(b[0], b[1], b[2]).into_format());
+fn render_indices(indices: &[usize], palette: &[[u8; 3]]) -> ColorImage {
+    let rgb: Vec<u8> = indices.iter().flat_map(|&idx| palette[idx]).collect();
+    ColorImage::from_rgb([IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize], &rgb)
+}
 
-    let dl = lab_a.l - lab_b.l;
-    let da = lab_a.a - lab_b.a;
-    let db = lab_a.b - lab_b.b;
+/// The flag with pixels unchanged from `saved` faded to gray, so the
+/// changed ones stand out.
+fn render_diff(saved: &[usize], indices: &[usize], palette: &[[u8; 3]]) -> ColorImage {
+    let rgb: Vec<u8> = indices
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &idx)| {
+            let [r, g, b] = palette[idx];
+            if saved.get(i) != Some(&idx) {
+                return [r, g, b];
+            }
+            let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8;
+            [luma / 4 + 96; 3]
+        })
+        .collect();
+    ColorImage::from_rgb([IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize], &rgb)
+}
 
-    (dl * dl + da * da + db * db).sqrt()
+/// Yellow fading in at small errors through to opaque red at
+/// `HEATMAP_MAX_DELTA_E`.
+fn render_heatmap(errors: &[f32]) -> ColorImage {
+    let rgba: Vec<u8> = errors
+        .iter()
+        .flat_map(|&error| {
+            let t = (error / HEATMAP_MAX_DELTA_E).clamp(0.0, 1.0);
+            [255, (255.0 * (1.0 - t)) as u8, 0, (230.0 * t) as u8]
+        })
+        .collect();
+    ColorImage::from_rgba_unmultiplied([IMAGE_WIDTH as usize, IMAGE_HEIGHT as usize], &rgba)
 }
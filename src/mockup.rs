@@ -0,0 +1,150 @@
+//! The flag hung on a pole in a bundled scene, in perspective and waving,
+//! for sharing what it roughly looks like in game.
+
+use std::f32::consts::TAU;
+use std::path::Path;
+
+use eframe::egui::ColorImage;
+use image::RgbaImage;
+
+const EMBEDDED_TEMPLATE: &[u8] = include_bytes!("mockup.png");
+// Where the flag's top-left, top-right, bottom-right and bottom-left corners
+// land in the template; the far edge is shorter for perspective.
+const FLAG_CORNERS: [[f32; 2]; 4] = [[151.0, 50.0], [470.0, 72.0], [470.0, 222.0], [151.0, 250.0]];
+// Ripples along the flag, their height as a fraction of the flag's, and how
+// much the folds darken.
+const WAVES: f32 = 1.75;
+const AMPLITUDE: f32 = 0.06;
+const SHADE: f32 = 0.4;
+// Subsamples per side of an output pixel, smoothing the flag's edges.
+const SUPERSAMPLE: u32 = 3;
+
+/// Maps the unit square onto a quadrilateral.
+struct Homography([[f32; 3]; 3]);
+
+impl Homography {
+    /// Square-to-quad mapping after Heckbert's "Fundamentals of Texture
+    /// Mapping"; `corners` run clockwise from (0, 0).
+    fn square_to_quad(corners: [[f32; 2]; 4]) -> Self {
+        let [[x0, y0], [x1, y1], [x2, y2], [x3, y3]] = corners;
+        let (sx, sy) = (x0 - x1 + x2 - x3, y0 - y1 + y2 - y3);
+        let (dx1, dx2, dy1, dy2) = (x1 - x2, x3 - x2, y1 - y2, y3 - y2);
+        let den = dx1 * dy2 - dx2 * dy1;
+        let g = (sx * dy2 - dx2 * sy) / den;
+        let h = (dx1 * sy - sx * dy1) / den;
+        Self([
+            [x1 - x0 + g * x1, x3 - x0 + h * x3, x0],
+            [y1 - y0 + g * y1, y3 - y0 + h * y3, y0],
+            [g, h, 1.0],
+        ])
+    }
+
+    fn inverse(&self) -> Self {
+        let [[a, b, c], [d, e, f], [g, h, i]] = self.0;
+        let det = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+        Self([
+            [
+                (e * i - f * h) / det,
+                (c * h - b * i) / det,
+                (b * f - c * e) / det,
+            ],
+            [
+                (f * g - d * i) / det,
+                (a * i - c * g) / det,
+                (c * d - a * f) / det,
+            ],
+            [
+                (d * h - e * g) / det,
+                (b * g - a * h) / det,
+                (a * e - b * d) / det,
+            ],
+        ])
+    }
+
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let [[a, b, c], [d, e, f], [g, h, i]] = self.0;
+        let w = g * x + h * y + i;
+        ((a * x + b * y + c) / w, (d * x + e * y + f) / w)
+    }
+}
+
+/// The template with `flag` drawn onto it, bilinearly filtered as in game.
+pub fn render(flag: &ColorImage) -> RgbaImage {
+    let mut scene = image::load_from_memory(EMBEDDED_TEMPLATE)
+        .expect("Invalid embedded mockup template")
+        .to_rgba8();
+    let to_flag = Homography::square_to_quad(FLAG_CORNERS).inverse();
+    // Padding for the ripples, which leave the flat quad.
+    let pad = AMPLITUDE * (FLAG_CORNERS[3][1] - FLAG_CORNERS[0][1]);
+    let (left, right) = (FLAG_CORNERS[0][0], FLAG_CORNERS[1][0]);
+    let (top, bottom) = (FLAG_CORNERS[0][1] - pad, FLAG_CORNERS[3][1] + pad);
+
+    let samples = (SUPERSAMPLE * SUPERSAMPLE) as f32;
+    for y in top.max(0.0) as u32..(bottom.ceil() as u32).min(scene.height()) {
+        for x in left.max(0.0) as u32..(right.ceil() as u32).min(scene.width()) {
+            let mut sum = [0.0f32; 3];
+            let mut coverage = 0.0;
+            for sy in 0..SUPERSAMPLE {
+                for sx in 0..SUPERSAMPLE {
+                    let px = x as f32 + (sx as f32 + 0.5) / SUPERSAMPLE as f32;
+                    let py = y as f32 + (sy as f32 + 0.5) / SUPERSAMPLE as f32;
+                    let (u, v) = to_flag.apply(px, py);
+                    // Folds swing wider away from the pole.
+                    let phase = (u * WAVES - v * 0.2) * TAU;
+                    let v = v - AMPLITUDE * u * phase.sin();
+                    if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                        continue;
+                    }
+                    let shade = 1.0 - SHADE * u * (0.5 + 0.5 * phase.cos());
+                    let color = sample(flag, u, v);
+                    for (total, channel) in sum.iter_mut().zip(color) {
+                        *total += channel * shade;
+                    }
+                    coverage += 1.0;
+                }
+            }
+            if coverage == 0.0 {
+                continue;
+            }
+            let alpha = coverage / samples;
+            let pixel = scene.get_pixel_mut(x, y);
+            for (channel, total) in pixel.0.iter_mut().zip(sum) {
+                let flag = total / coverage;
+                *channel = (flag * alpha + *channel as f32 * (1.0 - alpha)).round() as u8;
+            }
+        }
+    }
+    scene
+}
+
+/// Bilinear sample of `image` at texture coordinate (`u`, `v`).
+fn sample(image: &ColorImage, u: f32, v: f32) -> [f32; 3] {
+    let [w, h] = image.size;
+    let x = (u * w as f32 - 0.5).clamp(0.0, (w - 1) as f32);
+    let y = (v * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x.fract(), y.fract());
+    let at = |x: usize, y: usize| image.pixels[y * w + x].to_array().map(f32::from);
+    let mut color = [0.0; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        let top = at(x0, y0)[i] * (1.0 - fx) + at(x1, y0)[i] * fx;
+        let bottom = at(x0, y1)[i] * (1.0 - fx) + at(x1, y1)[i] * fx;
+        *channel = top * (1.0 - fy) + bottom * fy;
+    }
+    color
+}
+
+pub fn save(mockup: &RgbaImage, path: &Path) -> Result<(), String> {
+    mockup
+        .save(path)
+        .map_err(|e| format!("Could not save {}: {e}", path.display()))
+}
+
+/// `mockup` for display.
+pub fn to_color_image(mockup: &RgbaImage) -> ColorImage {
+    ColorImage::from_rgba_unmultiplied(
+        [mockup.width() as usize, mockup.height() as usize],
+        mockup.as_raw(),
+    )
+}
@@ -0,0 +1,210 @@
+//! The first-run walkthrough: finding the game, checking its registry key,
+//! proving a flag can be written and picking a few preferences.
+
+use std::path::PathBuf;
+
+use eframe::egui;
+use winreg::RegKey;
+use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE, RegType};
+
+use crate::hotkey::Hotkey;
+use crate::palettes::Palette;
+use crate::{
+    REGISTRY_PATH, REGISTRY_VALUE_NAME, SETTINGS_REGISTRY_PATH, a11y, calibrate, pipeline, steam,
+};
+
+// Registry value older versions set once the walkthrough was finished.
+const DONE_VALUE_NAME: &str = "OnboardingDone";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Game,
+    Registry,
+    TestWrite,
+    Preferences,
+}
+
+impl Step {
+    const ALL: [Step; 4] = [
+        Step::Game,
+        Step::Registry,
+        Step::TestWrite,
+        Step::Preferences,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Step::Game => "Find MageArena",
+            Step::Registry => "Check the flag's registry key",
+            Step::TestWrite => "Test writing a flag",
+            Step::Preferences => "Preferences",
+        }
+    }
+}
+
+pub struct Wizard {
+    step: Step,
+    game_dir: Option<PathBuf>,
+    /// Whether the game's key exists, as checked on the registry step.
+    key_found: Option<bool>,
+    key_error: Option<String>,
+    write_result: Option<Result<String, String>>,
+}
+
+impl Wizard {
+    pub fn new() -> Self {
+        Self {
+            step: Step::Game,
+            game_dir: steam::game_dir(),
+            key_found: None,
+            key_error: None,
+            write_result: None,
+        }
+    }
+
+    /// Draws the current step; returns true once the user finishes or skips.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        palette: &Palette,
+        review: &mut bool,
+        hotkey: &mut Hotkey,
+    ) -> bool {
+        let index = Step::ALL
+            .iter()
+            .position(|&step| step == self.step)
+            .unwrap_or(0);
+        ui.label(format!(
+            "Step {} of {}: {}",
+            index + 1,
+            Step::ALL.len(),
+            self.step.label()
+        ));
+        ui.separator();
+
+        match self.step {
+            Step::Game => {
+                match &self.game_dir {
+                    Some(dir) => ui.label(format!("✅ Found MageArena in {}", dir.display())),
+                    None => ui.label(
+                        "MageArena was not found in any Steam library. MageFlag still works \
+                         as long as the game has saved its settings once.",
+                    ),
+                };
+                if ui.button("Search again").clicked() {
+                    self.game_dir = steam::game_dir();
+                }
+            }
+            Step::Registry => {
+                if self.key_found.is_none() {
+                    self.key_found = Some(
+                        RegKey::predef(HKEY_CURRENT_USER)
+                            .open_subkey(REGISTRY_PATH)
+                            .is_ok(),
+                    );
+                }
+                if self.key_found == Some(true) {
+                    ui.label("✅ The game's settings key exists.");
+                } else {
+                    ui.label(
+                        "The game has not saved its settings yet. Start MageArena once, or \
+                         create the key now.",
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Check again").clicked() {
+                            self.key_found = None;
+                        }
+                        if ui.button("Create key").clicked() {
+                            match RegKey::predef(HKEY_CURRENT_USER).create_subkey(REGISTRY_PATH) {
+                                Ok(_) => self.key_found = Some(true),
+                                Err(error) => {
+                                    self.key_error = Some(format!("Could not create it: {error}"))
+                                }
+                            }
+                        }
+                    });
+                }
+                if let Some(error) = &self.key_error {
+                    a11y::status(ui, error, true);
+                }
+            }
+            Step::TestWrite => {
+                ui.label(
+                    "Checks MageFlag may write the flag. A saved flag is written back \
+                     unchanged; without one, a test flag is saved.",
+                );
+                if ui.button("Test write").clicked() {
+                    self.write_result = Some(test_write(palette));
+                }
+                match &self.write_result {
+                    Some(Ok(message)) => {
+                        ui.label(format!("✅ {message}"));
+                    }
+                    Some(Err(error)) => {
+                        a11y::status(ui, error, true);
+                    }
+                    None => {}
+                }
+            }
+            Step::Preferences => {
+                ui.checkbox(review, "Review before apply")
+                    .on_hover_text("Copied images only show in the preview until you click Apply");
+                ui.checkbox(&mut hotkey.enabled, "Capture with a global hotkey")
+                    .on_hover_text(format!("Press {} while playing", hotkey.label()));
+                ui.label("Everything else is under the sections of the main window.");
+            }
+        }
+
+        ui.separator();
+        let mut finished = false;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(index > 0, egui::Button::new("Back"))
+                .clicked()
+            {
+                self.step = Step::ALL[index - 1];
+            }
+            if let Some(&next) = Step::ALL.get(index + 1) {
+                if ui.button("Next").clicked() {
+                    self.step = next;
+                }
+                if ui.button("Skip setup").clicked() {
+                    finished = true;
+                }
+            } else if ui.button("Finish").clicked() {
+                finished = true;
+            }
+        });
+        finished
+    }
+}
+
+/// Writes the saved flag back as it is, or a test flag when there is none.
+fn test_write(palette: &Palette) -> Result<String, String> {
+    let key = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags(REGISTRY_PATH, KEY_READ | KEY_WRITE)
+        .map_err(|e| format!("Could not open the game's registry key: {e}"))?;
+    let (value, message) = match key.get_raw_value(REGISTRY_VALUE_NAME) {
+        Ok(saved) => (saved, "Your saved flag was written back unchanged."),
+        Err(_) => {
+            let csv = pipeline::encode_uv_csv(&calibrate::pattern(palette), palette);
+            let value = winreg::RegValue {
+                vtype: RegType::REG_BINARY,
+                bytes: csv.into_bytes(),
+            };
+            (value, "A test flag was saved; copy an image to replace it.")
+        }
+    };
+    key.set_raw_value(REGISTRY_VALUE_NAME, &value)
+        .map_err(|e| format!("Could not write the flag: {e}"))?;
+    Ok(message.to_owned())
+}
+
+/// Whether the walkthrough was marked done in the registry, before settings
+/// moved to the config file.
+pub fn done_in_registry() -> bool {
+    RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(SETTINGS_REGISTRY_PATH)
+        .and_then(|key| key.get_value::<u32, _>(DONE_VALUE_NAME))
+        .is_ok_and(|done| done != 0)
+}
@@ -0,0 +1,53 @@
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+/// Flag-sized layer of palette indices drawn over the quantized flag, for
+/// elements that must come out pixel-exact rather than go through matching
+/// and dithering.
+pub struct Layer {
+    cells: Vec<Option<usize>>,
+    palette_len: usize,
+}
+
+impl Layer {
+    pub fn new(palette_len: usize) -> Self {
+        Self {
+            cells: vec![None; (IMAGE_WIDTH * IMAGE_HEIGHT) as usize],
+            palette_len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(Option::is_none)
+    }
+
+    /// Sets one cell, ignoring coordinates off the flag. Indices past the
+    /// end of a smaller palette clamp to its last cell.
+    pub fn set(&mut self, x: i32, y: i32, index: usize) {
+        if (0..IMAGE_WIDTH as i32).contains(&x) && (0..IMAGE_HEIGHT as i32).contains(&y) {
+            self.cells[(y as u32 * IMAGE_WIDTH + x as u32) as usize] =
+                Some(index.min(self.palette_len - 1));
+        }
+    }
+
+    /// `img` with the layer's palette colors painted in, so previews and
+    /// error measurements see the layer too.
+    pub fn paint(&self, img: &DynamicImage, palette: &[[u8; 3]]) -> DynamicImage {
+        let mut rgb: RgbImage = img.to_rgb8();
+        for (pixel, cell) in rgb.pixels_mut().zip(&self.cells) {
+            if let Some(index) = cell {
+                *pixel = Rgb(palette[*index]);
+            }
+        }
+        DynamicImage::ImageRgb8(rgb)
+    }
+
+    pub fn stamp(&self, indices: &mut [usize]) {
+        for (index, cell) in indices.iter_mut().zip(&self.cells) {
+            if let Some(cell) = cell {
+                *index = *cell;
+            }
+        }
+    }
+}
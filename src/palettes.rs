@@ -0,0 +1,337 @@
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, GenericImageView, Pixel, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use winreg::RegKey;
+use winreg::enums::HKEY_CURRENT_USER;
+
+use crate::calibrate::Curve;
+use crate::{EMBEDDED_PALETTE, PALETTE_COLS, PALETTE_ROWS, SETTINGS_REGISTRY_PATH};
+
+// Side of one cell in saved palette images, so the grid can be read back
+// from the image size alone.
+const SAVED_CELL_SIZE: u32 = 16;
+// Most columns or rows a palette grid can have.
+pub const GRID_MAX: u32 = 32;
+
+/// The flag colors as the game lays them out in its palette texture. The
+/// grid shape matters beyond the colors: it decides the UV each cell is
+/// addressed by.
+#[derive(Clone, PartialEq)]
+pub struct Palette {
+    pub cols: u32,
+    pub rows: u32,
+    /// Row-major cell colors.
+    pub colors: Vec<[u8; 3]>,
+    /// Image file the palette was sampled from; `None` for the built-in one.
+    pub source: Option<PathBuf>,
+    /// Name given inside an imported palette file.
+    pub name: Option<String>,
+}
+
+/// A palette file and the grid it is read with, as kept in the config.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaletteChoice {
+    pub path: PathBuf,
+    pub cols: u32,
+    pub rows: u32,
+}
+
+/// The shareable palette format: the grid, each cell as `#RRGGBB`, and the
+/// calibration measured for it if any.
+#[derive(Serialize, Deserialize)]
+struct PaletteFile {
+    name: String,
+    cols: u32,
+    rows: u32,
+    colors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    calibration: Option<Curve>,
+}
+
+impl Palette {
+    pub fn embedded() -> Self {
+        let image = image::load_from_memory(EMBEDDED_PALETTE).expect("Invalid embedded palette");
+        Self::sample(&image, PALETTE_COLS, PALETTE_ROWS)
+    }
+
+    /// Samples a `cols` x `rows` grid image at each cell's center.
+    pub fn sample(img: &DynamicImage, cols: u32, rows: u32) -> Self {
+        let (w, h) = img.dimensions();
+        let cell_w = w as f32 / cols as f32;
+        let cell_h = h as f32 / rows as f32;
+
+        let mut colors = Vec::with_capacity((cols * rows) as usize);
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let cx = ((col as f32 + 0.5) * cell_w).round() as u32;
+                let cy = ((row as f32 + 0.5) * cell_h).round() as u32;
+                let pixel = average_patch(img, cx.min(w - 1), cy.min(h - 1));
+                colors.push(pixel);
+            }
+        }
+
+        Self {
+            cols,
+            rows,
+            colors,
+            source: None,
+            name: None,
+        }
+    }
+
+    /// Samples a grid image, or reads a JSON palette, whose grid is its own.
+    pub fn load(path: &Path, cols: u32, rows: u32) -> Result<Self, String> {
+        if is_json(path) {
+            return Self::import(path).map(|(palette, _)| palette);
+        }
+        check_grid(cols, rows).map_err(|e| format!("{}: {e}", path.display()))?;
+        let image = image::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(Self {
+            source: Some(path.to_owned()),
+            ..Self::sample(&image, cols, rows)
+        })
+    }
+
+    /// Loads a palette written by `save`, whose grid follows from its size.
+    pub fn load_saved(path: &Path) -> Result<Self, String> {
+        if is_json(path) {
+            return Self::import(path).map(|(palette, _)| palette);
+        }
+        let (w, h) =
+            image::image_dimensions(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::load(path, w / SAVED_CELL_SIZE, h / SAVED_CELL_SIZE)
+    }
+
+    /// Reads a JSON palette file along with the calibration stored in it.
+    pub fn import(path: &Path) -> Result<(Self, Option<Curve>), String> {
+        let error = |e: &dyn std::fmt::Display| format!("{}: {e}", path.display());
+        let text = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+        let file: PaletteFile = serde_json::from_str(&text).map_err(|e| error(&e))?;
+
+        check_grid(file.cols, file.rows).map_err(|e| error(&e))?;
+        let cells = file.cols.checked_mul(file.rows).map(|cells| cells as usize);
+        if cells != Some(file.colors.len()) {
+            return Err(error(&format!(
+                "{} colors do not fill a {}x{} grid",
+                file.colors.len(),
+                file.cols,
+                file.rows
+            )));
+        }
+        let colors = file
+            .colors
+            .iter()
+            .map(|hex| parse_hex(hex).ok_or_else(|| error(&format!("invalid color {hex:?}"))))
+            .collect::<Result<_, _>>()?;
+
+        let palette = Self {
+            cols: file.cols,
+            rows: file.rows,
+            colors,
+            source: Some(path.to_owned()),
+            name: Some(file.name).filter(|name| !name.trim().is_empty()),
+        };
+        Ok((palette, file.calibration))
+    }
+
+    /// Writes the palette as JSON, named after the file.
+    pub fn export(&self, path: &Path, calibration: Option<Curve>) -> Result<(), String> {
+        let file = PaletteFile {
+            name: path
+                .file_stem()
+                .map_or_else(|| self.name(), |stem| stem.to_string_lossy().into_owned()),
+            cols: self.cols,
+            rows: self.rows,
+            colors: self
+                .colors
+                .iter()
+                .map(|[r, g, b]| format!("#{r:02X}{g:02X}{b:02X}"))
+                .collect(),
+            calibration,
+        };
+        let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Writes the palette as `name.png` in the palettes folder and returns
+    /// it as loaded from there.
+    pub fn save(&self, name: &str) -> Result<Self, String> {
+        let dir = palettes_dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        let path = dir.join(format!("{name}.png"));
+
+        let image = RgbImage::from_fn(
+            self.cols * SAVED_CELL_SIZE,
+            self.rows * SAVED_CELL_SIZE,
+            |x, y| {
+                let idx = (y / SAVED_CELL_SIZE * self.cols + x / SAVED_CELL_SIZE) as usize;
+                Rgb(self.colors.get(idx).copied().unwrap_or_default())
+            },
+        );
+        image
+            .save(&path)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(Self {
+            source: Some(path),
+            name: None,
+            ..self.clone()
+        })
+    }
+
+    /// Changes the grid shape, keeping each surviving cell at its row and
+    /// column; new cells start black.
+    pub fn reshape(&mut self, cols: u32, rows: u32) {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        self.colors = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                if row < self.rows && col < self.cols {
+                    self.colors[(row * self.cols + col) as usize]
+                } else {
+                    [0, 0, 0]
+                }
+            })
+            .collect();
+        self.cols = cols;
+        self.rows = rows;
+    }
+
+    /// Cell `idx`'s UV as written into the flag data.
+    pub fn uv_string(&self, idx: usize) -> String {
+        let (u, v) = self.uv(idx);
+        format!("{u:.2}:{v:.2}")
+    }
+
+    /// Where the palette came from, to restore it next time; `None` for the
+    /// built-in one.
+    pub fn choice(&self) -> Option<PaletteChoice> {
+        Some(PaletteChoice {
+            path: self.source.clone()?,
+            cols: self.cols,
+            rows: self.rows,
+        })
+    }
+
+    /// The name stored in the file, else the file name without extension,
+    /// or "Built-in".
+    pub fn name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        self.source
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map_or_else(
+                || "Built-in".to_owned(),
+                |stem| stem.to_string_lossy().into_owned(),
+            )
+    }
+
+    /// The texture coordinate the game samples for cell `idx`; V runs
+    /// bottom-up.
+    pub fn uv(&self, idx: usize) -> (f32, f32) {
+        let row = self.rows - 1 - idx as u32 / self.cols;
+        let col = idx as u32 % self.cols;
+        (
+            (col as f32 + 0.5) / self.cols as f32,
+            (row as f32 + 0.5) / self.rows as f32,
+        )
+    }
+}
+
+fn average_patch(img: &DynamicImage, cx: u32, cy: u32) -> [u8; 3] {
+    let mut r = 0u32;
+    let mut g = 0u32;
+    let mut b = 0u32;
+    let mut count = 0u32;
+
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let x = (cx as i32 + dx).clamp(0, img.width() as i32 - 1) as u32;
+            let y = (cy as i32 + dy).clamp(0, img.height() as i32 - 1) as u32;
+            let pixel = img.get_pixel(x, y).to_rgb();
+            r += pixel[0] as u32;
+            g += pixel[1] as u32;
+            b += pixel[2] as u32;
+            count += 1;
+        }
+    }
+
+    [(r / count) as u8, (g / count) as u8, (b / count) as u8]
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Where named palettes are saved: `%APPDATA%\MageFlag\palettes`.
+pub fn palettes_dir() -> PathBuf {
+    std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("MageFlag")
+        .join("palettes")
+}
+
+/// Saved palettes, sorted by name.
+pub fn profiles() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(palettes_dir()) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            is_json(path)
+                || path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Rejects grids outside 1x1 to `GRID_MAX`x`GRID_MAX`.
+fn check_grid(cols: u32, rows: u32) -> Result<(), String> {
+    let grid = 1..=GRID_MAX;
+    if grid.contains(&cols) && grid.contains(&rows) {
+        Ok(())
+    } else {
+        Err(format!(
+            "a {cols}x{rows} grid is outside 1x1 to {GRID_MAX}x{GRID_MAX}"
+        ))
+    }
+}
+
+/// The palette `choice` names, falling back to the built-in one when there
+/// is none or the file can no longer be read.
+pub fn restore(choice: Option<&PaletteChoice>) -> Palette {
+    choice
+        .and_then(|choice| Palette::load(&choice.path, choice.cols, choice.rows).ok())
+        .unwrap_or_else(Palette::embedded)
+}
+
+/// The palette chosen as saved before settings moved to the config file.
+pub fn registry_choice() -> Option<PaletteChoice> {
+    let key = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(SETTINGS_REGISTRY_PATH)
+        .ok()?;
+    let path: String = key.get_value("PalettePath").ok()?;
+    Some(PaletteChoice {
+        path: PathBuf::from(path),
+        cols: key.get_value("PaletteCols").ok()?,
+        rows: key.get_value("PaletteRows").ok()?,
+    })
+}
@@ -0,0 +1,485 @@
+use std::time::{Duration, Instant};
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::adjust::SharpenSettings;
+use crate::alpha::{self, AlphaSettings, OutlineSettings};
+use crate::calibrate::{self, Curve, Measurement};
+use crate::chroma::{self, ChromaKey};
+use crate::color::{self, ColorMetric, LabWeights, LutCache, Matcher};
+use crate::composite::{self, Layers};
+use crate::crop::{self, CropRect};
+use crate::filter;
+use crate::fit::{self, FitMode, FitSettings};
+use crate::overlay::Layer;
+use crate::palettes::Palette;
+use crate::remap::CompiledRules;
+use crate::resize::{self, ResizeSettings};
+use crate::segment::{self, BackgroundRemoval};
+use crate::transform::{self, Orientation, Symmetry};
+use crate::{
+    IMAGE_HEIGHT, IMAGE_WIDTH, Settings, adjust, border, contrast, denoise, dither, quality,
+    saliency, text, uv,
+};
+
+/// Everything one encode produces, for the registry and the previews.
+pub struct Encoded {
+    /// Flag-sized source after adjustments, as fed to quantization.
+    pub prepared: DynamicImage,
+    /// `prepared` as it would be without sharpening, when sharpening is on.
+    pub unsharpened: Option<DynamicImage>,
+    /// Row-major palette indices with the current settings.
+    pub indices: Vec<usize>,
+    /// Same settings with neutral Lab weights, for comparison.
+    pub unweighted: Vec<usize>,
+    /// Per-pixel ΔE of `indices` against `prepared`.
+    pub errors: Vec<f32>,
+    pub csv: String,
+    /// Wall time of quantize + CSV encode.
+    pub encode_time: Duration,
+    /// `prepared` was already made of palette colors and mapped 1:1.
+    pub exact: bool,
+}
+
+/// The source → flag stages, holding the current source image and the
+/// expensive intermediates so a settings change only re-runs what it affects:
+/// orienting, cropping, outlining, background removal, keying, layering,
+/// flattening alpha, fitting, sharpening and resampling are redone only when their
+/// settings or the source change.
+pub struct Pipeline {
+    palette: Palette,
+    /// The palette colors every stage matches and fills with: as the
+    /// calibration curve predicts them in game when one is active.
+    colors: Vec<[u8; 3]>,
+    calibration: Option<Curve>,
+    source: Option<RgbaImage>,
+    /// Palette indices `source` was decoded from; they stand in for every
+    /// stage up to quantization, so a flag read back stays as it was.
+    flag: Option<Vec<usize>>,
+    /// Image pinned as the bottom layer for compositing.
+    background: Option<RgbaImage>,
+    /// `source` under a non-identity orientation, keyed by that orientation.
+    oriented: Option<(Orientation, RgbaImage)>,
+    resampled: Option<Resampled>,
+    luts: LutCache,
+}
+
+/// The settings the full-resolution stages depend on.
+#[derive(Clone, PartialEq)]
+struct SourceKey {
+    orientation: Orientation,
+    crop: Option<CropRect>,
+    outline: OutlineSettings,
+    layers: Layers,
+    alpha: AlphaSettings,
+    background_removal: BackgroundRemoval,
+    chroma_key: ChromaKey,
+    fit: FitSettings,
+    sharpen: SharpenSettings,
+    resize: ResizeSettings,
+}
+
+impl SourceKey {
+    fn of(settings: &Settings) -> Self {
+        Self {
+            orientation: settings.orientation,
+            crop: settings.crop,
+            outline: settings.outline,
+            layers: settings.layers,
+            alpha: settings.alpha,
+            background_removal: settings.background_removal,
+            chroma_key: settings.chroma_key,
+            fit: settings.fit,
+            sharpen: settings.sharpen,
+            resize: settings.resize,
+        }
+    }
+}
+
+/// The full-resolution stages' output, keyed by the settings that made it.
+struct Resampled {
+    key: SourceKey,
+    image: DynamicImage,
+    unsharpened: Option<DynamicImage>,
+}
+
+impl Resampled {
+    fn new(
+        source: &RgbaImage,
+        background: Option<&RgbaImage>,
+        key: SourceKey,
+        palette: &[[u8; 3]],
+    ) -> Self {
+        // A layered canvas is resampled, so its cells are no longer the art's.
+        let layering = key.layers.enabled && background.is_some();
+        let art = (!layering)
+            .then(|| resize::art_pixels(source, key.resize))
+            .flatten();
+        let source = art.as_ref().unwrap_or(source);
+        let cropped;
+        let source = match key.crop {
+            Some(rect) => {
+                cropped = crop::apply(source, rect);
+                &cropped
+            }
+            None => source,
+        };
+        let outlined = key
+            .outline
+            .enabled
+            .then(|| alpha::outline(source, key.outline, palette))
+            .flatten();
+        let source = outlined.as_ref().unwrap_or(source);
+        // Removed and keyed parts of the source are cut out for a pinned
+        // layer to show through, and filled when there is none.
+        let background = background.filter(|_| key.layers.enabled);
+        let cut_out = background.is_some();
+        let segmented = key
+            .background_removal
+            .enabled
+            .then(|| segment::remove_background(source, key.background_removal, palette, cut_out))
+            .flatten();
+        let source = segmented.as_ref().unwrap_or(source);
+        let keyed;
+        let source = if key.chroma_key.enabled {
+            keyed = chroma::apply(source, key.chroma_key, palette, cut_out);
+            &keyed
+        } else {
+            source
+        };
+        let layered;
+        let source = match background {
+            Some(background) => {
+                layered = composite::flatten(background, source, key.layers);
+                &layered
+            }
+            None => source,
+        };
+        let flattened = alpha::composite(source, key.alpha);
+        let source = flattened.as_ref().unwrap_or(source);
+        let fitted;
+        let source = match key.fit.mode {
+            FitMode::Stretch => source,
+            _ => {
+                fitted = fit::apply(source, key.fit, palette);
+                &fitted
+            }
+        };
+
+        // Art pixels are already as sharp as they get.
+        let (image, unsharpened) = if art.is_some() {
+            (resize::sample_art(source), None)
+        } else if key.sharpen.enabled {
+            let sharpened = adjust::unsharp_mask(source, key.sharpen);
+            (
+                resize::resize(&sharpened, key.resize),
+                Some(resize::resize(source, key.resize)),
+            )
+        } else {
+            (resize::resize(source, key.resize), None)
+        };
+
+        Self {
+            key,
+            image,
+            unsharpened,
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            luts: prewarmed_luts(&palette.colors),
+            colors: palette.colors.clone(),
+            calibration: None,
+            palette,
+            source: None,
+            flag: None,
+            background: None,
+            oriented: None,
+            resampled: None,
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Switches to another palette; every cached stage that used the old
+    /// colors is dropped.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.flag = None;
+        self.update_colors();
+    }
+
+    fn update_colors(&mut self) {
+        self.colors = match self.calibration {
+            Some(curve) => self
+                .palette
+                .colors
+                .iter()
+                .map(|&c| curve.apply(c))
+                .collect(),
+            None => self.palette.colors.clone(),
+        };
+        self.luts = prewarmed_luts(&self.colors);
+        self.resampled = None;
+    }
+
+    /// The calibration test flag as registry CSV.
+    pub fn test_pattern(&self) -> String {
+        encode_uv_csv(&calibrate::pattern(&self.palette), &self.palette)
+    }
+
+    /// Measures the test flag in the current source, a screenshot cropped
+    /// to the flag; `None` without a source.
+    pub fn measure_calibration(
+        &mut self,
+        settings: &Settings,
+    ) -> Option<Result<Measurement, String>> {
+        self.oriented_source(settings.orientation)?;
+        let source = if settings.orientation.is_identity() {
+            self.source.as_ref()?
+        } else {
+            &self.oriented.as_ref()?.1
+        };
+        let cropped;
+        let source = match settings.crop {
+            Some(rect) => {
+                cropped = crop::apply(source, rect);
+                &cropped
+            }
+            None => source,
+        };
+        Some(calibrate::measure(source, &self.palette))
+    }
+
+    pub fn set_source(&mut self, source: RgbaImage) {
+        self.source = Some(source);
+        self.flag = None;
+        self.oriented = None;
+        self.resampled = None;
+    }
+
+    /// Encodes `indices` as they are, instead of quantizing the current
+    /// source, until another source or palette is set. Text, borders and
+    /// hand edits still go on top.
+    pub fn set_flag(&mut self, indices: Vec<usize>) {
+        self.flag = Some(indices);
+    }
+
+    /// Keeps the current (oriented) source as the compositing background and
+    /// returns its size, or `None` without a source.
+    pub fn pin_background(&mut self, orientation: Orientation) -> Option<[u32; 2]> {
+        let pinned = self.oriented_source(orientation)?.clone();
+        let size = [pinned.width(), pinned.height()];
+        self.background = Some(pinned);
+        self.resampled = None;
+        Some(size)
+    }
+
+    /// The current source rotated and flipped per `orientation`, which is
+    /// what cropping and every later stage see.
+    pub fn oriented_source(&mut self, orientation: Orientation) -> Option<&RgbaImage> {
+        let source = self.source.as_ref()?;
+        if orientation.is_identity() {
+            return Some(source);
+        }
+
+        if self
+            .oriented
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != orientation)
+        {
+            self.oriented = Some((orientation, transform::orient(source, orientation)));
+            self.resampled = None;
+        }
+        self.oriented.as_ref().map(|(_, image)| image)
+    }
+
+    /// Runs every stage for the current source, or `None` without one.
+    pub fn run(&mut self, settings: &Settings) -> Option<Encoded> {
+        let calibration = settings.calibration.active();
+        if calibration != self.calibration {
+            self.calibration = calibration;
+            self.update_colors();
+        }
+        self.oriented_source(settings.orientation)?;
+        let source = if settings.orientation.is_identity() {
+            self.source.as_ref()?
+        } else {
+            &self.oriented.as_ref()?.1
+        };
+
+        let key = SourceKey::of(settings);
+        if self
+            .resampled
+            .as_ref()
+            .is_none_or(|cached| cached.key != key)
+        {
+            self.resampled = Some(Resampled::new(
+                source,
+                self.background.as_ref(),
+                key,
+                &self.colors,
+            ));
+        }
+        let resampled = self.resampled.as_ref()?;
+
+        let palette = &self.colors;
+        let mut layer = Layer::new(palette.len());
+        text::draw(&settings.text_overlay, &mut layer);
+        border::draw(settings.border, &mut layer);
+        settings.pixel_edits.draw(&mut layer);
+        let finish = |image: &DynamicImage| {
+            let prepared = prepare(image, settings, palette);
+            if layer.is_empty() {
+                prepared
+            } else {
+                layer.paint(&prepared, palette)
+            }
+        };
+        let (prepared, exact) = match &self.flag {
+            // Drawn in the matching colors, so calibration does not read as
+            // an error.
+            Some(flag) => {
+                let image = DynamicImage::ImageRgba8(uv::render(flag, palette));
+                let image = if layer.is_empty() {
+                    image
+                } else {
+                    layer.paint(&image, palette)
+                };
+                (image, Some(flag.clone()))
+            }
+            None => {
+                let prepared = finish(&resampled.image);
+                let exact = exact_match(&prepared, palette, settings);
+                (prepared, exact)
+            }
+        };
+
+        let started = Instant::now();
+        let mut indices = match &exact {
+            Some(indices) => indices.clone(),
+            None => quantize(&prepared, palette, settings, &mut self.luts),
+        };
+        layer.stamp(&mut indices);
+        let csv = encode_uv_csv(&indices, &self.palette);
+        let encode_time = started.elapsed();
+        let errors = quality::delta_e_map(&prepared, &indices, palette);
+
+        let unweighted = if exact.is_some() || settings.weights == LabWeights::default() {
+            indices.clone()
+        } else {
+            let neutral = Settings {
+                weights: LabWeights::default(),
+                ..settings.clone()
+            };
+            let mut unweighted = quantize(&prepared, palette, &neutral, &mut self.luts);
+            layer.stamp(&mut unweighted);
+            unweighted
+        };
+
+        Some(Encoded {
+            prepared,
+            unsharpened: resampled
+                .unsharpened
+                .as_ref()
+                .filter(|_| self.flag.is_none())
+                .map(finish),
+            indices,
+            unweighted,
+            errors,
+            csv,
+            encode_time,
+            exact: exact.is_some(),
+        })
+    }
+}
+
+/// A LUT cache with the tables the default "auto" metric resolves to built
+/// up front.
+fn prewarmed_luts(palette: &[[u8; 3]]) -> LutCache {
+    let mut luts = LutCache::default();
+    for metric in [ColorMetric::Cie76, ColorMetric::Ciede2000] {
+        luts.get(palette, &Settings::default().match_params(palette, metric));
+    }
+    luts
+}
+
+/// The flag-sized stages between resampling and quantization.
+fn prepare(resized: &DynamicImage, settings: &Settings, palette: &[[u8; 3]]) -> DynamicImage {
+    let symmetric;
+    let resized = match settings.symmetry {
+        Symmetry::Off => resized,
+        symmetry => {
+            symmetric = transform::symmetrize(resized, symmetry);
+            &symmetric
+        }
+    };
+    let denoised = denoise::denoise(resized, settings.denoise);
+    let adjusted = adjust::apply(&denoised, settings.adjustments);
+    let mut adjusted = filter::apply(&adjusted, settings.filter, palette);
+    if settings.vignette.enabled {
+        adjusted = filter::vignette(&adjusted, settings.vignette);
+    }
+    if settings.posterize.enabled {
+        adjust::posterize(&adjusted, settings.posterize.levels)
+    } else {
+        adjusted
+    }
+}
+
+/// Skips matching and dithering when the image is already drawn in allowed
+/// palette colors. Remap rules express an intent to change colors, so they
+/// disable the shortcut.
+fn exact_match(img: &DynamicImage, palette: &[[u8; 3]], settings: &Settings) -> Option<Vec<usize>> {
+    if !settings.remap_rules.is_empty() {
+        return None;
+    }
+    let params = settings.match_params(palette, ColorMetric::Cie76);
+    color::exact_indices(img, palette, &params.allowed)
+}
+
+fn quantize(
+    img: &DynamicImage,
+    palette: &[[u8; 3]],
+    settings: &Settings,
+    luts: &mut LutCache,
+) -> Vec<usize> {
+    let metric = ColorMetric::resolve(settings.metric, img);
+    let params = settings.match_params(palette, metric);
+    let mut matcher = Matcher::new(palette, params.clone());
+    if settings.use_lut {
+        matcher = matcher.with_lut(luts.get(palette, &params));
+    }
+    matcher = matcher.with_rules(CompiledRules::new(&settings.remap_rules, palette.len()));
+
+    let focus = settings
+        .saliency
+        .enabled
+        .then(|| saliency::focus_map(img, settings.saliency));
+    let mut indices = dither::quantize(img, &matcher, settings.dither, focus.as_deref());
+    if settings.contrast.enabled {
+        contrast::preserve_local_contrast(img, &matcher, &mut indices, settings.contrast.threshold);
+    }
+    if settings.symmetry != Symmetry::Off {
+        transform::symmetrize_indices(&mut indices, settings.symmetry);
+    }
+    indices
+}
+
+pub fn encode_uv_csv(indices: &[usize], palette: &Palette) -> String {
+    let mut result = Vec::with_capacity((IMAGE_WIDTH * IMAGE_HEIGHT) as usize);
+
+    for x in 0..IMAGE_WIDTH {
+        for y in (0..IMAGE_HEIGHT).rev() {
+            let idx = indices[(y * IMAGE_WIDTH + x) as usize];
+            result.push(palette.uv_string(idx));
+        }
+    }
+
+    result.join(",")
+}
@@ -0,0 +1,45 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::color::{ColorMetric, to_lab};
+
+// Mean ΔE00 bands for the one-word verdict next to the numbers.
+const GOOD_MEAN: f32 = 5.0;
+const FAIR_MEAN: f32 = 10.0;
+
+/// Perceptual error of a quantized flag against its flag-sized source.
+#[derive(Clone, Copy)]
+pub struct QualityReport {
+    pub mean: f32,
+    pub max: f32,
+}
+
+impl QualityReport {
+    pub fn from_errors(errors: &[f32]) -> Self {
+        Self {
+            mean: errors.iter().sum::<f32>() / errors.len().max(1) as f32,
+            max: errors.iter().copied().fold(0.0, f32::max),
+        }
+    }
+
+    pub fn verdict(&self) -> &'static str {
+        if self.mean < GOOD_MEAN {
+            "good"
+        } else if self.mean < FAIR_MEAN {
+            "fair"
+        } else {
+            "poor"
+        }
+    }
+}
+
+/// CIEDE2000 distance between each source pixel and the palette color it was
+/// mapped to, row-major.
+pub fn delta_e_map(img: &DynamicImage, indices: &[usize], palette: &[[u8; 3]]) -> Vec<f32> {
+    img.pixels()
+        .zip(indices)
+        .map(|((_, _, pixel), &idx)| {
+            ColorMetric::Ciede2000
+                .distance(to_lab([pixel[0], pixel[1], pixel[2]]), to_lab(palette[idx]))
+        })
+        .collect()
+}
@@ -0,0 +1,51 @@
+use palette::Lab;
+
+use crate::color::{ColorMetric, to_lab};
+
+/// A user override: source colors within `tolerance` (ΔE76) of `source` are
+/// forced onto palette entry `target` instead of their nearest match.
+#[derive(Clone, PartialEq)]
+pub struct RemapRule {
+    pub source: [u8; 3],
+    pub tolerance: f32,
+    pub target: usize,
+}
+
+impl Default for RemapRule {
+    fn default() -> Self {
+        Self {
+            source: [0x1A, 0x73, 0xE8],
+            tolerance: 10.0,
+            target: 0,
+        }
+    }
+}
+
+/// Rules with their source colors converted to Lab once.
+pub struct CompiledRules {
+    rules: Vec<(Lab, f32, usize)>,
+}
+
+impl CompiledRules {
+    pub fn new(rules: &[RemapRule], palette_len: usize) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .filter(|rule| rule.target < palette_len)
+                .map(|rule| (to_lab(rule.source), rule.tolerance, rule.target))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The target of the first rule whose source is close enough to `lab`.
+    pub fn apply(&self, lab: Lab) -> Option<usize> {
+        self.rules
+            .iter()
+            .find(|(source, tolerance, _)| ColorMetric::Cie76.distance(lab, *source) <= *tolerance)
+            .map(|&(_, _, target)| target)
+    }
+}
@@ -0,0 +1,322 @@
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage, imageops::FilterType};
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ResizeMode {
+    /// Box filter: every destination pixel is the coverage-weighted mean of
+    /// the source pixels under it.
+    #[default]
+    AreaAverage,
+    /// Nearest-neighbor sampling (the original behavior).
+    PixelSample,
+    Triangle,
+    CatmullRom,
+    Lanczos3,
+}
+
+impl ResizeMode {
+    pub const ALL: [ResizeMode; 5] = [
+        ResizeMode::AreaAverage,
+        ResizeMode::PixelSample,
+        ResizeMode::Triangle,
+        ResizeMode::CatmullRom,
+        ResizeMode::Lanczos3,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ResizeMode::AreaAverage => "Area average (box)",
+            ResizeMode::PixelSample => "Pixel sample (nearest)",
+            ResizeMode::Triangle => "Triangle (bilinear)",
+            ResizeMode::CatmullRom => "Catmull-Rom (bicubic)",
+            ResizeMode::Lanczos3 => "Lanczos3",
+        }
+    }
+
+    fn filter_type(self) -> Option<FilterType> {
+        match self {
+            ResizeMode::AreaAverage => None,
+            ResizeMode::PixelSample => Some(FilterType::Nearest),
+            ResizeMode::Triangle => Some(FilterType::Triangle),
+            ResizeMode::CatmullRom => Some(FilterType::CatmullRom),
+            ResizeMode::Lanczos3 => Some(FilterType::Lanczos3),
+        }
+    }
+}
+
+/// Filter for sources smaller than the flag, chosen separately because what
+/// looks best shrinking rarely looks best enlarging.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum UpscaleMode {
+    Nearest,
+    #[default]
+    Bicubic,
+    /// Edge-directed Scale2x passes, which round off staircase diagonals in
+    /// icons and emotes, then the downscale filter to the exact size.
+    Scale2x,
+}
+
+impl UpscaleMode {
+    pub const ALL: [UpscaleMode; 3] = [
+        UpscaleMode::Nearest,
+        UpscaleMode::Bicubic,
+        UpscaleMode::Scale2x,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UpscaleMode::Nearest => "Nearest",
+            UpscaleMode::Bicubic => "Bicubic",
+            UpscaleMode::Scale2x => "Scale2x (pixel art)",
+        }
+    }
+}
+
+// Most Scale2x doublings of one source, 16x in all.
+const SCALE2X_MAX_PASSES: u32 = 4;
+// Largest per-channel difference still counted as the same pixel-art cell,
+// to tolerate light compression noise.
+const CELL_TOLERANCE: i16 = 4;
+
+/// How sources drawn as scaled-up pixel art are handled.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum PixelArt {
+    /// Always use the resampling filter.
+    #[default]
+    Off,
+    /// Look for a grid of uniform square cells and sample their centers.
+    Detect,
+    /// Sample the centers of cells of `ResizeSettings::pixel_size`.
+    Fixed,
+}
+
+impl PixelArt {
+    pub const ALL: [PixelArt; 3] = [PixelArt::Off, PixelArt::Detect, PixelArt::Fixed];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PixelArt::Off => "Off",
+            PixelArt::Detect => "Detect grid",
+            PixelArt::Fixed => "Fixed pixel size",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ResizeSettings {
+    pub mode: ResizeMode,
+    /// Filter in linear light instead of on gamma-encoded sRGB, which keeps
+    /// fine detail and high-contrast edges from darkening when shrunk.
+    pub linear_light: bool,
+    pub upscale: UpscaleMode,
+    pub pixel_art: PixelArt,
+    /// Source pixels per art pixel for `PixelArt::Fixed`.
+    pub pixel_size: u32,
+}
+
+impl Default for ResizeSettings {
+    fn default() -> Self {
+        Self {
+            mode: ResizeMode::default(),
+            linear_light: false,
+            upscale: UpscaleMode::default(),
+            pixel_art: PixelArt::Off,
+            pixel_size: 4,
+        }
+    }
+}
+
+/// Scales the source image to the flag size. A source that already is flag
+/// sized passes through untouched, so externally drawn flags stay
+/// bit-identical.
+pub fn resize(img: &RgbaImage, settings: ResizeSettings) -> DynamicImage {
+    if img.dimensions() == (IMAGE_WIDTH, IMAGE_HEIGHT) {
+        return DynamicImage::ImageRgba8(img.clone());
+    }
+
+    let smaller = |img: &RgbaImage| img.width() < IMAGE_WIDTH || img.height() < IMAGE_HEIGHT;
+    // Doubling stops once either side reaches the flag, so a thin strip is
+    // not blown up along its long side; the resampler does the rest.
+    let both_smaller = |img: &RgbaImage| img.width() < IMAGE_WIDTH && img.height() < IMAGE_HEIGHT;
+    let mut doubled = None;
+    if settings.upscale == UpscaleMode::Scale2x {
+        for _ in 0..SCALE2X_MAX_PASSES {
+            let current = doubled.as_ref().unwrap_or(img);
+            if !both_smaller(current) {
+                break;
+            }
+            doubled = Some(scale2x(current));
+        }
+    }
+    let img = doubled.as_ref().unwrap_or(img);
+    let filter = if smaller(img) {
+        match settings.upscale {
+            UpscaleMode::Nearest => Some(FilterType::Nearest),
+            _ => Some(FilterType::CatmullRom),
+        }
+    } else {
+        settings.mode.filter_type()
+    };
+
+    let decode = |c: u8| {
+        if settings.linear_light {
+            srgb_to_linear(c)
+        } else {
+            c as f32 / 255.0
+        }
+    };
+    let encode = |c: f32| {
+        if settings.linear_light {
+            linear_to_srgb(c)
+        } else {
+            (c * 255.0).round().clamp(0.0, 255.0) as u8
+        }
+    };
+
+    let source = Rgba32FImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        Rgba([decode(r), decode(g), decode(b), a as f32 / 255.0])
+    });
+
+    let resized = match filter {
+        Some(filter) => image::imageops::resize(&source, IMAGE_WIDTH, IMAGE_HEIGHT, filter),
+        None => area_average(&source, IMAGE_WIDTH, IMAGE_HEIGHT),
+    };
+
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(IMAGE_WIDTH, IMAGE_HEIGHT, |x, y| {
+        let [r, g, b, a] = resized.get_pixel(x, y).0;
+        Rgba([
+            encode(r),
+            encode(g),
+            encode(b),
+            (a * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    }))
+}
+
+/// The largest cell size that divides both dimensions and for which every
+/// cell is a single flat color, or `None` if the image isn't scaled pixel art.
+fn detect_cell_size(img: &RgbaImage) -> Option<u32> {
+    let (w, h) = img.dimensions();
+    let same = |a: [u8; 4], b: [u8; 4]| {
+        a.iter()
+            .zip(b)
+            .all(|(&a, b)| (a as i16 - b as i16).abs() <= CELL_TOLERANCE)
+    };
+
+    (2..=w.min(h) / 2)
+        .rev()
+        .filter(|&cell| w.is_multiple_of(cell) && h.is_multiple_of(cell))
+        .find(|&cell| {
+            img.enumerate_pixels().all(|(x, y, pixel)| {
+                let anchor = img.get_pixel(x - x % cell, y - y % cell).0;
+                same(pixel.0, anchor)
+            })
+        })
+}
+
+/// The source reduced to one pixel per art pixel, by the detected or given
+/// cell size, when it is scaled-up pixel art; `None` otherwise. Done on the
+/// source as it comes, since cropping, fitting and sharpening would shift
+/// or blur its cells.
+pub fn art_pixels(img: &RgbaImage, settings: ResizeSettings) -> Option<RgbaImage> {
+    if img.dimensions() == (IMAGE_WIDTH, IMAGE_HEIGHT) {
+        return None;
+    }
+    let cell = match settings.pixel_art {
+        PixelArt::Off => None,
+        PixelArt::Detect => detect_cell_size(img),
+        PixelArt::Fixed => Some(settings.pixel_size.max(1)),
+    }?;
+    let (w, h) = img.dimensions();
+    Some(RgbaImage::from_fn(
+        w.div_ceil(cell),
+        h.div_ceil(cell),
+        |x, y| {
+            *img.get_pixel(
+                (x * cell + cell / 2).min(w - 1),
+                (y * cell + cell / 2).min(h - 1),
+            )
+        },
+    ))
+}
+
+/// Scales art pixels from `art_pixels` to the flag size by nearest sampling
+/// on their centers, so every flag pixel takes exactly one art pixel's color.
+pub fn sample_art(img: &RgbaImage) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(IMAGE_WIDTH, IMAGE_HEIGHT, |x, y| {
+        let art_x = ((x as f32 + 0.5) * w as f32 / IMAGE_WIDTH as f32) as u32;
+        let art_y = ((y as f32 + 0.5) * h as f32 / IMAGE_HEIGHT as f32) as u32;
+        *img.get_pixel(art_x.min(w - 1), art_y.min(h - 1))
+    }))
+}
+
+/// Doubles `img` with the Scale2x rules: each pixel becomes four, and a
+/// quarter takes a neighbor's color where two neighbors agree along an edge.
+fn scale2x(img: &RgbaImage) -> RgbaImage {
+    let (w, h) = img.dimensions();
+    let at = |x: i64, y: i64| {
+        *img.get_pixel(
+            x.clamp(0, w as i64 - 1) as u32,
+            y.clamp(0, h as i64 - 1) as u32,
+        )
+    };
+
+    RgbaImage::from_fn(w * 2, h * 2, |x, y| {
+        let (sx, sy) = ((x / 2) as i64, (y / 2) as i64);
+        let center = at(sx, sy);
+        let (up, down) = (at(sx, sy - 1), at(sx, sy + 1));
+        let (left, right) = (at(sx - 1, sy), at(sx + 1, sy));
+
+        // The two neighbors flanking this quarter, and the two opposite.
+        let (vertical, horizontal, far_vertical, far_horizontal) = match (x % 2, y % 2) {
+            (0, 0) => (up, left, down, right),
+            (1, 0) => (up, right, down, left),
+            (0, _) => (down, left, up, right),
+            _ => (down, right, up, left),
+        };
+        if vertical == horizontal && vertical != far_horizontal && horizontal != far_vertical {
+            vertical
+        } else {
+            center
+        }
+    })
+}
+
+/// Supersampling box filter: each destination pixel averages every source
+/// pixel it overlaps, weighted by the overlapping area.
+fn area_average(src: &Rgba32FImage, width: u32, height: u32) -> Rgba32FImage {
+    let scale_x = src.width() as f32 / width as f32;
+    let scale_y = src.height() as f32 / height as f32;
+
+    Rgba32FImage::from_fn(width, height, |x, y| {
+        let (x0, x1) = (x as f32 * scale_x, (x + 1) as f32 * scale_x);
+        let (y0, y1) = (y as f32 * scale_y, (y + 1) as f32 * scale_y);
+
+        let mut sum = [0.0f32; 4];
+        let mut total = 0.0f32;
+
+        for sy in y0.floor() as u32..(y1.ceil() as u32).min(src.height()) {
+            let wy = coverage(sy, y0, y1);
+            for sx in x0.floor() as u32..(x1.ceil() as u32).min(src.width()) {
+                let weight = coverage(sx, x0, x1) * wy;
+                let pixel = src.get_pixel(sx, sy).0;
+                for c in 0..4 {
+                    sum[c] += pixel[c] * weight;
+                }
+                total += weight;
+            }
+        }
+
+        Rgba(sum.map(|c| c / total))
+    })
+}
+
+/// How much of source cell `i` lies inside `[start, end)`.
+fn coverage(i: u32, start: f32, end: f32) -> f32 {
+    let i = i as f32;
+    ((i + 1.0).min(end) - i.max(start)).max(0.0)
+}
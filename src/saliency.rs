@@ -0,0 +1,72 @@
+use image::{DynamicImage, GenericImageView};
+use palette::Lab;
+
+use crate::color::{ColorMetric, to_lab};
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+// Spread of the center weight, as a fraction of the half-width/half-height.
+const CENTER_SIGMA: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct SaliencySettings {
+    pub enabled: bool,
+    /// 0.0–1.0; how much salient pixels are shielded from dithering noise.
+    pub amount: f32,
+    /// 0.0–1.0 mix between color contrast (0) and distance from center (1).
+    pub center_bias: f32,
+}
+
+impl Default for SaliencySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amount: 0.6,
+            center_bias: 0.5,
+        }
+    }
+}
+
+/// Per-pixel focus (row-major, 0.0–`amount`): a blend of a centered Gaussian
+/// and each pixel's color distance from the image mean, which picks out faces
+/// and emblems against plainer backgrounds.
+pub fn focus_map(img: &DynamicImage, settings: SaliencySettings) -> Vec<f32> {
+    let (w, h) = (IMAGE_WIDTH as f32, IMAGE_HEIGHT as f32);
+    let labs: Vec<Lab> = img
+        .pixels()
+        .map(|(_, _, pixel)| to_lab([pixel[0], pixel[1], pixel[2]]))
+        .collect();
+
+    let count = labs.len() as f32;
+    let mean = labs.iter().fold(Lab::new(0.0, 0.0, 0.0), |acc, lab| {
+        Lab::new(
+            acc.l + lab.l / count,
+            acc.a + lab.a / count,
+            acc.b + lab.b / count,
+        )
+    });
+    let contrast: Vec<f32> = labs
+        .iter()
+        .map(|lab| ColorMetric::Cie76.distance(*lab, mean))
+        .collect();
+    let max_contrast = contrast.iter().copied().fold(0.0, f32::max);
+
+    let bias = settings.center_bias.clamp(0.0, 1.0);
+    contrast
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let x = (i as u32 % IMAGE_WIDTH) as f32 + 0.5;
+            let y = (i as u32 / IMAGE_WIDTH) as f32 + 0.5;
+            let dx = (x - w / 2.0) / (w / 2.0);
+            let dy = (y - h / 2.0) / (h / 2.0);
+            let center = (-(dx * dx + dy * dy) / (2.0 * CENTER_SIGMA * CENTER_SIGMA)).exp();
+            let contrast = if max_contrast > 0.0 {
+                c / max_contrast
+            } else {
+                0.0
+            };
+
+            (bias * center + (1.0 - bias) * contrast) * settings.amount
+        })
+        .collect()
+}
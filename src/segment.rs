@@ -0,0 +1,238 @@
+//! Optional subject/background segmentation with the U²-Net (small) ONNX
+//! model. Built only with the `segmentation` feature; the model itself is
+//! downloaded on first use, and everything degrades to "unavailable" without
+//! either.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use image::{GrayImage, Rgba, RgbaImage};
+
+const MODEL_FILE: &str = "u2netp.onnx";
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct BackgroundRemoval {
+    pub enabled: bool,
+    /// Palette index the removed background is filled with.
+    pub background: usize,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum ModelStatus {
+    /// Built without the `segmentation` feature.
+    Unsupported,
+    Missing,
+    Downloading,
+    Ready,
+    Failed(String),
+}
+
+static STATUS: Mutex<Option<ModelStatus>> = Mutex::new(None);
+
+#[cfg(feature = "segmentation")]
+static SESSION: Mutex<Option<ort::session::Session>> = Mutex::new(None);
+
+fn model_path() -> PathBuf {
+    let base = std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("MageFlag").join(MODEL_FILE)
+}
+
+pub fn status() -> ModelStatus {
+    let mut status = STATUS.lock().unwrap();
+    status
+        .get_or_insert_with(|| {
+            if !cfg!(feature = "segmentation") {
+                ModelStatus::Unsupported
+            } else if model_path().exists() {
+                ModelStatus::Ready
+            } else {
+                ModelStatus::Missing
+            }
+        })
+        .clone()
+}
+
+fn set_status(status: ModelStatus) {
+    *STATUS.lock().unwrap() = Some(status);
+}
+
+/// Fetches the model in the background; `status` reports progress.
+pub fn download() {
+    if !matches!(status(), ModelStatus::Missing | ModelStatus::Failed(_)) {
+        return;
+    }
+    set_status(ModelStatus::Downloading);
+    std::thread::spawn(|| {
+        set_status(match fetch_model() {
+            Ok(()) => ModelStatus::Ready,
+            Err(error) => ModelStatus::Failed(error),
+        });
+    });
+}
+
+#[cfg(feature = "segmentation")]
+fn fetch_model() -> Result<(), String> {
+    use std::io::Read;
+
+    use ort::session::Session;
+
+    const MODEL_URL: &str =
+        "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2netp.onnx";
+    // The model is about 4.5 MB; anything much larger is not it.
+    const MODEL_MAX_BYTES: u64 = 16 * 1024 * 1024;
+
+    let path = model_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    // Download next to the target and rename once the runtime has accepted
+    // it, so an interrupted or garbled transfer never becomes the model.
+    let partial = path.with_extension("part");
+    let download = || -> Result<(), String> {
+        let response = ureq::get(MODEL_URL).call().map_err(|e| e.to_string())?;
+        let mut bytes = Vec::new();
+        response
+            .into_reader()
+            .take(MODEL_MAX_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| e.to_string())?;
+        if bytes.len() as u64 > MODEL_MAX_BYTES {
+            return Err("the download is too large to be the model".to_owned());
+        }
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_memory(&bytes))
+            .map_err(|e| format!("the download is not a usable model: {e}"))?;
+        std::fs::write(&partial, &bytes).map_err(|e| e.to_string())?;
+        std::fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+        *SESSION.lock().unwrap() = Some(session);
+        Ok(())
+    };
+    let result = download();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+#[cfg(not(feature = "segmentation"))]
+fn fetch_model() -> Result<(), String> {
+    Err("built without segmentation support".to_owned())
+}
+
+/// Foreground probability (0 = background, 255 = subject) at `source`'s
+/// size, or `None` when the model is unavailable or fails.
+#[cfg(feature = "segmentation")]
+pub fn mask(source: &RgbaImage) -> Option<GrayImage> {
+    use image::imageops::{self, FilterType};
+    use ort::session::Session;
+    use ort::value::Tensor;
+
+    // Side of the square the model runs at.
+    const MODEL_SIZE: u32 = 320;
+    // ImageNet normalization the model was trained with.
+    const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+    const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+    if status() != ModelStatus::Ready {
+        return None;
+    }
+
+    let mut session = SESSION.lock().unwrap();
+    if session.is_none() {
+        match Session::builder().and_then(|builder| builder.commit_from_file(model_path())) {
+            Ok(loaded) => *session = Some(loaded),
+            Err(error) => {
+                set_status(ModelStatus::Failed(error.to_string()));
+                return None;
+            }
+        }
+    }
+    let session = session.as_ref()?;
+
+    let small = imageops::resize(source, MODEL_SIZE, MODEL_SIZE, FilterType::Triangle);
+    let max = small
+        .pixels()
+        .flat_map(|p| [p[0], p[1], p[2]])
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+    let plane = (MODEL_SIZE * MODEL_SIZE) as usize;
+    let mut input = vec![0.0f32; 3 * plane];
+    for (i, pixel) in small.pixels().enumerate() {
+        for c in 0..3 {
+            input[c * plane + i] = (pixel[c] as f32 / max - MEAN[c]) / STD[c];
+        }
+    }
+
+    let size = MODEL_SIZE as usize;
+    let run = || -> ort::Result<Vec<f32>> {
+        let tensor = Tensor::from_array(([1usize, 3, size, size], input))?;
+        let outputs = session.run(ort::inputs![tensor]?)?;
+        let (_, data) = outputs[0].try_extract_raw_tensor::<f32>()?;
+        Ok(data.to_vec())
+    };
+    let output = match run() {
+        Ok(output) => output,
+        Err(error) => {
+            set_status(ModelStatus::Failed(error.to_string()));
+            return None;
+        }
+    };
+
+    // The first output is the finest side output; stretch it to 0–255.
+    let (lo, hi) = output[..plane]
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let range = (hi - lo).max(f32::EPSILON);
+    let small_mask = GrayImage::from_fn(MODEL_SIZE, MODEL_SIZE, |x, y| {
+        let v = output[(y * MODEL_SIZE + x) as usize];
+        image::Luma([((v - lo) / range * 255.0).round() as u8])
+    });
+    Some(imageops::resize(
+        &small_mask,
+        source.width(),
+        source.height(),
+        FilterType::Triangle,
+    ))
+}
+
+#[cfg(not(feature = "segmentation"))]
+pub fn mask(_source: &RgbaImage) -> Option<GrayImage> {
+    None
+}
+
+/// Fills everything the model calls background with the palette color,
+/// blending along the mask's soft edge, or fades it out when `cut_out` is
+/// set. Returns `None` when no mask could be made, leaving the source
+/// untouched.
+pub fn remove_background(
+    source: &RgbaImage,
+    settings: BackgroundRemoval,
+    palette: &[[u8; 3]],
+    cut_out: bool,
+) -> Option<RgbaImage> {
+    let mask = mask(source)?;
+    let background = palette[settings.background.min(palette.len() - 1)];
+
+    let mut out = source.clone();
+    for (pixel, weight) in out.pixels_mut().zip(mask.pixels()) {
+        let alpha = weight[0] as u32;
+        let Rgba([r, g, b, a]) = *pixel;
+        if cut_out {
+            pixel[3] = ((a as u32 * alpha + 127) / 255) as u8;
+            continue;
+        }
+        let blend =
+            |c: u8, bg: u8| ((c as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+        *pixel = Rgba([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+            a,
+        ]);
+    }
+    Some(out)
+}
@@ -0,0 +1,500 @@
+//! Source images from outside the clipboard bitmap.
+
+use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{
+    AnimationDecoder, DynamicImage, Frames, ImageDecoder, ImageFormat, ImageReader, RgbaImage,
+};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use winreg::RegKey;
+use winreg::enums::HKEY_CURRENT_USER;
+
+use crate::palettes::Palette;
+use crate::{SETTINGS_REGISTRY_PATH, svg, uv, wic};
+
+// Extensions offered by the open dialog; SVG is rasterized, HEIC and AVIF
+// go through WIC, the rest decode with the enabled `image` codecs.
+pub const EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "bmp", "gif", "webp", "tga", "tif", "tiff", "svg", "heic", "heif", "avif",
+];
+
+// Registered clipboard format browsers put copied markup under.
+const CLIPBOARD_HTML_FORMAT: &str = "HTML Format";
+
+// Give up on a download after this long.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+// Larger downloads are cut off; no flag source needs more.
+const FETCH_MAX_BYTES: u64 = 32 * 1024 * 1024;
+// Frames kept of an animation, by count and by pixels across all of them;
+// the rest are dropped so a long or huge one cannot exhaust memory.
+const ANIMATION_MAX_FRAMES: usize = 500;
+const ANIMATION_MAX_PIXELS: u64 = 64 * 1024 * 1024;
+
+// A watched file counts as fully written once it has had no events for
+// this long.
+const WATCH_SETTLE: Duration = Duration::from_millis(500);
+
+/// An image ready to become the source, with the ICC profile it carried.
+#[derive(Clone)]
+pub struct Incoming {
+    pub image: RgbaImage,
+    pub icc: Option<Vec<u8>>,
+    /// Shown in the UI as where the current source came from.
+    pub name: String,
+    /// Every frame, when the image is animated; `image` is the sharpest.
+    pub animation: Option<Animation>,
+    /// Palette indices `image` was drawn from, when it is decoded flag data.
+    pub indices: Option<Vec<usize>>,
+}
+
+impl Incoming {
+    pub fn new(image: RgbaImage, name: impl Into<String>) -> Self {
+        Self {
+            image,
+            icc: None,
+            name: name.into(),
+            animation: None,
+            indices: None,
+        }
+    }
+
+    /// Decoded flag data, drawn in `colors` for display.
+    pub fn decoded(indices: Vec<usize>, colors: &[[u8; 3]], name: impl Into<String>) -> Self {
+        let image = uv::render(&indices, colors);
+        Self {
+            indices: Some(indices),
+            ..Self::new(image, name)
+        }
+    }
+}
+
+/// The composited frames of a GIF, APNG or animated WebP.
+#[derive(Clone)]
+pub struct Animation {
+    pub frames: Vec<RgbaImage>,
+    /// Index of the frame with the most fine detail.
+    pub sharpest: usize,
+}
+
+impl Animation {
+    fn collect(frames: Frames) -> Option<Self> {
+        let mut kept = Vec::new();
+        let mut pixels = 0;
+        for frame in frames.take(ANIMATION_MAX_FRAMES) {
+            let frame = frame.ok()?.into_buffer();
+            pixels += frame.width() as u64 * frame.height() as u64;
+            if pixels > ANIMATION_MAX_PIXELS {
+                break;
+            }
+            kept.push(frame);
+        }
+        if kept.len() < 2 {
+            return None;
+        }
+        Some(Self {
+            sharpest: Self::sharpest(&kept),
+            frames: kept,
+        })
+    }
+
+    /// The frame with the most fine detail, by variance of the Laplacian of
+    /// its luma; motion-blurred frames score low.
+    fn sharpest(frames: &[RgbaImage]) -> usize {
+        let sharpness = |frame: &RgbaImage| {
+            let luma = DynamicImage::ImageRgba8(frame.clone()).to_luma32f();
+            let (w, h) = luma.dimensions();
+            let at = |x: u32, y: u32| luma.get_pixel(x, y).0[0];
+            let (mut sum, mut sum_sq, mut n) = (0.0f64, 0.0f64, 0.0f64);
+            for y in 1..h.saturating_sub(1) {
+                for x in 1..w.saturating_sub(1) {
+                    let laplacian = (at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1)
+                        - 4.0 * at(x, y)) as f64;
+                    sum += laplacian;
+                    sum_sq += laplacian * laplacian;
+                    n += 1.0;
+                }
+            }
+            if n == 0.0 {
+                0.0
+            } else {
+                sum_sq / n - (sum / n).powi(2)
+            }
+        };
+        frames
+            .iter()
+            .map(sharpness)
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(idx, _)| idx)
+    }
+}
+
+/// An image the clipboard points at instead of holding a bitmap.
+pub enum Reference {
+    File(PathBuf),
+    Url(String),
+    /// A `data:image/...;base64,` URI.
+    DataUri(String),
+    /// Copied SVG markup.
+    Svg(String),
+    /// The game's flag encoding as text.
+    FlagData(String),
+}
+
+impl Reference {
+    /// What distinguishes one clipboard change from the next.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Self::File(path) => path.as_os_str().as_encoded_bytes(),
+            Self::Url(text) | Self::DataUri(text) | Self::Svg(text) | Self::FlagData(text) => {
+                text.as_bytes()
+            }
+        }
+    }
+
+    /// Loads the image; flag data is drawn with `palette`.
+    pub fn load(&self, palette: &Palette) -> Result<Incoming, String> {
+        match self {
+            Self::File(path) => open(path),
+            Self::Url(url) => fetch(url),
+            Self::DataUri(uri) => decode_data_uri(uri),
+            Self::Svg(markup) => decode_bytes(markup.as_bytes().to_vec(), "SVG".to_owned()),
+            Self::FlagData(text) => {
+                let indices = uv::decode(text, palette)?;
+                Ok(Incoming::decoded(indices, &palette.colors, "Flag data"))
+            }
+        }
+    }
+}
+
+/// The first image among files copied in Explorer (CF_HDROP), SVG copied
+/// from a vector editor, the first `<img>` in copied HTML, or copied text
+/// that is SVG markup, flag data, a path to an image, an https URL or a
+/// data URI. Plain http is never offered for fetching.
+pub fn clipboard_reference(clipboard: &mut arboard::Clipboard) -> Option<Reference> {
+    let mut files = Vec::new();
+    let mut html = Vec::new();
+    let mut svg = Vec::new();
+    if let Ok(_open) = clipboard_win::Clipboard::new_attempts(10) {
+        let _ = clipboard_win::raw::get_file_list_path(&mut files);
+        for (name, buffer) in [
+            (CLIPBOARD_HTML_FORMAT, &mut html),
+            (svg::CLIPBOARD_FORMAT, &mut svg),
+        ] {
+            if let Some(format) = clipboard_win::raw::register_format(name)
+                && clipboard_win::raw::is_format_avail(format.get())
+            {
+                let _ = clipboard_win::raw::get_vec(format.get(), buffer);
+            }
+        }
+    }
+    if let Some(path) = files.into_iter().find(|path| is_image(path)) {
+        return Some(Reference::File(path));
+    }
+    if svg::sniff(&svg) {
+        return Some(Reference::Svg(String::from_utf8_lossy(&svg).into_owned()));
+    }
+    if let Some(src) = html_image(&String::from_utf8_lossy(&html)) {
+        match src.strip_prefix("file:///") {
+            Some(path) => return Some(Reference::File(PathBuf::from(path.replace("%20", " ")))),
+            None if src.starts_with("data:") => return Some(Reference::DataUri(src)),
+            None if src.starts_with("https://") => return Some(Reference::Url(src)),
+            None => {}
+        }
+    }
+
+    let text = clipboard.get_text().ok()?;
+    let text = text.trim().trim_matches('"');
+    if svg::sniff(text.as_bytes()) {
+        return Some(Reference::Svg(text.to_owned()));
+    }
+    if uv::sniff(text) {
+        return Some(Reference::FlagData(text.to_owned()));
+    }
+    if text.starts_with("data:image/") {
+        return Some(Reference::DataUri(text.to_owned()));
+    }
+    if text.starts_with("https://") && !text.contains(char::is_whitespace) {
+        return Some(Reference::Url(text.to_owned()));
+    }
+    let path = PathBuf::from(text);
+    (is_image(&path) && path.is_file()).then_some(Reference::File(path))
+}
+
+/// The `src` of the first `<img>` in a CF_HTML clipboard entry, made
+/// absolute against the page it was copied from.
+fn html_image(html: &str) -> Option<String> {
+    // CF_HTML starts with "Key:value" header lines; the copied markup
+    // follows, with `SourceURL` naming the page.
+    let source_url = html
+        .lines()
+        .take_while(|line| !line.starts_with('<'))
+        .find_map(|line| line.strip_prefix("SourceURL:"))
+        .map(str::trim);
+
+    let lower = html.to_ascii_lowercase();
+    let tag_start = lower.find("<img")?;
+    let tag_end = tag_start + lower[tag_start..].find('>')?;
+    let attr = tag_start + lower[tag_start..tag_end].find(" src=")? + " src=".len();
+
+    let rest = &html[attr..tag_end];
+    let src = match rest.chars().next()? {
+        quote @ ('"' | '\'') => rest[1..].split(quote).next()?,
+        _ => rest.split(char::is_whitespace).next()?,
+    };
+    let src = src.trim().replace("&amp;", "&");
+
+    Some(match source_url {
+        _ if src.contains("://") || src.starts_with("data:") => src,
+        _ if src.starts_with("//") => format!("https:{src}"),
+        Some(page) => {
+            let origin_end = page
+                .find("://")
+                .and_then(|scheme| page[scheme + 3..].find('/').map(|i| scheme + 3 + i))
+                .unwrap_or(page.len());
+            if src.starts_with('/') {
+                format!("{}{src}", &page[..origin_end])
+            } else {
+                let dir_end = page
+                    .rfind('/')
+                    .filter(|&i| i >= origin_end)
+                    .unwrap_or(origin_end);
+                format!("{}/{src}", &page[..dir_end])
+            }
+        }
+        None => return None,
+    })
+}
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        EXTENSIONS
+            .iter()
+            .any(|known| ext.eq_ignore_ascii_case(known))
+    })
+}
+
+/// Decodes an image file, keeping its embedded color profile.
+pub fn open(path: &Path) -> Result<Incoming, String> {
+    let name = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    decode_bytes(bytes, name)
+}
+
+/// Downloads an image over https. URLs without an image extension are
+/// still fetched, but only read past the headers when the server says it is
+/// an image, and only decoded when the bytes look like one. This blocks for
+/// up to `FETCH_TIMEOUT`, so callers run it on a thread of its own.
+pub fn fetch(url: &str) -> Result<Incoming, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{url}: {e}");
+    if !url.starts_with("https://") {
+        return Err(error(&"only https URLs are fetched"));
+    }
+    let response = ureq::get(url)
+        .timeout(FETCH_TIMEOUT)
+        .call()
+        .map_err(|e| error(&e))?;
+    if !response.content_type().starts_with("image/") {
+        return Err(error(&format!(
+            "not an image ({})",
+            response.content_type()
+        )));
+    }
+
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(FETCH_MAX_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| error(&e))?;
+    if !(svg::sniff(&bytes) || wic::sniff(&bytes) || image::guess_format(&bytes).is_ok()) {
+        return Err(error(&"the response is not an image"));
+    }
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(url)
+        .to_owned();
+    decode_bytes(bytes, name)
+}
+
+/// Decodes a base64 `data:image/...` URI.
+pub fn decode_data_uri(uri: &str) -> Result<Incoming, String> {
+    let (header, payload) = uri.split_once(',').ok_or("Data URI without a payload")?;
+    let media_type = header.strip_prefix("data:").unwrap_or(header);
+    let media_type = media_type
+        .strip_suffix(";base64")
+        .ok_or("Only base64 data URIs are supported")?;
+    let bytes = base64_decode(payload).ok_or("Data URI is not valid base64")?;
+    decode_bytes(bytes, format!("Data URI ({media_type})"))
+}
+
+/// Standard or URL-safe base64, ignoring whitespace and padding.
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let sextet = |c: u8| -> Option<u32> {
+        Some(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        } as u32)
+    };
+
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        bits = bits << 6 | sextet(c)?;
+        count += 1;
+        if count == 4 {
+            out.extend_from_slice(&bits.to_be_bytes()[1..]);
+            (bits, count) = (0, 0);
+        }
+    }
+    match count {
+        0 => {}
+        2 => out.push((bits >> 4) as u8),
+        3 => out.extend_from_slice(&((bits >> 2) as u16).to_be_bytes()),
+        _ => return None,
+    }
+    Some(out)
+}
+
+/// Watches a folder for image files being created or replaced.
+pub struct FolderWatch {
+    pub dir: PathBuf,
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Images still being written, with when they last changed.
+    pending: Vec<(PathBuf, Instant)>,
+}
+
+impl FolderWatch {
+    pub fn new(dir: &Path) -> Result<Self, String> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(|e| e.to_string())?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("{}: {e}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            _watcher: watcher,
+            events,
+            pending: Vec::new(),
+        })
+    }
+
+    /// The most recent image that has finished being written, if any.
+    pub fn poll(&mut self) -> Option<PathBuf> {
+        let now = Instant::now();
+        for event in self.events.try_iter().filter_map(Result::ok) {
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths.into_iter().filter(|path| is_image(path)) {
+                self.pending.retain(|(pending, _)| *pending != path);
+                self.pending.push((path, now));
+            }
+        }
+
+        let settled = self
+            .pending
+            .iter()
+            .rposition(|(_, changed)| now.duration_since(*changed) >= WATCH_SETTLE)?;
+        // Anything that settled before it is older news.
+        let (path, _) = self.pending.drain(..=settled).next_back()?;
+        Some(path)
+    }
+}
+
+/// The watched folder as saved before settings moved to the config file.
+pub fn restore_watch_folder() -> Option<PathBuf> {
+    let key = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(SETTINGS_REGISTRY_PATH)
+        .ok()?;
+    let dir: String = key.get_value("WatchFolder").ok()?;
+    Some(PathBuf::from(dir))
+}
+
+/// Rasterizes SVG markup or decodes a bitmap, whichever `bytes` hold.
+pub fn decode_bytes(bytes: Vec<u8>, name: String) -> Result<Incoming, String> {
+    if svg::sniff(&bytes) {
+        let image = svg::rasterize(&bytes).map_err(|e| format!("{name}: {e}"))?;
+        return Ok(Incoming::new(image, name));
+    }
+    if wic::sniff(&bytes) {
+        let (image, icc) = wic::decode(&bytes).map_err(|e| format!("{name}: {e}"))?;
+        return Ok(Incoming {
+            icc,
+            ..Incoming::new(image, name)
+        });
+    }
+    decode(ImageReader::new(Cursor::new(bytes)), name)
+}
+
+fn decode(reader: ImageReader<impl BufRead + Seek>, name: String) -> Result<Incoming, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{name}: {e}");
+    let reader = reader.with_guessed_format().map_err(|e| error(&e))?;
+    let format = reader.format();
+    let mut inner = reader.into_inner();
+
+    let start = inner.stream_position().map_err(|e| error(&e))?;
+    let animation = format.and_then(|format| animation(&mut inner, format));
+    inner.seek(SeekFrom::Start(start)).map_err(|e| error(&e))?;
+
+    let mut reader = ImageReader::new(inner);
+    if let Some(format) = format {
+        reader.set_format(format);
+    }
+    let mut decoder = reader.into_decoder().map_err(|e| error(&e))?;
+    let icc = decoder.icc_profile().ok().flatten();
+    let image = DynamicImage::from_decoder(decoder).map_err(|e| error(&e))?;
+
+    Ok(Incoming {
+        image: match &animation {
+            Some(animation) => animation.frames[animation.sharpest].clone(),
+            None => image.to_rgba8(),
+        },
+        icc,
+        name,
+        animation,
+        indices: None,
+    })
+}
+
+/// Every frame of an animated GIF, APNG or WebP; `None` for anything else.
+fn animation(reader: impl BufRead + Seek, format: ImageFormat) -> Option<Animation> {
+    match format {
+        ImageFormat::Gif => Animation::collect(GifDecoder::new(reader).ok()?.into_frames()),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(reader).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            Animation::collect(decoder.apng().ok()?.into_frames())
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(reader).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            Animation::collect(decoder.into_frames())
+        }
+        _ => None,
+    }
+}
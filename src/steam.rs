@@ -0,0 +1,60 @@
+//! Locating the game's install through Steam's registry entry and library
+//! list.
+
+use std::path::{Path, PathBuf};
+
+use winreg::RegKey;
+use winreg::enums::HKEY_CURRENT_USER;
+
+const STEAM_REGISTRY_PATH: &str = "Software\\Valve\\Steam";
+// Install folder name with spaces removed, compared case-insensitively.
+const GAME_FOLDER: &str = "magearena";
+
+/// The game's install folder in any Steam library, if it is installed.
+pub fn game_dir() -> Option<PathBuf> {
+    let steam: String = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(STEAM_REGISTRY_PATH)
+        .ok()?
+        .get_value("SteamPath")
+        .ok()?;
+    let steam = PathBuf::from(steam);
+
+    library_folders(&steam).into_iter().find_map(|library| {
+        std::fs::read_dir(library.join("steamapps").join("common"))
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.is_dir()
+                    && path.file_name().is_some_and(|name| {
+                        name.to_string_lossy()
+                            .replace(' ', "")
+                            .eq_ignore_ascii_case(GAME_FOLDER)
+                    })
+            })
+    })
+}
+
+/// Steam's own folder followed by every library listed in
+/// `libraryfolders.vdf`.
+fn library_folders(steam: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam.to_owned()];
+    let vdf = steam.join("steamapps").join("libraryfolders.vdf");
+    let Ok(text) = std::fs::read_to_string(vdf) else {
+        return libraries;
+    };
+
+    // Entries look like `"path"		"D:\\SteamLibrary"`.
+    for line in text.lines() {
+        let mut quoted = line.split('"').skip(1).step_by(2);
+        if quoted.next() == Some("path")
+            && let Some(path) = quoted.next()
+        {
+            let path = PathBuf::from(path.replace("\\\\", "\\"));
+            if !libraries.contains(&path) {
+                libraries.push(path);
+            }
+        }
+    }
+    libraries
+}
@@ -0,0 +1,47 @@
+//! SVG sources, rasterized with resvg well above flag size so downscaling
+//! works from clean edges.
+
+use image::RgbaImage;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+
+// Longer side of the rasterized image, in pixels.
+const RASTER_SIZE: f32 = 2048.0;
+
+// Registered clipboard format vector editors put copied SVG markup under.
+pub const CLIPBOARD_FORMAT: &str = "image/svg+xml";
+
+/// Whether `data` looks like SVG markup rather than a bitmap.
+pub fn sniff(data: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    head.starts_with('<') && head.contains("<svg")
+}
+
+/// Renders the document scaled so its longer side is `RASTER_SIZE`.
+pub fn rasterize(data: &[u8]) -> Result<RgbaImage, String> {
+    let mut options = Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = Tree::from_data(data, &options).map_err(|e| e.to_string())?;
+
+    let size = tree.size();
+    let scale = RASTER_SIZE / size.width().max(size.height());
+    let w = (size.width() * scale).round().max(1.0) as u32;
+    let h = (size.height() * scale).round().max(1.0) as u32;
+    let mut pixmap = Pixmap::new(w, h).ok_or("SVG has no area")?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    let rgba = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(w, h, rgba).ok_or_else(|| "SVG raster size mismatch".to_owned())
+}
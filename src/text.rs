@@ -0,0 +1,113 @@
+use crate::font::Font;
+use crate::overlay::Layer;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH, PALETTE_DARKEST, PALETTE_LIGHTEST};
+
+// Gap between the flag edge and top- or bottom-placed text, in pixels.
+const EDGE_MARGIN: i32 = 2;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Placement {
+    Top,
+    #[default]
+    Middle,
+    Bottom,
+}
+
+impl Placement {
+    pub const ALL: [Placement; 3] = [Placement::Top, Placement::Middle, Placement::Bottom];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Placement::Top => "Top",
+            Placement::Middle => "Middle",
+            Placement::Bottom => "Bottom",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct TextOverlay {
+    pub enabled: bool,
+    /// One or more lines, each centered horizontally.
+    pub text: String,
+    pub font: Font,
+    /// Integer pixel scale of the font.
+    pub scale: u32,
+    /// Palette index of the glyphs.
+    pub color: usize,
+    /// One-pixel ring around the glyphs, in `outline_color`.
+    pub outline: bool,
+    pub outline_color: usize,
+    pub placement: Placement,
+}
+
+impl Default for TextOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            text: String::new(),
+            font: Font::default(),
+            scale: 1,
+            color: PALETTE_LIGHTEST,
+            outline: true,
+            outline_color: PALETTE_DARKEST,
+            placement: Placement::default(),
+        }
+    }
+}
+
+/// Draws the caption into `layer`, outline first so the glyphs sit on top.
+pub fn draw(overlay: &TextOverlay, layer: &mut Layer) {
+    if !overlay.enabled || overlay.text.trim().is_empty() {
+        return;
+    }
+
+    let font = overlay.font;
+    let scale = overlay.scale.max(1) as i32;
+    let advance = (font.width() as i32 + 1) * scale;
+    let line_height = (font.height() as i32 + 1) * scale;
+
+    let lines: Vec<&str> = overlay.text.lines().collect();
+    let block_height = line_height * lines.len() as i32 - scale;
+    let top = match overlay.placement {
+        Placement::Top => EDGE_MARGIN,
+        Placement::Middle => (IMAGE_HEIGHT as i32 - block_height) / 2,
+        Placement::Bottom => IMAGE_HEIGHT as i32 - EDGE_MARGIN - block_height,
+    };
+
+    let mut pixels = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        let line_width = advance * line.chars().count() as i32 - scale;
+        let left = (IMAGE_WIDTH as i32 - line_width) / 2;
+        let y0 = top + row as i32 * line_height;
+
+        for (i, c) in line.chars().enumerate() {
+            let x0 = left + i as i32 * advance;
+            for (col, bits) in font.glyph(c).iter().enumerate() {
+                for bit in 0..font.height() as i32 {
+                    if bits >> bit & 1 == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            pixels.push((x0 + col as i32 * scale + dx, y0 + bit * scale + dy));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if overlay.outline {
+        for &(x, y) in &pixels {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    layer.set(x + dx, y + dy, overlay.outline_color);
+                }
+            }
+        }
+    }
+    for (x, y) in pixels {
+        layer.set(x, y, overlay.color);
+    }
+}
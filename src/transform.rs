@@ -0,0 +1,170 @@
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops};
+
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+/// Quarter turns and mirroring applied to the source before anything else.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Orientation {
+    /// Clockwise 90° turns, 0–3.
+    pub quarter_turns: u8,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Orientation {
+    pub fn is_identity(self) -> bool {
+        self == Self::default()
+    }
+
+    /// Turns the oriented image a quarter clockwise.
+    pub fn rotate_clockwise(&mut self) {
+        self.turn(1);
+    }
+
+    pub fn rotate_counterclockwise(&mut self) {
+        self.turn(3);
+    }
+
+    /// Adds `turns` clockwise quarter turns to the result. The flips come
+    /// after the rotation, and a single mirror reverses its direction.
+    fn turn(&mut self, turns: u8) {
+        let turns = if self.flip_horizontal ^ self.flip_vertical {
+            4 - turns
+        } else {
+            turns
+        };
+        self.quarter_turns = (self.quarter_turns + turns) % 4;
+    }
+}
+
+/// Rotates, then flips, `source`.
+pub fn orient(source: &RgbaImage, orientation: Orientation) -> RgbaImage {
+    let mut image = match orientation.quarter_turns % 4 {
+        1 => imageops::rotate90(source),
+        2 => imageops::rotate180(source),
+        3 => imageops::rotate270(source),
+        _ => source.clone(),
+    };
+    if orientation.flip_horizontal {
+        imageops::flip_horizontal_in_place(&mut image);
+    }
+    if orientation.flip_vertical {
+        imageops::flip_vertical_in_place(&mut image);
+    }
+    image
+}
+
+/// Builds a symmetrical flag from part of the image.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Symmetry {
+    #[default]
+    Off,
+    /// Left half reflected onto the right.
+    MirrorHorizontal,
+    /// Top half reflected onto the bottom.
+    MirrorVertical,
+    /// Top-left quadrant reflected into all four.
+    MirrorQuad,
+    /// Left half repeated on the right.
+    TileHorizontal,
+    /// Top-left quadrant repeated 2x2.
+    TileQuad,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 6] = [
+        Symmetry::Off,
+        Symmetry::MirrorHorizontal,
+        Symmetry::MirrorVertical,
+        Symmetry::MirrorQuad,
+        Symmetry::TileHorizontal,
+        Symmetry::TileQuad,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Symmetry::Off => "Off",
+            Symmetry::MirrorHorizontal => "Mirror left → right",
+            Symmetry::MirrorVertical => "Mirror top → bottom",
+            Symmetry::MirrorQuad => "Mirror quadrant",
+            Symmetry::TileHorizontal => "Tile left half",
+            Symmetry::TileQuad => "Tile quadrant",
+        }
+    }
+}
+
+impl Symmetry {
+    /// The pixel whose value `(x, y)` takes in a `w` x `h` image.
+    fn source(self, x: u32, y: u32, w: u32, h: u32) -> (u32, u32) {
+        let mirror = |v: u32, size: u32| if v < size / 2 { v } else { size - 1 - v };
+        let tile = |v: u32, size: u32| v % size.div_ceil(2);
+
+        match self {
+            Symmetry::Off => (x, y),
+            Symmetry::MirrorHorizontal => (mirror(x, w), y),
+            Symmetry::MirrorVertical => (x, mirror(y, h)),
+            Symmetry::MirrorQuad => (mirror(x, w), mirror(y, h)),
+            Symmetry::TileHorizontal => (tile(x, w), y),
+            Symmetry::TileQuad => (tile(x, w), tile(y, h)),
+        }
+    }
+}
+
+/// Rebuilds `img` from the part `symmetry` keeps.
+pub fn symmetrize(img: &DynamicImage, symmetry: Symmetry) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(w, h, |x, y| {
+        let (sx, sy) = symmetry.source(x, y, w, h);
+        img.get_pixel(sx, sy)
+    }))
+}
+
+/// Applies `symmetry` to row-major flag indices. Dithering of a symmetric
+/// image is not itself symmetric, so the encoder repeats this afterwards.
+pub fn symmetrize_indices(indices: &mut [usize], symmetry: Symmetry) {
+    let (w, h) = (IMAGE_WIDTH, IMAGE_HEIGHT);
+    let original = indices.to_vec();
+    for y in 0..h {
+        for x in 0..w {
+            let (sx, sy) = symmetry.source(x, y, w, h);
+            indices[(y * w + x) as usize] = original[(sy * w + sx) as usize];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 image with every pixel different, so any wrong turn shows.
+    fn sample() -> RgbaImage {
+        RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 0, 255]))
+    }
+
+    #[test]
+    fn rotating_turns_the_result_with_one_flip_on() {
+        for (flip_horizontal, flip_vertical) in
+            [(false, false), (true, false), (false, true), (true, true)]
+        {
+            for quarter_turns in 0..4 {
+                let before = Orientation {
+                    quarter_turns,
+                    flip_horizontal,
+                    flip_vertical,
+                };
+                let shown = orient(&sample(), before);
+
+                let mut clockwise = before;
+                clockwise.rotate_clockwise();
+                assert_eq!(orient(&sample(), clockwise), imageops::rotate90(&shown));
+
+                let mut counterclockwise = before;
+                counterclockwise.rotate_counterclockwise();
+                assert_eq!(
+                    orient(&sample(), counterclockwise),
+                    imageops::rotate270(&shown)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Reading the game's flag encoding back: comma-separated `u:v` texture
+//! coordinates, column by column from the bottom-left, each naming the
+//! palette cell sampled there.
+
+use image::{Rgba, RgbaImage};
+
+use crate::palettes::Palette;
+use crate::{IMAGE_HEIGHT, IMAGE_WIDTH};
+
+// Entries checked before text is treated as flag data.
+const SNIFF_ENTRIES: usize = 4;
+
+fn parse_entry(entry: &str) -> Option<(f32, f32)> {
+    let (u, v) = entry.trim().split_once(':')?;
+    Some((u.parse().ok()?, v.parse().ok()?))
+}
+
+/// Whether `text` starts like flag data rather than ordinary text.
+pub fn sniff(text: &str) -> bool {
+    let mut entries = text.trim().split(',');
+    entries
+        .by_ref()
+        .take(SNIFF_ENTRIES)
+        .all(|entry| parse_entry(entry).is_some())
+        && entries.next().is_some()
+}
+
+/// Row-major palette indices of the flag `text` encodes, taking each
+/// coordinate to the cell it falls in.
+pub fn decode(text: &str, palette: &Palette) -> Result<Vec<usize>, String> {
+    let entries: Vec<&str> = text.trim().trim_end_matches(',').split(',').collect();
+    let expected = (IMAGE_WIDTH * IMAGE_HEIGHT) as usize;
+    if entries.len() != expected {
+        return Err(format!(
+            "Flag data has {} cells; a flag has {expected}",
+            entries.len()
+        ));
+    }
+
+    let mut indices = vec![0; expected];
+    for (i, entry) in entries.iter().enumerate() {
+        let (u, v) =
+            parse_entry(entry).ok_or_else(|| format!("Flag data entry {} is “{entry}”", i + 1))?;
+        let col = ((u * palette.cols as f32) as u32).min(palette.cols - 1);
+        let row = palette.rows - 1 - ((v * palette.rows as f32) as u32).min(palette.rows - 1);
+        let idx = ((row * palette.cols + col) as usize).min(palette.colors.len() - 1);
+
+        // Entries run up each column in turn, starting at the bottom.
+        let x = i as u32 / IMAGE_HEIGHT;
+        let y = IMAGE_HEIGHT - 1 - i as u32 % IMAGE_HEIGHT;
+        indices[(y * IMAGE_WIDTH + x) as usize] = idx;
+    }
+    Ok(indices)
+}
+
+/// The flag as an image, each index drawn in its color from `colors`.
+pub fn render(indices: &[usize], colors: &[[u8; 3]]) -> RgbaImage {
+    RgbaImage::from_fn(IMAGE_WIDTH, IMAGE_HEIGHT, |x, y| {
+        let [r, g, b] = colors[indices[(y * IMAGE_WIDTH + x) as usize]];
+        Rgba([r, g, b, 255])
+    })
+}
@@ -0,0 +1,184 @@
+//! Webcam frames streamed on a background thread, with a preview panel to
+//! snap one as the source.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use eframe::egui;
+use egui::{ColorImage, TextureHandle, TextureOptions};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use nokhwa::pixel_format::RgbFormat;
+use nokhwa::utils::{ApiBackend, CameraIndex, RequestedFormat, RequestedFormatType};
+use nokhwa::{Camera, query};
+
+use crate::a11y;
+
+// Width the live preview is drawn at.
+const PREVIEW_WIDTH: f32 = 320.0;
+
+#[derive(Default)]
+struct Shared {
+    frame: Option<RgbaImage>,
+    error: Option<String>,
+}
+
+/// A camera streaming until dropped.
+struct Stream {
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Stream {
+    fn start(index: CameraIndex) -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_shared, thread_stop) = (Arc::clone(&shared), Arc::clone(&stop));
+
+        thread::spawn(move || {
+            let fail = |error: nokhwa::NokhwaError| {
+                thread_shared.lock().unwrap().error = Some(error.to_string());
+            };
+            let format =
+                RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate);
+            let mut camera = match Camera::new(index, format) {
+                Ok(camera) => camera,
+                Err(error) => return fail(error),
+            };
+            if let Err(error) = camera.open_stream() {
+                return fail(error);
+            }
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                let decoded = match camera
+                    .frame()
+                    .and_then(|frame| frame.decode_image::<RgbFormat>())
+                {
+                    Ok(decoded) => decoded,
+                    Err(error) => {
+                        fail(error);
+                        break;
+                    }
+                };
+                let (w, h) = (decoded.width(), decoded.height());
+                if let Some(rgb) = RgbImage::from_raw(w, h, decoded.into_raw()) {
+                    thread_shared.lock().unwrap().frame =
+                        Some(DynamicImage::ImageRgb8(rgb).to_rgba8());
+                }
+            }
+            let _ = camera.stop_stream();
+        });
+
+        Self { shared, stop }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Camera picker, live preview and snap button.
+#[derive(Default)]
+pub struct Panel {
+    cameras: Vec<(CameraIndex, String)>,
+    choice: usize,
+    stream: Option<Stream>,
+    /// The newest frame and its uploaded texture.
+    frame: Option<(RgbaImage, TextureHandle)>,
+    error: Option<String>,
+}
+
+impl Panel {
+    /// Stops the camera while the panel is not shown; it is started again
+    /// from the panel.
+    pub fn hide(&mut self) {
+        self.stream = None;
+        self.frame = None;
+    }
+
+    /// Draws the panel; returns the frame when "Snap" is clicked.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<RgbaImage> {
+        ui.horizontal(|ui| {
+            if ui.button("Find cameras").clicked() {
+                match query(ApiBackend::Auto) {
+                    Ok(found) => {
+                        self.cameras = found
+                            .iter()
+                            .map(|info| (info.index().clone(), info.human_name()))
+                            .collect();
+                        self.choice = 0;
+                        self.error = self
+                            .cameras
+                            .is_empty()
+                            .then(|| "No cameras found".to_owned());
+                    }
+                    Err(error) => self.error = Some(error.to_string()),
+                }
+            }
+            if !self.cameras.is_empty() {
+                egui::ComboBox::from_label("Camera")
+                    .selected_text(&self.cameras[self.choice.min(self.cameras.len() - 1)].1)
+                    .show_ui(ui, |ui| {
+                        for (i, (_, name)) in self.cameras.iter().enumerate() {
+                            ui.selectable_value(&mut self.choice, i, name);
+                        }
+                    });
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if self.stream.is_none() {
+                if ui
+                    .add_enabled(!self.cameras.is_empty(), egui::Button::new("Start"))
+                    .clicked()
+                {
+                    let (index, _) = &self.cameras[self.choice.min(self.cameras.len() - 1)];
+                    self.stream = Some(Stream::start(index.clone()));
+                    self.error = None;
+                }
+            } else if ui.button("Stop").clicked() {
+                self.hide();
+            }
+        });
+
+        if let Some(stream) = &self.stream {
+            let mut shared = stream.shared.lock().unwrap();
+            if let Some(error) = shared.error.take() {
+                self.error = Some(error);
+            }
+            if let Some(image) = shared.frame.take() {
+                let color = ColorImage::from_rgba_unmultiplied(
+                    [image.width() as usize, image.height() as usize],
+                    image.as_raw(),
+                );
+                let texture = match self.frame.take() {
+                    Some((_, mut texture)) => {
+                        texture.set(color, TextureOptions::LINEAR);
+                        texture
+                    }
+                    None => ui
+                        .ctx()
+                        .load_texture("webcam", color, TextureOptions::LINEAR),
+                };
+                self.frame = Some((image, texture));
+            }
+            ui.ctx().request_repaint();
+        }
+        if self.error.is_some() {
+            self.stream = None;
+        }
+        if let Some(error) = &self.error {
+            a11y::status(ui, error, true);
+        }
+
+        let (image, texture) = self.frame.as_ref()?;
+        let aspect = image.height() as f32 / image.width().max(1) as f32;
+        ui.image((
+            texture.id(),
+            egui::vec2(PREVIEW_WIDTH, PREVIEW_WIDTH * aspect),
+        ));
+        ui.button("Snap").clicked().then(|| image.clone())
+    }
+}
@@ -0,0 +1,90 @@
+//! HEIC and AVIF through the Windows Imaging Component, which decodes them
+//! with the HEIF and AV1 extensions Windows installs from the Store.
+
+use image::RgbaImage;
+use windows::Win32::Graphics::Imaging::{
+    CLSID_WICImagingFactory, GUID_WICPixelFormat32bppRGBA, IWICBitmapFrameDecode,
+    IWICImagingFactory, WICBitmapDitherTypeNone, WICBitmapPaletteTypeCustom,
+    WICColorContextProfile, WICDecodeMetadataCacheOnDemand,
+};
+use windows::Win32::System::Com::{
+    CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED, CoCreateInstance, CoInitializeEx,
+};
+
+// ISO base media `ftyp` brands of HEIF stills and sequences, HEVC- or
+// AV1-coded.
+const HEIF_BRANDS: &[&[u8; 4]] = &[
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1", b"avif", b"avis",
+];
+
+/// Whether `data` is a HEIF container (HEIC or AVIF).
+pub fn sniff(data: &[u8]) -> bool {
+    data.get(4..8) == Some(b"ftyp")
+        && data
+            .get(8..12)
+            .is_some_and(|brand| HEIF_BRANDS.iter().any(|known| known[..] == *brand))
+}
+
+/// The first image in `data` and its embedded ICC profile.
+pub fn decode(data: &[u8]) -> Result<(RgbaImage, Option<Vec<u8>>), String> {
+    let error = |e: windows::core::Error| match e.code().0 as u32 {
+        // WINCODEC_ERR_COMPONENTNOTFOUND: no codec for the container.
+        0x8898_2F50 => "needs the HEIF/AV1 Image Extensions from the Microsoft Store".to_owned(),
+        _ => e.message().to_string(),
+    };
+
+    unsafe {
+        // Already initialized on this thread is fine.
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        let factory: IWICImagingFactory =
+            CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)
+                .map_err(error)?;
+        let stream = factory.CreateStream().map_err(error)?;
+        stream.InitializeFromMemory(data).map_err(error)?;
+        let decoder = factory
+            .CreateDecoderFromStream(&stream, None, WICDecodeMetadataCacheOnDemand)
+            .map_err(error)?;
+        let frame = decoder.GetFrame(0).map_err(error)?;
+        let icc = profile(&factory, &frame);
+
+        let converter = factory.CreateFormatConverter().map_err(error)?;
+        converter
+            .Initialize(
+                &frame,
+                &GUID_WICPixelFormat32bppRGBA,
+                WICBitmapDitherTypeNone,
+                None,
+                0.0,
+                WICBitmapPaletteTypeCustom,
+            )
+            .map_err(error)?;
+        let (mut w, mut h) = (0, 0);
+        converter.GetSize(&mut w, &mut h).map_err(error)?;
+        let mut rgba = vec![0u8; w as usize * h as usize * 4];
+        converter
+            .CopyPixels(std::ptr::null(), w * 4, &mut rgba)
+            .map_err(error)?;
+
+        let image = RgbaImage::from_raw(w, h, rgba).ok_or("Decoded size mismatch")?;
+        Ok((image, icc))
+    }
+}
+
+/// The frame's embedded ICC profile, if it has one.
+fn profile(factory: &IWICImagingFactory, frame: &IWICBitmapFrameDecode) -> Option<Vec<u8>> {
+    unsafe {
+        let context = factory.CreateColorContext().ok()?;
+        let mut count = 0;
+        frame
+            .GetColorContexts(&mut [Some(context.clone())], &mut count)
+            .ok()?;
+        if count == 0 || context.GetType().ok()? != WICColorContextProfile {
+            return None;
+        }
+        let mut size = 0;
+        context.GetProfileBytes(&mut [], &mut size).ok()?;
+        let mut bytes = vec![0u8; size as usize];
+        context.GetProfileBytes(&mut bytes, &mut size).ok()?;
+        Some(bytes)
+    }
+}